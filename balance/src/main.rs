@@ -0,0 +1,114 @@
+//! Card set balance simulation
+//!
+//! Plays a game for every seed of the range with bots taking all the seats and reports how the
+//! cards fared. Games are split between all the available threads.
+
+use std::io::Write;
+use std::thread;
+
+use clap::Parser;
+use color_eyre::Result;
+use game::bot::{self, Greedy, Mcts, Random, Strategy};
+use game::{CardSet, Game, GameConfig, Rng, Setup};
+
+use crate::opt::{Bot, Format, Opt};
+use crate::stats::{Played, Stats};
+
+mod opt;
+mod stats;
+
+/// Plays the game with the bots, abandoning it after the number of actions
+fn play(setup: Setup, bots: &[Bot], iterations: usize, max_actions: usize) -> Result<Played> {
+    let seed = setup.seed;
+    let mut played = Played::new(Game::new(setup)?);
+    let mut rng = Rng::new(seed);
+
+    let mut strategies: Vec<Box<dyn Strategy>> = (0..played.game.players().len())
+        .map(|seat| -> Box<dyn Strategy> {
+            match bots[seat % bots.len()] {
+                Bot::Random => Box::new(Random::new(rng.next_u64())),
+                Bot::Greedy => Box::new(Greedy),
+                Bot::Mcts => Box::new(Mcts::new(iterations, rng.next_u64())),
+            }
+        })
+        .collect();
+
+    for _ in 0..max_actions {
+        let Some(player) = played.game.waiting_for() else {
+            break;
+        };
+
+        let strategy = &mut *strategies[player.seat()];
+        let action = bot::decide(strategy, &played.game, player, rng.next_u64());
+        let events = played.game.apply(player, action)?;
+        played.record(&events);
+    }
+
+    Ok(played)
+}
+
+fn main() -> Result<()> {
+    color_eyre::install()?;
+    let Opt {
+        cards,
+        config,
+        seeds,
+        players,
+        bot,
+        iterations,
+        max_actions,
+        format,
+        mut output,
+    } = Opt::parse();
+
+    let card_set = match &cards {
+        Some(dir) => CardSet::load_dir(dir)?,
+        None => CardSet::basic(),
+    };
+    let config: GameConfig = match &config {
+        Some(path) => toml::from_str(&std::fs::read_to_string(path)?)?,
+        None => GameConfig::default(),
+    };
+    let setup = |seed| Setup::new(card_set.clone(), players, seed).config(config.clone());
+
+    // Invalid setup fails once, not for every game
+    Game::new(setup(seeds.start))?;
+
+    let threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+    let chunk = (seeds.end - seeds.start).div_ceil(threads as u64);
+    let chunks = seeds
+        .clone()
+        .step_by(chunk as usize)
+        .map(|start| start..start.saturating_add(chunk).min(seeds.end));
+
+    let (card_set, setup, bot) = (&card_set, &setup, &bot);
+    let stats = thread::scope(|scope| {
+        let workers: Vec<_> = chunks
+            .map(|seeds| {
+                scope.spawn(move || -> Result<Stats> {
+                    let mut stats = Stats::new(card_set);
+                    for seed in seeds {
+                        stats.add(&play(setup(seed), bot, iterations, max_actions)?);
+                    }
+                    Ok(stats)
+                })
+            })
+            .collect();
+
+        workers
+            .into_iter()
+            .try_fold(Stats::new(card_set), |stats, worker| {
+                let worker = worker.join().expect("Simulation thread panicked");
+                Ok::<_, color_eyre::Report>(stats.merge(worker?))
+            })
+    })?;
+
+    let report = stats.report(card_set);
+    match format {
+        Format::Table => write!(output, "{}", report.table())?,
+        Format::Csv => write!(output, "{}", report.csv())?,
+        Format::Json => writeln!(output, "{}", serde_json::to_string_pretty(&report)?)?,
+    }
+
+    Ok(())
+}
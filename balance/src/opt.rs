@@ -0,0 +1,70 @@
+//! Command line options
+
+use std::ops::Range;
+use std::path::PathBuf;
+
+use clio::Output;
+
+#[derive(Clone, Debug, clap::Parser)]
+#[command(
+    name = "balance",
+    about = "Plays bot-vs-bot games over a card set and reports card statistics"
+)]
+pub struct Opt {
+    /// Card set directory, the basic card set if not given
+    #[arg(long)]
+    pub cards: Option<PathBuf>,
+    /// Rules variant file in TOML, the standard rules if not given
+    #[arg(long)]
+    pub config: Option<PathBuf>,
+    /// Range of the game seeds, one game is played for every seed
+    #[arg(short, long, value_parser = parse_seeds, default_value = "0..1000")]
+    pub seeds: Range<u64>,
+    /// Number of players in every game
+    #[arg(short, long, default_value_t = 2)]
+    pub players: usize,
+    /// Bots taking the seats in order, repeated if there are more seats than bots
+    #[arg(short, long, value_enum, default_values_t = [Bot::Greedy])]
+    pub bot: Vec<Bot>,
+    /// Search iterations of the tree search bot for every action
+    #[arg(long, default_value_t = 100)]
+    pub iterations: usize,
+    /// Actions after which the game is abandoned as unfinished
+    #[arg(long, default_value_t = 5000)]
+    pub max_actions: usize,
+    /// Report format
+    #[arg(short, long, value_enum, default_value_t = Format::Table)]
+    pub format: Format,
+    /// Report file path
+    #[arg(short, long, value_parser, default_value = "-")]
+    pub output: Output,
+}
+
+/// Strategy playing a seat
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Bot {
+    Random,
+    Greedy,
+    Mcts,
+}
+
+/// Format of the report
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    Table,
+    Csv,
+    Json,
+}
+
+/// Parses the `START..END` seed range
+fn parse_seeds(seeds: &str) -> Result<Range<u64>, String> {
+    let (start, end) = seeds
+        .split_once("..")
+        .ok_or_else(|| format!("Expected START..END, got {seeds}"))?;
+    let parse = |bound: &str| bound.parse::<u64>().map_err(|err| err.to_string());
+    let seeds = parse(start)?..parse(end)?;
+    match seeds.is_empty() {
+        true => Err(format!("Seed range {seeds:?} is empty")),
+        false => Ok(seeds),
+    }
+}
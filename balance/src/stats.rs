@@ -0,0 +1,369 @@
+//! Statistics collected over the simulated games
+
+use std::fmt::Write;
+
+use game::{CardSet, Event, Game, PlayerId, Purchase};
+use serde::Serialize;
+
+/// Outcome of a single simulated game
+#[derive(Debug, Clone)]
+pub struct Played {
+    /// Final state of the game
+    pub game: Game,
+    /// Player who took the first turn
+    pub first: PlayerId,
+    /// Copies of every card definition bought by every player, in the seat order
+    pub bought: Vec<Vec<u32>>,
+}
+
+impl Played {
+    /// Starts recording the game
+    pub fn new(game: Game) -> Self {
+        Self {
+            first: game.active(),
+            bought: vec![vec![0; game.card_set().len()]; game.players().len()],
+            game,
+        }
+    }
+
+    /// Records the events emitted by the game
+    pub fn record(&mut self, events: &[Event]) {
+        for event in events {
+            if let Event::CardBought {
+                player,
+                card,
+                source,
+            } = event
+            {
+                let def = match source {
+                    Purchase::Pile(def) => *def,
+                    Purchase::Market(_) => self.game.cards().def(*card),
+                };
+                self.bought[player.seat()][def.index()] += 1;
+            }
+        }
+    }
+}
+
+/// Counts of a single card over all the games
+#[derive(Debug, Clone, Copy, Default)]
+struct CardCounts {
+    /// Copies bought
+    copies: u64,
+    /// Players who bought the card at least once
+    buyers: u64,
+    /// Buyers who won their game
+    wins: u64,
+    /// Games in which anybody bought the card
+    games: u64,
+    /// Sum of the lengths of these games, in turns
+    turns: u64,
+}
+
+/// Counts collected over the games, ready to be merged with the counts of other games
+#[derive(Debug, Clone)]
+pub struct Stats {
+    /// Games played
+    games: u64,
+    /// Games abandoned before they ended
+    unfinished: u64,
+    /// Sum of the lengths of the finished games, in turns
+    turns: u64,
+    /// Seats taken in the finished games
+    seats: u64,
+    /// Finished games won by the first player
+    first_player_wins: u64,
+    /// Finished games without a winner
+    ties: u64,
+    /// Counts of every card definition
+    cards: Vec<CardCounts>,
+}
+
+impl Stats {
+    /// Empty statistics for the card set
+    pub fn new(card_set: &CardSet) -> Self {
+        Self {
+            games: 0,
+            unfinished: 0,
+            turns: 0,
+            seats: 0,
+            first_player_wins: 0,
+            ties: 0,
+            cards: vec![CardCounts::default(); card_set.len()],
+        }
+    }
+
+    /// Adds the game to the statistics. Unfinished games are only counted.
+    pub fn add(&mut self, played: &Played) {
+        self.games += 1;
+        let Some(result) = played.game.result() else {
+            self.unfinished += 1;
+            return;
+        };
+
+        let turns = u64::from(played.game.turn());
+        let winners = played.game.winners();
+        self.turns += turns;
+        self.seats += played.bought.len() as u64;
+        self.first_player_wins += winners.contains(&played.first) as u64;
+        self.ties += result.winner.is_none() as u64;
+
+        for (idx, counts) in self.cards.iter_mut().enumerate() {
+            let buyers: Vec<_> = played
+                .bought
+                .iter()
+                .enumerate()
+                .filter(|(_, bought)| bought[idx] > 0)
+                .map(|(seat, _)| PlayerId::new(seat))
+                .collect();
+            if buyers.is_empty() {
+                continue;
+            }
+
+            counts.copies += played.bought.iter().map(|b| u64::from(b[idx])).sum::<u64>();
+            counts.buyers += buyers.len() as u64;
+            counts.wins += buyers.iter().filter(|b| winners.contains(b)).count() as u64;
+            counts.games += 1;
+            counts.turns += turns;
+        }
+    }
+
+    /// Combines statistics of two sets of games
+    pub fn merge(mut self, other: Stats) -> Self {
+        self.games += other.games;
+        self.unfinished += other.unfinished;
+        self.turns += other.turns;
+        self.seats += other.seats;
+        self.first_player_wins += other.first_player_wins;
+        self.ties += other.ties;
+        for (counts, other) in self.cards.iter_mut().zip(other.cards) {
+            counts.copies += other.copies;
+            counts.buyers += other.buyers;
+            counts.wins += other.wins;
+            counts.games += other.games;
+            counts.turns += other.turns;
+        }
+        self
+    }
+
+    /// Turns the counts into the report. Only cards that can be bought are reported.
+    pub fn report(&self, card_set: &CardSet) -> Report {
+        let finished = self.games - self.unfinished;
+        let cards = card_set
+            .iter()
+            .zip(&self.cards)
+            .filter(|((_, def), _)| def.supply > 0 || def.market > 0)
+            .map(|((_, def), counts)| CardReport {
+                card: def.name.clone(),
+                cost: def.cost,
+                copies_per_game: ratio(counts.copies, finished).unwrap_or(0.0),
+                buy_rate: ratio(counts.buyers, self.seats).unwrap_or(0.0),
+                win_rate_when_bought: ratio(counts.wins, counts.buyers),
+                turns_when_bought: ratio(counts.turns, counts.games),
+            })
+            .collect();
+
+        Report {
+            games: self.games,
+            unfinished: self.unfinished,
+            average_turns: ratio(self.turns, finished),
+            first_player_win_rate: ratio(self.first_player_wins, finished),
+            fair_win_rate: ratio(finished, self.seats),
+            ties: self.ties,
+            cards,
+        }
+    }
+}
+
+/// Ratio of the counts, `None` if nothing was counted
+fn ratio(count: u64, of: u64) -> Option<f64> {
+    (of > 0).then(|| count as f64 / of as f64)
+}
+
+/// Statistics of a single card
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CardReport {
+    /// Card name
+    pub card: String,
+    /// Card cost
+    pub cost: u32,
+    /// Average copies bought in a game, by all the players together
+    pub copies_per_game: f64,
+    /// Share of the players who bought at least one copy
+    pub buy_rate: f64,
+    /// Share of the players who bought the card and won
+    pub win_rate_when_bought: Option<f64>,
+    /// Average length of the games in which the card was bought, in turns
+    pub turns_when_bought: Option<f64>,
+}
+
+/// Balance report of the card set. Rates and averages are over the finished games.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Report {
+    /// Games played
+    pub games: u64,
+    /// Games abandoned before they ended
+    pub unfinished: u64,
+    /// Average game length, in turns
+    pub average_turns: Option<f64>,
+    /// Share of the games won by the player taking the first turn
+    pub first_player_win_rate: Option<f64>,
+    /// Share of the games every player would win if seats made no difference
+    pub fair_win_rate: Option<f64>,
+    /// Games without a winner
+    pub ties: u64,
+    /// Statistics of every card that can be bought
+    pub cards: Vec<CardReport>,
+}
+
+impl Report {
+    /// Human readable table
+    pub fn table(&self) -> String {
+        let mut out = String::new();
+        let rate = |rate: Option<f64>| rate.map_or("-".to_owned(), |rate| format!("{rate:.3}"));
+
+        writeln!(
+            out,
+            "Games: {} ({} unfinished)",
+            self.games, self.unfinished
+        )
+        .unwrap();
+        writeln!(out, "Average length: {} turns", rate(self.average_turns)).unwrap();
+        writeln!(
+            out,
+            "First player win rate: {} (fair: {}), ties: {}",
+            rate(self.first_player_win_rate),
+            rate(self.fair_win_rate),
+            self.ties
+        )
+        .unwrap();
+        writeln!(out).unwrap();
+
+        let width = self.cards.iter().map(|c| c.card.len()).max().unwrap_or(0);
+        let width = width.max("Card".len());
+        writeln!(
+            out,
+            "{:width$}  {:>4}  {:>11}  {:>8}  {:>8}  {:>8}",
+            "Card", "Cost", "Copies/game", "Buy rate", "Win rate", "Turns"
+        )
+        .unwrap();
+        for card in &self.cards {
+            writeln!(
+                out,
+                "{:width$}  {:>4}  {:>11.3}  {:>8.3}  {:>8}  {:>8}",
+                card.card,
+                card.cost,
+                card.copies_per_game,
+                card.buy_rate,
+                rate(card.win_rate_when_bought),
+                rate(card.turns_when_bought),
+            )
+            .unwrap();
+        }
+
+        out
+    }
+
+    /// Card statistics as CSV, one card per row
+    pub fn csv(&self) -> String {
+        let mut out = "card,cost,copies_per_game,buy_rate,win_rate_when_bought,turns_when_bought\n"
+            .to_owned();
+        let rate = |rate: Option<f64>| rate.map_or(String::new(), |rate| rate.to_string());
+
+        for card in &self.cards {
+            writeln!(
+                out,
+                "{},{},{},{},{},{}",
+                csv_field(&card.card),
+                card.cost,
+                card.copies_per_game,
+                card.buy_rate,
+                rate(card.win_rate_when_bought),
+                rate(card.turns_when_bought),
+            )
+            .unwrap();
+        }
+
+        out
+    }
+}
+
+/// Quotes the CSV field if needed
+fn csv_field(field: &str) -> String {
+    match field.contains([',', '"', '\n']) {
+        true => format!("\"{}\"", field.replace('"', "\"\"")),
+        false => field.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use game::{CardDef, Setup};
+
+    fn card_set() -> CardSet {
+        CardSet::new([
+            CardDef::new("Copper", 0).resources(1).starter(7).supply(30),
+            CardDef::new("Estate", 0).points(1).starter(3).supply(2),
+            CardDef::new("Silver", 3).resources(2).supply(30),
+        ])
+    }
+
+    /// Game in which the first player buys both Estates, ending it
+    fn played() -> Played {
+        let game = Game::new(Setup::new(card_set(), 2, 1)).unwrap();
+        let mut played = Played::new(game);
+        let player = played.first;
+        let estate = played.game.card_set().find("Estate").unwrap();
+
+        let events = played.game.apply(player, game::Action::EndPhase).unwrap();
+        played.record(&events);
+        for _ in 0..2 {
+            let buy = game::Action::BuyCard(Purchase::Pile(estate));
+            let events = played.game.apply(player, buy).unwrap();
+            played.record(&events);
+        }
+        played
+    }
+
+    #[test]
+    fn reporting_games() {
+        let played = played();
+        assert!(played.game.is_over());
+
+        let mut stats = Stats::new(&card_set());
+        stats.add(&played);
+        let mut unfinished = Stats::new(&card_set());
+        unfinished.add(&Played::new(
+            Game::new(Setup::new(card_set(), 2, 1)).unwrap(),
+        ));
+        let report = stats.merge(unfinished).report(&card_set());
+
+        assert_eq!(report.games, 2);
+        assert_eq!(report.unfinished, 1);
+        assert_eq!(report.average_turns, Some(1.0));
+        assert_eq!(report.first_player_win_rate, Some(1.0));
+        assert_eq!(report.fair_win_rate, Some(0.5));
+        assert_eq!(
+            report.cards[1],
+            CardReport {
+                card: "Estate".to_owned(),
+                cost: 0,
+                copies_per_game: 2.0,
+                buy_rate: 0.5,
+                win_rate_when_bought: Some(1.0),
+                turns_when_bought: Some(1.0),
+            }
+        );
+        assert_eq!(report.cards[2].win_rate_when_bought, None);
+
+        let csv = report.csv();
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next(),
+            Some("card,cost,copies_per_game,buy_rate,win_rate_when_bought,turns_when_bought")
+        );
+        assert_eq!(lines.nth(1), Some("Estate,0,2,0.5,1,1"));
+        assert_eq!(csv_field("Mr. \"X\", Esq"), "\"Mr. \"\"X\"\", Esq\"");
+    }
+}
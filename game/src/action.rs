@@ -0,0 +1,510 @@
+//! Player actions and their validation
+
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::Game;
+use crate::card::{CardDefId, CardId, CardType};
+use crate::event::Event;
+use crate::phase::Phase;
+use crate::player::PlayerId;
+
+/// Where the bought card comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Purchase {
+    /// New card from the supply pile
+    Pile(CardDefId),
+    /// Card from the market row
+    Market(CardId),
+}
+
+/// Answer to a decision the player was asked for
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Choice {
+    /// Selected cards
+    Cards(Vec<CardId>),
+    /// Index of the selected option
+    Option(usize),
+}
+
+/// Everything a player can do in the game
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Action {
+    /// Plays the card from the hand
+    PlayCard(CardId),
+    /// Buys a card paying its cost
+    BuyCard(Purchase),
+    /// Moves on to the next phase of the turn
+    EndPhase,
+    /// Finishes the turn, skipping all the remaining phases
+    EndTurn,
+    /// Answers the pending decision
+    ResolveChoice(Choice),
+    /// Takes back the actions of the turn since the last irreversible one
+    Undo,
+    /// Leaves the game
+    Concede,
+}
+
+/// Reason why an action was rejected. Rejected actions never change the game state.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum Rejection {
+    #[error("Game is over")]
+    GameOver,
+    #[error("Player {0} is not in the game")]
+    UnknownPlayer(PlayerId),
+    #[error("Player {0} already conceded")]
+    Conceded(PlayerId),
+    #[error("It is not a turn of {0}")]
+    NotYourTurn(PlayerId),
+    #[error("Action not allowed in the {0:?} phase")]
+    WrongPhase(Phase),
+    #[error("Card {0} is not in hand")]
+    NotInHand(CardId),
+    #[error("Card {0} is not in the market")]
+    NotInMarket(CardId),
+    #[error("Supply pile is empty or missing")]
+    PileEmpty(CardDefId),
+    #[error("Not enough resources: {available} available, {cost} needed")]
+    NotEnoughResources { cost: u32, available: u32 },
+    #[error("No decision is pending")]
+    NoPendingChoice,
+    #[error("Pending decision must be resolved first")]
+    ChoicePending,
+    #[error("Pending decision is not up to {0}")]
+    NotYourChoice(PlayerId),
+    #[error("Choice does not answer the pending decision")]
+    InvalidChoice,
+    #[error("No reaction window is open")]
+    NoReactionWindow,
+    #[error("Nothing to undo since the last irreversible action")]
+    NothingToUndo,
+}
+
+impl Game {
+    /// Validates and performs the action on behalf of the player.
+    ///
+    /// Returns events describing what happened, in order. On rejection the state is not
+    /// modified.
+    pub fn apply(&mut self, player: PlayerId, action: Action) -> Result<Vec<Event>, Rejection> {
+        if self.is_over() {
+            return Err(Rejection::GameOver);
+        }
+
+        let state = self
+            .players
+            .get(player.seat())
+            .ok_or(Rejection::UnknownPlayer(player))?;
+
+        if state.conceded {
+            return Err(Rejection::Conceded(player));
+        }
+
+        let mut events = vec![];
+
+        match action {
+            Action::Concede => {
+                self.emit(&mut events, Event::Conceded { player });
+                self.settle_conceded(&mut events);
+                self.check_end(&mut events);
+                if player == self.active && !self.is_over() {
+                    self.end_turn(&mut events);
+                }
+            }
+            Action::ResolveChoice(choice) => {
+                let decision = self.pending.as_ref().ok_or(Rejection::NoPendingChoice)?;
+                if decision.player() != player {
+                    return Err(Rejection::NotYourChoice(player));
+                }
+                if !decision.request().accepts(&choice) {
+                    return Err(Rejection::InvalidChoice);
+                }
+
+                let choice = decision.request().arrange(choice);
+                self.decide(&mut events, choice);
+            }
+            Action::Undo => {
+                if player != self.active {
+                    return Err(Rejection::NotYourTurn(player));
+                }
+                if !self.can_undo() {
+                    return Err(Rejection::NothingToUndo);
+                }
+
+                self.emit(&mut events, Event::Undone { player });
+            }
+            _ if self.pending.is_some() => return Err(Rejection::ChoicePending),
+            _ if player != self.active => return Err(Rejection::NotYourTurn(player)),
+            _ if !self.phase.permits(&action) => return Err(Rejection::WrongPhase(self.phase)),
+            Action::PlayCard(card) => {
+                if !state.hand.contains(&card) {
+                    return Err(Rejection::NotInHand(card));
+                }
+
+                self.emit(&mut events, Event::CardPlayed { player, card });
+                if self.card(card).is(CardType::Base) {
+                    self.emit(&mut events, Event::BaseDeployed { player, card });
+                }
+
+                let effects = self.card(card).effects.clone();
+                self.resolve(&mut events, player, card, &effects);
+            }
+            Action::BuyCard(source) => {
+                let (card, cost) = match source {
+                    Purchase::Pile(def) => {
+                        if self.supply.pile(def).is_none_or(|pile| pile.count == 0) {
+                            return Err(Rejection::PileEmpty(def));
+                        }
+                        (self.cards.next_id(), self.card_set.get(def).cost)
+                    }
+                    Purchase::Market(card) => {
+                        if !self.supply.market().contains(&card) {
+                            return Err(Rejection::NotInMarket(card));
+                        }
+                        (card, self.card(card).cost)
+                    }
+                };
+
+                if cost > state.resources {
+                    return Err(Rejection::NotEnoughResources {
+                        cost,
+                        available: state.resources,
+                    });
+                }
+
+                self.emit(
+                    &mut events,
+                    Event::CardBought {
+                        player,
+                        card,
+                        source,
+                    },
+                );
+                self.refill_market(&mut events);
+                // Resolves the triggers of gaining the card
+                self.run(&mut events, VecDeque::new());
+            }
+            Action::EndPhase => match self.phase.next() {
+                Phase::Cleanup => self.end_turn(&mut events),
+                phase => self.emit(&mut events, Event::PhaseStarted { player, phase }),
+            },
+            Action::EndTurn => self.end_turn(&mut events),
+        }
+
+        self.check_end(&mut events);
+        Ok(events)
+    }
+
+    /// Draws up to `count` cards, turning the discard pile into the deck when it runs out
+    pub(crate) fn draw(&mut self, events: &mut Vec<Event>, player: PlayerId, count: usize) {
+        for _ in 0..count {
+            let state = &self.players[player.seat()];
+            if state.deck.is_empty() {
+                if state.discard.is_empty() {
+                    return;
+                }
+                self.emit(events, Event::DeckReshuffled { player });
+            }
+
+            let card = *self.players[player.seat()].deck.last().unwrap();
+            self.emit(events, Event::CardDrawn { player, card });
+        }
+    }
+
+    /// Reveals market deck cards until the market row is full
+    pub(crate) fn refill_market(&mut self, events: &mut Vec<Event>) {
+        while self.supply.market().len() < self.config.market_size {
+            let Some(&card) = self.supply.market_deck().last() else {
+                return;
+            };
+            self.emit(events, Event::MarketRefilled { card });
+        }
+    }
+
+    /// Moves through the remaining phases of the turn to cleanup and resolves the triggers at
+    /// the end of the turn. Once nothing is left to resolve, the turn is over.
+    fn end_turn(&mut self, events: &mut Vec<Event>) {
+        let player = self.active;
+        while self.phase != Phase::Cleanup {
+            let phase = self.phase.next();
+            self.emit(events, Event::PhaseStarted { player, phase });
+        }
+
+        self.run(events, VecDeque::new());
+        self.proceed(events);
+    }
+
+    /// Finishes the turn in cleanup, where the active player discards and draws their new hand,
+    /// then starts the next player's turn
+    fn next_turn(&mut self, events: &mut Vec<Event>) {
+        let player = self.active;
+        self.emit(events, Event::TurnEnded { player });
+        self.draw(events, player, self.config.hand_size);
+
+        let next = self.next_player(player);
+        let turn = self.turn + 1;
+        self.emit(events, Event::TurnStarted { player: next, turn });
+        self.start_turn(events);
+    }
+
+    /// Resolves the triggers at the start of the active player's turn and moves on to the action
+    /// phase
+    pub(crate) fn start_turn(&mut self, events: &mut Vec<Event>) {
+        self.run(events, VecDeque::new());
+        self.proceed(events);
+    }
+
+    /// Moves the turn on once nothing is left to resolve: from its start to the action phase,
+    /// and from the cleanup to the next player's turn
+    pub(crate) fn proceed(&mut self, events: &mut Vec<Event>) {
+        if self.pending.is_some() {
+            return;
+        }
+
+        let player = self.active;
+        match self.phase {
+            Phase::Start => self.emit(
+                events,
+                Event::PhaseStarted {
+                    player,
+                    phase: Phase::Action,
+                },
+            ),
+            Phase::Cleanup => self.next_turn(events),
+            Phase::Action | Phase::Buy => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::card::{CardDef, CardSet};
+    use crate::test_util::{game, p};
+
+    fn card_set() -> CardSet {
+        CardSet::new([
+            CardDef::new("Copper", 0).resources(1).starter(7).supply(30),
+            CardDef::new("Estate", 2).starter(3).supply(8),
+            CardDef::new("Smithy", 4).market(4),
+            CardDef::new("Village", 2).market(4),
+        ])
+    }
+
+    fn play_hand(game: &mut Game, player: PlayerId) -> u32 {
+        let hand = game.player(player).hand().to_vec();
+        for card in hand {
+            game.apply(player, Action::PlayCard(card)).unwrap();
+        }
+        game.player(player).resources()
+    }
+
+    #[test]
+    fn playing_cards() {
+        let mut game = game(card_set());
+        let card = game.player(p(0)).hand()[0];
+        let resources = if game.card(card).name == "Copper" {
+            1
+        } else {
+            0
+        };
+
+        let events = game.apply(p(0), Action::PlayCard(card)).unwrap();
+        assert_eq!(events[0], Event::CardPlayed { player: p(0), card });
+        assert_eq!(game.player(p(0)).in_play(), [card]);
+        assert_eq!(game.player(p(0)).resources(), resources);
+
+        assert_eq!(
+            game.apply(p(0), Action::PlayCard(card)),
+            Err(Rejection::NotInHand(card))
+        );
+    }
+
+    #[test]
+    fn only_active_player_acts() {
+        let mut game = game(card_set());
+        let card = game.player(p(1)).hand()[0];
+
+        assert_eq!(
+            game.apply(p(1), Action::PlayCard(card)),
+            Err(Rejection::NotYourTurn(p(1)))
+        );
+        assert_eq!(
+            game.apply(p(1), Action::EndTurn),
+            Err(Rejection::NotYourTurn(p(1)))
+        );
+        assert_eq!(
+            game.apply(p(2), Action::EndTurn),
+            Err(Rejection::UnknownPlayer(p(2)))
+        );
+    }
+
+    #[test]
+    fn buying_cards() {
+        let mut game = game(card_set());
+        let estate = game.card_set().find("Estate").unwrap();
+        let resources = play_hand(&mut game, p(0));
+        assert!(resources >= 2);
+        game.apply(p(0), Action::EndPhase).unwrap();
+
+        let events = game
+            .apply(p(0), Action::BuyCard(Purchase::Pile(estate)))
+            .unwrap();
+        let [Event::CardBought { card, .. }] = events[..] else {
+            panic!("Unexpected events: {events:?}");
+        };
+        assert_eq!(game.card(card).name, "Estate");
+        assert_eq!(game.player(p(0)).discard(), [card]);
+        assert_eq!(game.player(p(0)).resources(), resources - 2);
+        assert_eq!(game.supply().pile(estate).unwrap().count, 7);
+    }
+
+    #[test]
+    fn buying_from_market() {
+        let mut game = game(card_set());
+        play_hand(&mut game, p(0));
+        game.apply(p(0), Action::EndPhase).unwrap();
+        let expensive = game
+            .supply()
+            .market()
+            .iter()
+            .copied()
+            .find(|card| game.card(*card).cost > game.player(p(0)).resources());
+
+        if let Some(card) = expensive {
+            let before = game.clone();
+            assert!(matches!(
+                game.apply(p(0), Action::BuyCard(Purchase::Market(card))),
+                Err(Rejection::NotEnoughResources { .. })
+            ));
+            assert_eq!(game, before);
+        }
+
+        let card = game
+            .supply()
+            .market()
+            .iter()
+            .copied()
+            .find(|card| game.card(*card).cost <= game.player(p(0)).resources())
+            .unwrap();
+        let refill = *game.supply().market_deck().last().unwrap();
+
+        let events = game
+            .apply(p(0), Action::BuyCard(Purchase::Market(card)))
+            .unwrap();
+        assert_eq!(
+            events,
+            [
+                Event::CardBought {
+                    player: p(0),
+                    card,
+                    source: Purchase::Market(card)
+                },
+                Event::MarketRefilled { card: refill }
+            ]
+        );
+        assert!(game.supply().market().contains(&refill));
+        assert_eq!(
+            game.apply(p(0), Action::BuyCard(Purchase::Market(card))),
+            Err(Rejection::NotInMarket(card))
+        );
+    }
+
+    #[test]
+    fn ending_turn() {
+        let mut game = game(card_set());
+        play_hand(&mut game, p(0));
+
+        let events = game.apply(p(0), Action::EndTurn).unwrap();
+        assert_eq!(
+            events[..3],
+            [
+                Event::PhaseStarted {
+                    player: p(0),
+                    phase: Phase::Buy
+                },
+                Event::PhaseStarted {
+                    player: p(0),
+                    phase: Phase::Cleanup
+                },
+                Event::TurnEnded { player: p(0) },
+            ]
+        );
+        assert_eq!(
+            events[events.len() - 2..],
+            [
+                Event::TurnStarted {
+                    player: p(1),
+                    turn: 2
+                },
+                Event::PhaseStarted {
+                    player: p(1),
+                    phase: Phase::Action
+                }
+            ]
+        );
+
+        let player = game.player(p(0));
+        assert_eq!(player.hand().len(), game.config().hand_size);
+        assert!(player.in_play().is_empty());
+        assert_eq!(player.resources(), 0);
+        assert_eq!(game.active(), p(1));
+
+        game.apply(p(1), Action::EndTurn).unwrap();
+        let events = game.apply(p(0), Action::EndTurn).unwrap();
+        assert!(events.contains(&Event::DeckReshuffled { player: p(0) }));
+        assert_eq!(game.player(p(0)).hand().len(), game.config().hand_size);
+    }
+
+    #[test]
+    fn phases_restrict_actions() {
+        let mut game = game(card_set());
+        let card = game.player(p(0)).hand()[0];
+        let estate = game.card_set().find("Estate").unwrap();
+        assert_eq!(game.phase(), Phase::Action);
+
+        assert_eq!(
+            game.apply(p(0), Action::BuyCard(Purchase::Pile(estate))),
+            Err(Rejection::WrongPhase(Phase::Action))
+        );
+
+        let events = game.apply(p(0), Action::EndPhase).unwrap();
+        assert_eq!(
+            events,
+            [Event::PhaseStarted {
+                player: p(0),
+                phase: Phase::Buy
+            }]
+        );
+        assert_eq!(
+            game.apply(p(0), Action::PlayCard(card)),
+            Err(Rejection::WrongPhase(Phase::Buy))
+        );
+
+        game.apply(p(0), Action::EndPhase).unwrap();
+        assert_eq!(game.active(), p(1));
+        assert_eq!(game.phase(), Phase::Action);
+        assert_eq!(game.turn(), 2);
+    }
+
+    #[test]
+    fn conceding() {
+        let mut game = game(card_set());
+
+        assert_eq!(
+            game.apply(p(0), Action::ResolveChoice(Choice::Option(0))),
+            Err(Rejection::NoPendingChoice)
+        );
+
+        let events = game.apply(p(1), Action::Concede).unwrap();
+        assert!(matches!(
+            &events[..],
+            [Event::Conceded { player }, Event::GameEnded { result }]
+                if *player == p(1) && result.winner == Some(p(0))
+        ));
+        assert!(game.is_over());
+        assert_eq!(game.apply(p(0), Action::EndTurn), Err(Rejection::GameOver));
+    }
+}
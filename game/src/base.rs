@@ -0,0 +1,165 @@
+//! Bases - cards staying in play across turns
+//!
+//! A played base is deployed to its owner's `Bases` zone instead of being discarded at the end of
+//! the turn. While it stays there, its triggers stay registered and keep firing. Bases have
+//! durability: attacks wear them down, and once a base takes as much damage as its durability, it
+//! is destroyed and goes to its owner's discard pile.
+
+use crate::Game;
+use crate::card::CardId;
+use crate::event::Event;
+use crate::player::PlayerId;
+
+impl Game {
+    /// Durability the base in play has left
+    pub fn durability(&self, card: CardId) -> u32 {
+        let damage = self
+            .players
+            .iter()
+            .find_map(|player| player.damage.get(&card))
+            .copied()
+            .unwrap_or_default();
+        self.card(card).durability.saturating_sub(damage)
+    }
+
+    /// Deals the damage to the player's bases, guards first and the others in the order they were
+    /// deployed, destroying the ones worn out. Only guards are hit unless `all` is set. Bases
+    /// without durability are never hit. Returns the damage left over.
+    pub(crate) fn damage_bases(
+        &mut self,
+        events: &mut Vec<Event>,
+        player: PlayerId,
+        mut amount: u32,
+        all: bool,
+    ) -> u32 {
+        let bases = &self.player(player).bases;
+        let guards = bases.iter().filter(|card| self.card(**card).guard);
+        let others = bases.iter().filter(|card| all && !self.card(**card).guard);
+        let hit: Vec<_> = guards
+            .chain(others)
+            .copied()
+            .filter(|card| self.card(*card).durability > 0)
+            .collect();
+
+        for card in hit {
+            if amount == 0 {
+                break;
+            }
+
+            let left = self.durability(card);
+            let dealt = amount.min(left);
+            amount -= dealt;
+            self.emit(
+                events,
+                Event::BaseDamaged {
+                    player,
+                    card,
+                    amount: dealt,
+                },
+            );
+            if dealt == left {
+                self.emit(events, Event::BaseDestroyed { player, card });
+            }
+        }
+
+        amount
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Zone;
+    use crate::action::Action;
+    use crate::card::{CardDef, CardSet, CardType};
+    use crate::effect::Effect;
+    use crate::phase::Phase;
+    use crate::test_util::{game, give, p};
+    use crate::trigger::When;
+
+    fn card_set() -> CardSet {
+        CardSet::new([
+            CardDef::new("Copper", 0).resources(1).starter(7).supply(30),
+            CardDef::new("Estate", 0).starter(3).supply(8),
+            CardDef::new("Trading Post", 3)
+                .types([CardType::Base])
+                .durability(3)
+                .trigger(When::TurnStart, [Effect::Resources(1)]),
+            CardDef::new("Outpost", 4)
+                .types([CardType::Base])
+                .durability(2)
+                .guard(),
+            CardDef::new("Catapult", 5)
+                .types([CardType::Action, CardType::Attack])
+                .effect(Effect::Damage(3)),
+            CardDef::new("Siege", 5)
+                .types([CardType::Action, CardType::Attack])
+                .effect(Effect::Demolish(2)),
+        ])
+    }
+
+    #[test]
+    fn bases_stay_in_play_and_trigger() {
+        let mut game = game(card_set());
+        let post = give(&mut game, p(0), "Trading Post");
+        let events = game.apply(p(0), Action::PlayCard(post)).unwrap();
+        assert_eq!(
+            events,
+            [
+                Event::CardPlayed {
+                    player: p(0),
+                    card: post
+                },
+                Event::BaseDeployed {
+                    player: p(0),
+                    card: post
+                },
+            ]
+        );
+        assert_eq!(game.player(p(0)).zone(Zone::Bases), [post]);
+        assert_eq!(game.triggers().len(), 1);
+
+        game.apply(p(0), Action::EndTurn).unwrap();
+        assert_eq!(game.player(p(0)).bases(), [post]);
+        assert_eq!(game.player(p(0)).resources(), 0);
+
+        let events = game.apply(p(1), Action::EndTurn).unwrap();
+        assert!(events.contains(&Event::ResourcesGained {
+            player: p(0),
+            amount: 1
+        }));
+        assert_eq!(game.phase(), Phase::Action);
+        assert_eq!(game.player(p(0)).resources(), 1);
+
+        let replayed = Game::restore(&game.snapshot()).unwrap();
+        assert_eq!(replayed.triggers(), game.triggers());
+    }
+
+    #[test]
+    fn attacks_hit_guards_first() {
+        let mut game = game(card_set());
+        let outpost = give(&mut game, p(0), "Outpost");
+        let post = give(&mut game, p(0), "Trading Post");
+        game.apply(p(0), Action::PlayCard(post)).unwrap();
+        game.apply(p(0), Action::PlayCard(outpost)).unwrap();
+        game.apply(p(0), Action::EndTurn).unwrap();
+        let health = game.player(p(0)).health();
+
+        // Outpost absorbs 2 damage and is destroyed, the rest hits the player
+        let catapult = give(&mut game, p(1), "Catapult");
+        game.apply(p(1), Action::PlayCard(catapult)).unwrap();
+        assert_eq!(game.player(p(0)).bases(), [post]);
+        assert!(game.player(p(0)).discard().contains(&outpost));
+        assert_eq!(game.player(p(0)).health(), health - 1);
+
+        // Sieges wear down any base
+        let siege = give(&mut game, p(1), "Siege");
+        game.apply(p(1), Action::PlayCard(siege)).unwrap();
+        assert_eq!(game.durability(post), 1);
+        let siege = give(&mut game, p(1), "Siege");
+        game.apply(p(1), Action::PlayCard(siege)).unwrap();
+        assert_eq!(game.player(p(0)).bases(), []);
+        assert_eq!(game.triggers(), []);
+        assert_eq!(game.player(p(0)).health(), health - 1);
+    }
+}
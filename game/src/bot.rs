@@ -0,0 +1,173 @@
+//! Headless bot players
+//!
+//! A `Strategy` picks the action for a player. It never sees more than the player does: it is
+//! given the player's view, and a full game matching it with every card hidden from the player
+//! dealt at random. Strategies simulating the game play on this determinized copy.
+
+use crate::Game;
+use crate::action::Action;
+use crate::player::PlayerId;
+use crate::rng::Rng;
+use crate::team::Shared;
+use crate::view::PlayerView;
+
+mod greedy;
+mod mcts;
+mod random;
+
+pub use greedy::Greedy;
+pub use mcts::Mcts;
+pub use random::Random;
+
+/// Way of playing the game
+pub trait Strategy {
+    /// Picks one of the legal actions of the view. `game` is a possible state of the whole game
+    /// matching the view.
+    fn choose(&mut self, view: &PlayerView, game: &Game) -> Action;
+}
+
+/// Asks the strategy for the action of the player the game waits for, showing it only what the
+/// player is allowed to see. The seed deals the cards hidden from the player.
+pub fn decide(strategy: &mut dyn Strategy, game: &Game, player: PlayerId, seed: u64) -> Action {
+    assert_eq!(
+        game.waiting_for(),
+        Some(player),
+        "Only the player the game waits for decides"
+    );
+    strategy.choose(&game.view(player), &game.determinize(player, seed))
+}
+
+impl Game {
+    /// Player the game waits for - the one answering the pending decision, or the active one.
+    /// `None` once the game is over.
+    pub fn waiting_for(&self) -> Option<PlayerId> {
+        if self.is_over() {
+            return None;
+        }
+
+        Some(self.pending.as_ref().map_or(self.active, |d| d.player()))
+    }
+
+    /// Copy of the game as the viewer could imagine it: all the decks, the market deck and the
+    /// hands hidden from the viewer are shuffled, and the RNG is reseeded, so the copy is
+    /// indistinguishable from the game in the viewer's view. The undo checkpoint is dropped, as it
+    /// would reveal the cards as they were.
+    pub fn determinize(&self, viewer: PlayerId, seed: u64) -> Game {
+        let mut game = self.clone();
+        let mut rng = Rng::new(seed);
+
+        for player in self.player_ids() {
+            let state = &mut game.players[player.seat()];
+            if self.shares(viewer, player, Shared::Hand) {
+                rng.shuffle(&mut state.deck);
+                continue;
+            }
+
+            let hand = state.hand.len();
+            let mut hidden = std::mem::take(&mut state.hand);
+            hidden.append(&mut state.deck);
+            rng.shuffle(&mut hidden);
+            state.deck = hidden.split_off(hand);
+            state.hand = hidden;
+        }
+
+        game.supply.shuffle_market_deck(&mut rng);
+        game.rng = Rng::new(rng.next_u64());
+        game.checkpoint = None;
+        game
+    }
+}
+
+/// Actions worth considering - all the legal ones but undoing and conceding, unless nothing else
+/// is left
+fn playable(actions: &[Action]) -> Vec<Action> {
+    let playable: Vec<_> = actions
+        .iter()
+        .filter(|action| !matches!(action, Action::Undo | Action::Concede))
+        .cloned()
+        .collect();
+
+    match playable.is_empty() {
+        true => actions.to_vec(),
+        false => playable,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Setup;
+    use crate::card::CardSet;
+
+    /// Plays the game with a strategy for every seat, up to the number of actions
+    fn play(game: &mut Game, strategies: &mut [Box<dyn Strategy>], actions: usize) {
+        let mut rng = Rng::new(7);
+        for _ in 0..actions {
+            let Some(player) = game.waiting_for() else {
+                return;
+            };
+
+            let strategy = &mut *strategies[player.seat()];
+            let action = decide(strategy, game, player, rng.next_u64());
+            game.apply(player, action).unwrap();
+        }
+    }
+
+    #[test]
+    fn determinized_game_looks_the_same() {
+        let mut game = Game::new(Setup::new(CardSet::basic(), 3, 4)).unwrap();
+        let mut strategies: Vec<Box<dyn Strategy>> =
+            (0..3).map(|_| Box::new(Greedy) as _).collect();
+        play(&mut game, &mut strategies, 30);
+        let viewer = PlayerId::new(0);
+
+        let determinized = game.determinize(viewer, 11);
+        assert_eq!(determinized.view(viewer), game.view(viewer));
+        assert_eq!(
+            determinized.player(viewer).hand(),
+            game.player(viewer).hand()
+        );
+        assert_ne!(determinized, game);
+
+        let other = PlayerId::new(1);
+        let mut hidden = game.player(other).hand().to_vec();
+        hidden.extend(game.player(other).deck());
+        let mut dealt = determinized.player(other).hand().to_vec();
+        dealt.extend(determinized.player(other).deck());
+        hidden.sort();
+        dealt.sort();
+        assert_eq!(dealt, hidden);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the player the game waits for decides")]
+    fn only_the_waiting_player_decides() {
+        let game = Game::new(Setup::new(CardSet::basic(), 2, 4)).unwrap();
+        let other = game.next_player(game.active());
+        decide(&mut Greedy, &game, other, 1);
+    }
+
+    #[test]
+    fn bots_finish_the_game() {
+        let mut game = Game::new(Setup::new(CardSet::basic(), 3, 5)).unwrap();
+        let mut strategies: Vec<Box<dyn Strategy>> =
+            vec![Box::new(Greedy), Box::new(Random::new(1)), Box::new(Greedy)];
+        play(&mut game, &mut strategies, 5000);
+
+        assert!(game.is_over());
+        let winner = game.result().unwrap().winner.unwrap();
+        assert_ne!(winner, PlayerId::new(1));
+    }
+
+    #[test]
+    fn tree_search_plays_legal_actions() {
+        let mut game = Game::new(Setup::new(CardSet::basic(), 2, 6)).unwrap();
+        let mut strategies: Vec<Box<dyn Strategy>> = vec![
+            Box::new(Mcts::new(30, 1).depth(50)),
+            Box::new(Random::new(2)),
+        ];
+        play(&mut game, &mut strategies, 60);
+
+        assert!(game.turn() > 1);
+    }
+}
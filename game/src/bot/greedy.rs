@@ -0,0 +1,79 @@
+//! Bot following a simple greedy heuristic
+
+use std::cmp::Reverse;
+
+use crate::Game;
+use crate::action::{Action, Choice, Purchase};
+use crate::bot::{Strategy, playable};
+use crate::legal::LegalActions;
+use crate::phase::Phase;
+use crate::view::PlayerView;
+
+/// Plays every card in hand, then buys the most expensive card it can afford as long as it can
+/// afford any card that costs something. Always reacts to attacks, and answers other decisions
+/// with the first choice offered.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Greedy;
+
+impl Strategy for Greedy {
+    fn choose(&mut self, view: &PlayerView, game: &Game) -> Action {
+        pick(game, &view.legal_actions)
+    }
+}
+
+/// Picks the greedy action out of the legal ones
+pub(crate) fn pick(game: &Game, legal: &LegalActions) -> Action {
+    if let Some(choice) = &legal.choice {
+        return Action::ResolveChoice(choice.first());
+    }
+
+    let actions = playable(&legal.actions);
+
+    let mut choices = actions
+        .iter()
+        .filter(|action| matches!(action, Action::ResolveChoice(_)));
+    let reaction = game
+        .pending()
+        .is_some_and(|decision| decision.is_reaction());
+    let choice = match reaction {
+        true => choices.find(|action| **action != Action::ResolveChoice(Choice::Cards(vec![]))),
+        false => choices.next(),
+    };
+    if let Some(choice) = choice {
+        return choice.clone();
+    }
+    if game.pending().is_some() {
+        return actions[0].clone();
+    }
+
+    if let Some(play) = actions
+        .iter()
+        .find(|action| matches!(action, Action::PlayCard(_)))
+    {
+        return play.clone();
+    }
+
+    let cost = |purchase: &Purchase| match *purchase {
+        Purchase::Pile(def) => game.card_set().get(def).cost,
+        Purchase::Market(card) => game.card(card).cost,
+    };
+    let buy = actions
+        .iter()
+        .filter_map(|action| match action {
+            Action::BuyCard(purchase) if cost(purchase) > 0 => Some((action, cost(purchase))),
+            _ => None,
+        })
+        .min_by_key(|(_, cost)| Reverse(*cost));
+    if let Some((buy, _)) = buy {
+        return buy.clone();
+    }
+
+    let end = match game.phase() {
+        Phase::Action => Action::EndPhase,
+        _ => Action::EndTurn,
+    };
+    match actions.contains(&end) {
+        true => end,
+        false => actions[0].clone(),
+    }
+}
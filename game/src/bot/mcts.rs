@@ -0,0 +1,230 @@
+//! Bot searching the game tree with Monte-Carlo tree search
+//!
+//! Every iteration plays on its own determinized copy of the game, so the search never relies
+//! on the cards hidden from the player. Tree nodes are shared between the copies: a node is
+//! considered only when its action is legal in the copy, and its exploration term counts how
+//! many times it was available rather than how many times its parent was visited.
+
+use crate::Game;
+use crate::action::{Action, Choice};
+use crate::bot::{Strategy, greedy, playable};
+use crate::player::PlayerId;
+use crate::rng::Rng;
+use crate::view::PlayerView;
+
+/// Card choices sampled from their constraint in every searched state
+const CHOICE_SAMPLES: usize = 8;
+
+/// Monte-Carlo tree search bot. Simulations are played out with the greedy heuristic.
+#[derive(Debug, Clone)]
+pub struct Mcts {
+    /// Search iterations run for every action
+    iterations: usize,
+    /// Actions played out in every simulation
+    depth: usize,
+    /// Weight of exploring the less visited actions
+    exploration: f64,
+    rng: Rng,
+}
+
+/// Searched game tree
+#[derive(Debug, Default)]
+struct Tree {
+    /// All the nodes
+    nodes: Vec<Node>,
+    /// Nodes of the actions available in the searched state
+    roots: Vec<usize>,
+}
+
+/// Action in the searched game tree
+#[derive(Debug)]
+struct Node {
+    /// Action leading to the node
+    action: Action,
+    /// Player taking the action
+    player: PlayerId,
+    /// Simulations run through the node
+    visits: u32,
+    /// Times the node could have been selected
+    availability: u32,
+    /// Sum of the simulation rewards of every seat
+    rewards: Vec<f64>,
+    /// Nodes of the actions following this one
+    children: Vec<usize>,
+}
+
+impl Mcts {
+    /// Creates the bot running the number of search iterations for every action
+    pub fn new(iterations: usize, seed: u64) -> Self {
+        Self {
+            iterations,
+            depth: 200,
+            exploration: std::f64::consts::SQRT_2,
+            rng: Rng::new(seed),
+        }
+    }
+
+    /// Sets the number of actions played out in every simulation. Games not finished by then are
+    /// judged by the score.
+    pub fn depth(self, depth: usize) -> Self {
+        Self { depth, ..self }
+    }
+
+    /// Sets the weight of exploring the less visited actions
+    pub fn exploration(self, exploration: f64) -> Self {
+        Self {
+            exploration,
+            ..self
+        }
+    }
+
+    /// Runs a single search iteration: selects the path down the tree, expands it with a new
+    /// action, plays the game out and records the result along the path
+    fn iterate(&mut self, tree: &mut Tree, mut game: Game) {
+        let mut path: Vec<usize> = vec![];
+
+        while let Some(player) = game.waiting_for() {
+            let legal = game.legal_actions(player);
+            let candidates = playable(&legal.sample(&mut self.rng, CHOICE_SAMPLES));
+            let siblings = match path.last() {
+                None => &tree.roots,
+                Some(parent) => &tree.nodes[*parent].children,
+            };
+
+            // Card choices sampled in other iterations are still known if they are legal here
+            let known: Vec<_> = siblings
+                .iter()
+                .copied()
+                .filter(|node| {
+                    let action = &tree.nodes[*node].action;
+                    candidates.contains(action)
+                        || matches!(action, Action::ResolveChoice(Choice::Cards(_)))
+                            && legal.contains(action)
+                })
+                .collect();
+            let untried: Vec<_> = candidates
+                .into_iter()
+                .filter(|action| known.iter().all(|node| tree.nodes[*node].action != *action))
+                .collect();
+
+            let node = if untried.is_empty() {
+                for node in &known {
+                    tree.nodes[*node].availability += 1;
+                }
+                let score = |node: &usize| self.score(&tree.nodes[*node]);
+                known
+                    .iter()
+                    .copied()
+                    .max_by(|a, b| score(a).total_cmp(&score(b)))
+                    .expect("Some action is always legal")
+            } else {
+                let action = untried[self.rng.below(untried.len())].clone();
+                let node = tree.nodes.len();
+                tree.nodes.push(Node {
+                    action,
+                    player,
+                    visits: 0,
+                    availability: 1,
+                    rewards: vec![0.0; game.players().len()],
+                    children: vec![],
+                });
+                match path.last() {
+                    None => tree.roots.push(node),
+                    Some(parent) => tree.nodes[*parent].children.push(node),
+                }
+                node
+            };
+
+            game.apply(player, tree.nodes[node].action.clone())
+                .expect("Legal action must be accepted");
+            path.push(node);
+
+            if tree.nodes[node].visits == 0 {
+                break;
+            }
+        }
+
+        for _ in 0..self.depth {
+            let Some(player) = game.waiting_for() else {
+                break;
+            };
+            let action = greedy::pick(&game, &game.legal_actions(player));
+            game.apply(player, action)
+                .expect("Legal action must be accepted");
+        }
+
+        let rewards = rewards(&game);
+        for node in path {
+            let node = &mut tree.nodes[node];
+            node.visits += 1;
+            for (total, reward) in node.rewards.iter_mut().zip(&rewards) {
+                *total += reward;
+            }
+        }
+    }
+
+    /// Upper confidence bound of the node's action for the player taking it
+    fn score(&self, node: &Node) -> f64 {
+        let visits = f64::from(node.visits);
+        let exploitation = node.rewards[node.player.seat()] / visits;
+        let exploration = (f64::from(node.availability).ln() / visits).sqrt();
+        exploitation + self.exploration * exploration
+    }
+}
+
+impl Strategy for Mcts {
+    fn choose(&mut self, view: &PlayerView, game: &Game) -> Action {
+        let actions = playable(&view.legal_actions.sample(&mut self.rng, CHOICE_SAMPLES));
+        if actions.len() == 1 {
+            return actions[0].clone();
+        }
+
+        let mut tree = Tree::default();
+        for _ in 0..self.iterations {
+            let seed = self.rng.next_u64();
+            self.iterate(&mut tree, game.determinize(view.viewer, seed));
+        }
+
+        tree.roots
+            .iter()
+            .map(|node| &tree.nodes[*node])
+            .filter(|node| view.legal_actions.contains(&node.action))
+            .max_by_key(|node| node.visits)
+            .map_or_else(|| actions[0].clone(), |node| node.action.clone())
+    }
+}
+
+/// Reward of every seat for the simulated game: `1` for the winners, `0.5` for everybody when
+/// nobody won. Unfinished games are judged by the share of the total score every side has.
+fn rewards(game: &Game) -> Vec<f64> {
+    let mut rewards = vec![0.0; game.players().len()];
+
+    match game.result() {
+        Some(result) if result.winner.is_some() => {
+            for winner in game.winners() {
+                rewards[winner.seat()] = 1.0;
+            }
+        }
+        Some(_) => rewards.fill(0.5),
+        None => {
+            let sides = game.sides();
+            let score = |side: &[PlayerId]| -> f64 {
+                side.iter()
+                    .map(|player| f64::from(game.score(*player)))
+                    .sum()
+            };
+            let total: f64 = sides.iter().map(|side| score(side)).sum();
+            for side in &sides {
+                let share = match total > 0.0 {
+                    true => score(side) / total,
+                    false => 0.5,
+                };
+                for player in side {
+                    rewards[player.seat()] = share;
+                }
+            }
+        }
+    }
+
+    rewards
+}
@@ -0,0 +1,31 @@
+//! Bot playing at random
+
+use crate::Game;
+use crate::action::Action;
+use crate::bot::{Strategy, playable};
+use crate::rng::Rng;
+use crate::view::PlayerView;
+
+/// Picks any of the legal actions but conceding, uniformly at random. Card choices count as
+/// a single action, with the cards chosen at random.
+#[derive(Debug, Clone)]
+pub struct Random {
+    rng: Rng,
+}
+
+impl Random {
+    /// Creates the bot with its own seeded randomness
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: Rng::new(seed),
+        }
+    }
+}
+
+impl Strategy for Random {
+    fn choose(&mut self, view: &PlayerView, _game: &Game) -> Action {
+        let mut actions = playable(&view.legal_actions.sample(&mut self.rng, 1));
+        let idx = self.rng.below(actions.len());
+        actions.swap_remove(idx)
+    }
+}
@@ -0,0 +1,366 @@
+//! Card definitions and card instances
+
+use serde::{Deserialize, Serialize};
+
+use crate::effect::Effect;
+use crate::trigger::{Trigger, When};
+
+/// Index of a card definition in the `CardSet`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct CardDefId(u32);
+
+impl CardDefId {
+    /// Returns the index of the definition in the card set
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// Identifier of a single physical card in the game.
+///
+/// Ids are assigned when the card instance is created and never change, no matter which zone the
+/// card is moved to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct CardId(u32);
+
+impl CardId {
+    /// Returns the index of the instance in the game
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl std::fmt::Display for CardId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+/// Type of a card, defining how the engine treats it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CardType {
+    /// Card played for its abilities
+    Action,
+    /// Card played for resources
+    Treasure,
+    /// Card worth victory points
+    Victory,
+    /// Card affecting other players
+    Attack,
+    /// Card other players can respond with
+    Reaction,
+    /// Card staying in play across turns
+    Base,
+}
+
+/// Static description of a card - shared by all instances of the card
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CardDef {
+    /// Card name, unique in the card set
+    pub name: String,
+    /// Cost of buying the card
+    pub cost: u32,
+    /// Card types
+    #[serde(default)]
+    pub types: Vec<CardType>,
+    /// Factions the card belongs to. Cards sharing a faction are allies.
+    #[serde(default)]
+    pub factions: Vec<String>,
+    /// Ability resolved when the card is played
+    #[serde(default)]
+    pub effects: Vec<Effect>,
+    /// Ability resolved when the card is revealed from the hand in reaction to an attack. Only
+    /// used for `reaction` cards.
+    #[serde(default)]
+    pub reaction: Vec<Effect>,
+    /// Victory points the card is worth to its owner at the end of the game
+    #[serde(default)]
+    pub points: u32,
+    /// Copies of this card in every player's starting deck
+    #[serde(default)]
+    pub starter: u32,
+    /// Size of the supply pile for this card. Cards with `0` have no supply pile.
+    #[serde(default)]
+    pub supply: u32,
+    /// Copies of this card shuffled into the market deck
+    #[serde(default)]
+    pub market: u32,
+    /// Damage the card takes before it is destroyed. Only used for `base` cards - bases without
+    /// durability cannot be attacked.
+    #[serde(default)]
+    pub durability: u32,
+    /// Opponents' attacks must destroy this base before hitting its owner or their other bases
+    #[serde(default)]
+    pub guard: bool,
+    /// Abilities resolved whenever their moment comes while the card is in play, or when it is
+    /// trashed
+    #[serde(default)]
+    pub triggers: Vec<Trigger>,
+}
+
+impl CardDef {
+    /// Creates a card definition not distributed anywhere on setup
+    pub fn new(name: impl Into<String>, cost: u32) -> Self {
+        Self {
+            name: name.into(),
+            cost,
+            types: vec![],
+            factions: vec![],
+            effects: vec![],
+            reaction: vec![],
+            points: 0,
+            starter: 0,
+            supply: 0,
+            market: 0,
+            durability: 0,
+            guard: false,
+            triggers: vec![],
+        }
+    }
+
+    /// Sets card types
+    pub fn types(self, types: impl IntoIterator<Item = CardType>) -> Self {
+        Self {
+            types: types.into_iter().collect(),
+            ..self
+        }
+    }
+
+    /// Adds the card to the faction
+    pub fn faction(mut self, faction: impl Into<String>) -> Self {
+        self.factions.push(faction.into());
+        self
+    }
+
+    /// Checks if the card is of the given type
+    pub fn is(&self, card_type: CardType) -> bool {
+        self.types.contains(&card_type)
+    }
+
+    /// Checks if the card belongs to the faction
+    pub fn in_faction(&self, faction: &str) -> bool {
+        self.factions.iter().any(|f| f == faction)
+    }
+
+    /// Checks if the cards share a faction
+    pub fn allied_with(&self, other: &CardDef) -> bool {
+        self.factions
+            .iter()
+            .any(|faction| other.in_faction(faction))
+    }
+
+    /// Adds the effect to the card ability
+    pub fn effect(mut self, effect: Effect) -> Self {
+        self.effects.push(effect);
+        self
+    }
+
+    /// Adds the effect to the card reaction
+    pub fn reaction(mut self, effect: Effect) -> Self {
+        self.reaction.push(effect);
+        self
+    }
+
+    /// Adds gaining resources to the card ability
+    pub fn resources(self, amount: u32) -> Self {
+        self.effect(Effect::Resources(amount))
+    }
+
+    /// Adds the ability triggered at the moment
+    pub fn trigger(mut self, when: When, effects: impl IntoIterator<Item = Effect>) -> Self {
+        self.triggers.push(Trigger {
+            when,
+            effects: effects.into_iter().collect(),
+        });
+        self
+    }
+
+    /// Names of all the cards the ability refers to
+    pub fn references(&self) -> Vec<&str> {
+        self.effects
+            .iter()
+            .chain(&self.reaction)
+            .chain(self.triggers.iter().flat_map(|trigger| &trigger.effects))
+            .flat_map(Effect::references)
+            .collect()
+    }
+
+    /// Sets victory points of the card
+    pub fn points(self, points: u32) -> Self {
+        Self { points, ..self }
+    }
+
+    /// Sets number of copies in every starting deck
+    pub fn starter(self, starter: u32) -> Self {
+        Self { starter, ..self }
+    }
+
+    /// Sets the supply pile size
+    pub fn supply(self, supply: u32) -> Self {
+        Self { supply, ..self }
+    }
+
+    /// Sets number of copies in the market deck
+    pub fn market(self, market: u32) -> Self {
+        Self { market, ..self }
+    }
+
+    /// Sets the durability of the base
+    pub fn durability(self, durability: u32) -> Self {
+        Self { durability, ..self }
+    }
+
+    /// Makes the base guard its owner
+    pub fn guard(self) -> Self {
+        Self {
+            guard: true,
+            ..self
+        }
+    }
+}
+
+/// Collection of card definitions the game is played with
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct CardSet {
+    defs: Vec<CardDef>,
+}
+
+impl CardSet {
+    /// Loads the card set from all the `*.toml` files in the directory
+    pub fn load_dir(dir: impl AsRef<std::path::Path>) -> Result<Self, crate::LoadError> {
+        crate::loader::load_dir(dir)
+    }
+
+    /// Creates a card set from definitions
+    pub fn new(defs: impl IntoIterator<Item = CardDef>) -> Self {
+        Self {
+            defs: defs.into_iter().collect(),
+        }
+    }
+
+    /// Basic card set shipped with the crate, playable without any custom cards
+    pub fn basic() -> Self {
+        crate::loader::parse("basic.toml", include_str!("../cards/basic.toml"))
+            .map(Self::new)
+            .expect("Basic card set must be valid")
+    }
+
+    /// Returns the card definition
+    pub fn get(&self, id: CardDefId) -> &CardDef {
+        &self.defs[id.index()]
+    }
+
+    /// Finds the card definition by its name
+    pub fn find(&self, name: &str) -> Option<CardDefId> {
+        self.defs
+            .iter()
+            .position(|def| def.name == name)
+            .map(|idx| CardDefId(idx as u32))
+    }
+
+    /// Finds an ability referring to a card missing from the set. Returns the names of the
+    /// referring and the missing card.
+    pub fn unknown_reference(&self) -> Option<(&str, &str)> {
+        self.defs.iter().find_map(|def| {
+            def.references()
+                .into_iter()
+                .find(|name| self.find(name).is_none())
+                .map(|name| (&def.name[..], name))
+        })
+    }
+
+    /// Iterates over all the definitions with their ids
+    pub fn iter(&self) -> impl Iterator<Item = (CardDefId, &CardDef)> {
+        self.defs
+            .iter()
+            .enumerate()
+            .map(|(idx, def)| (CardDefId(idx as u32), def))
+    }
+
+    /// Number of definitions in the set
+    pub fn len(&self) -> usize {
+        self.defs.len()
+    }
+
+    /// Checks if the set is empty
+    pub fn is_empty(&self) -> bool {
+        self.defs.is_empty()
+    }
+}
+
+/// All card instances created in the game
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Cards {
+    /// Definition of every instance, indexed by `CardId`
+    instances: Vec<CardDefId>,
+}
+
+impl Cards {
+    /// Id the next created instance will get
+    pub fn next_id(&self) -> CardId {
+        CardId(self.instances.len() as u32)
+    }
+
+    /// Creates a new card instance
+    pub fn create(&mut self, def: CardDefId) -> CardId {
+        let id = self.next_id();
+        self.instances.push(def);
+        id
+    }
+
+    /// Returns the definition id of the card instance
+    pub fn def(&self, card: CardId) -> CardDefId {
+        self.instances[card.index()]
+    }
+
+    /// Number of created instances
+    pub fn len(&self) -> usize {
+        self.instances.len()
+    }
+
+    /// Checks if any card was created
+    pub fn is_empty(&self) -> bool {
+        self.instances.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn card_set_lookup() {
+        let set = CardSet::new([CardDef::new("Copper", 0), CardDef::new("Silver", 3)]);
+
+        let silver = set.find("Silver").unwrap();
+        assert_eq!(set.get(silver).cost, 3);
+        assert_eq!(set.find("Gold"), None);
+        assert_eq!(
+            set.iter().map(|(_, def)| &def.name[..]).collect::<Vec<_>>(),
+            ["Copper", "Silver"]
+        );
+    }
+
+    #[test]
+    fn instances_have_stable_ids() {
+        let set = CardSet::new([CardDef::new("Copper", 0), CardDef::new("Silver", 3)]);
+        let copper = set.find("Copper").unwrap();
+        let silver = set.find("Silver").unwrap();
+
+        let mut cards = Cards::default();
+        let c1 = cards.create(copper);
+        let c2 = cards.create(silver);
+        let c3 = cards.create(copper);
+
+        assert_ne!(c1, c3);
+        assert_eq!(cards.def(c1), copper);
+        assert_eq!(cards.def(c2), silver);
+        assert_eq!(cards.def(c3), copper);
+        assert_eq!(cards.len(), 3);
+    }
+}
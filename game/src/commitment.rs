@@ -0,0 +1,109 @@
+//! Verifiable shuffles
+//!
+//! Every shuffle and random pick of a game comes from the RNG seeded from its setup. The server
+//! publishes a commitment of the seed before any card is dealt and reveals the seed once the game
+//! is over. Anybody can then check that the revealed seed is the committed one, and that it
+//! produces every shuffle recorded in the event log.
+//!
+//! The seed is salted with a random nonce before hashing, and the nonce is revealed with the seed.
+//! Otherwise the seed could be found by hashing every possible one until the commitment matches.
+
+use sha3::{Digest, Sha3_256};
+use thiserror::Error;
+
+use crate::event::{Event, ReplayError};
+use crate::{Game, Setup};
+
+/// Random salt of the seed commitment
+pub type Nonce = [u8; 32];
+
+/// Failure of verifying the game against the seed commitment
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum VerifyError {
+    #[error("Seed does not match the commitment")]
+    CommitmentMismatch,
+    #[error("Event log does not follow from the seed: {0}")]
+    Replay(#[from] ReplayError),
+}
+
+impl Setup {
+    /// Commitment of the seed: SHA3-256 hash of the nonce followed by the little endian bytes of
+    /// the seed, hex encoded
+    pub fn commitment(&self, nonce: &Nonce) -> String {
+        let mut data = nonce.to_vec();
+        data.extend(self.seed.to_le_bytes());
+        sha3_hex(&data)
+    }
+}
+
+impl Game {
+    /// Verifies the game was played with the committed seed. The setup's seed salted with the
+    /// nonce must match the commitment, and replaying the event log from it must reproduce every
+    /// recorded shuffle, draw and random pick. Returns the replayed game.
+    pub fn verify<'a>(
+        setup: Setup,
+        nonce: &Nonce,
+        commitment: &str,
+        events: impl IntoIterator<Item = &'a Event>,
+    ) -> Result<Self, VerifyError> {
+        if setup.commitment(nonce) != commitment.to_ascii_lowercase() {
+            return Err(VerifyError::CommitmentMismatch);
+        }
+
+        Ok(Self::replay(setup, events)?)
+    }
+}
+
+/// SHA3-256 hash of the data, hex encoded
+pub(crate) fn sha3_hex(data: &[u8]) -> String {
+    Sha3_256::digest(data)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::action::Action;
+    use crate::card::CardSet;
+
+    #[test]
+    fn verifying_shuffles() {
+        let setup = Setup::new(CardSet::basic(), 2, 3);
+        let nonce = [7; 32];
+        let commitment = setup.commitment(&nonce);
+        let mut game = Game::new(setup.clone()).unwrap();
+        let mut log = vec![];
+        for _ in 0..6 {
+            log.extend(game.apply(game.active(), Action::EndTurn).unwrap());
+        }
+        assert!(
+            log.iter()
+                .any(|e| matches!(e, Event::DeckReshuffled { .. }))
+        );
+
+        assert_eq!(
+            Game::verify(setup.clone(), &nonce, &commitment, &log),
+            Ok(game)
+        );
+
+        // Commitment covers the nonce as well
+        assert_ne!(setup.commitment(&[8; 32]), commitment);
+        assert_eq!(
+            Game::verify(setup.clone(), &[8; 32], &commitment, &log),
+            Err(VerifyError::CommitmentMismatch)
+        );
+
+        // Other seed deals other cards, even if it were committed to
+        let rigged = Setup { seed: 4, ..setup };
+        assert_eq!(
+            Game::verify(rigged.clone(), &nonce, &commitment, &log),
+            Err(VerifyError::CommitmentMismatch)
+        );
+        assert!(matches!(
+            Game::verify(rigged.clone(), &nonce, &rigged.commitment(&nonce), &log),
+            Err(VerifyError::Replay(_))
+        ));
+    }
+}
@@ -0,0 +1,208 @@
+//! Rules variants a game can be played with
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+use crate::end::EndRules;
+use crate::team::Teams;
+
+/// Maximal number of cards in each player's starting deck
+pub const MAX_STARTING_DECK: u64 = 100;
+
+/// Maximal number of cards drawn at the end of every turn
+pub const MAX_HAND_SIZE: usize = 20;
+
+/// Maximal number of face-up cards in the market row
+pub const MAX_MARKET_SIZE: usize = 20;
+
+/// Rule choosing the player taking the first turn
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FirstPlayer {
+    /// Player chosen at random
+    Random,
+    /// Player in the given seat
+    Seat(usize),
+}
+
+/// Rules variant of a single game. Every setting defaults to the standard rules.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GameConfig {
+    /// Number of cards drawn at the end of every turn
+    pub hand_size: usize,
+    /// Number of copies of every card in each player's starting deck, by card name. When not set,
+    /// the `starter` counts from the card set are used.
+    pub starting_deck: Option<BTreeMap<String, u32>>,
+    /// Number of face-up cards in the market row
+    pub market_size: usize,
+    /// Number of supply piles, picked at random from the card set. When not set, every card
+    /// with a supply pile size gets its pile.
+    pub supply_piles: Option<usize>,
+    /// Player taking the first turn
+    pub first_player: FirstPlayer,
+    /// Rules deciding when the game ends
+    pub end: EndRules,
+    /// Teams the players are split into. When not set, every player plays for themselves.
+    pub teams: Option<Teams>,
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        Self {
+            hand_size: 5,
+            starting_deck: None,
+            market_size: 5,
+            supply_piles: None,
+            first_player: FirstPlayer::Random,
+            end: EndRules::default(),
+            teams: None,
+        }
+    }
+}
+
+impl GameConfig {
+    /// Checks the settings are within the limits
+    pub fn validate(&self) -> Result<(), Error> {
+        let deck: u64 = self
+            .starting_deck
+            .iter()
+            .flat_map(|deck| deck.values())
+            .map(|count| u64::from(*count))
+            .sum();
+        if deck > MAX_STARTING_DECK {
+            return Err(Error::StartingDeckTooBig(deck));
+        }
+
+        if self.hand_size > MAX_HAND_SIZE {
+            return Err(Error::HandSizeTooBig(self.hand_size));
+        }
+
+        if self.market_size > MAX_MARKET_SIZE {
+            return Err(Error::MarketSizeTooBig(self.market_size));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::card::{CardDef, CardSet};
+    use crate::{Error, Game, PlayerId, Setup};
+
+    fn card_set() -> CardSet {
+        CardSet::new([
+            CardDef::new("Copper", 0).starter(7).supply(30),
+            CardDef::new("Estate", 2).starter(3).supply(8),
+            CardDef::new("Silver", 3).supply(30),
+            CardDef::new("Smithy", 4).market(4),
+        ])
+    }
+
+    #[test]
+    fn parsing_config() {
+        let config: GameConfig = toml::from_str(
+            r#"
+                hand_size = 3
+                starting_deck = { Copper = 4, Silver = 2 }
+                first_player = { seat = 1 }
+
+                [end]
+                conditions = [{ score = 10 }]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.hand_size, 3);
+        assert_eq!(config.market_size, 5);
+        assert_eq!(config.first_player, FirstPlayer::Seat(1));
+        assert_eq!(
+            config.starting_deck,
+            Some([("Copper".to_owned(), 4), ("Silver".to_owned(), 2)].into())
+        );
+        assert_eq!(config.end.health, EndRules::default().health);
+    }
+
+    #[test]
+    fn playing_variants() {
+        let config = GameConfig {
+            hand_size: 3,
+            starting_deck: Some([("Silver".to_owned(), 6)].into()),
+            market_size: 2,
+            supply_piles: Some(2),
+            first_player: FirstPlayer::Seat(1),
+            ..GameConfig::default()
+        };
+        let game = Game::new(Setup::new(card_set(), 2, 4).config(config)).unwrap();
+
+        assert_eq!(game.active(), PlayerId::new(1));
+        let silver = game.card_set().find("Silver").unwrap();
+        for player in game.players() {
+            assert_eq!(player.hand().len(), 3);
+            assert_eq!(player.deck().len(), 3);
+            assert!(
+                player
+                    .hand()
+                    .iter()
+                    .chain(player.deck())
+                    .all(|card| game.cards().def(*card) == silver)
+            );
+        }
+        assert_eq!(game.supply().market().len(), 2);
+        assert_eq!(game.supply().piles().len(), 2);
+    }
+
+    #[test]
+    fn invalid_config() {
+        let setup = |config| Game::new(Setup::new(card_set(), 2, 4).config(config));
+
+        assert_eq!(
+            setup(GameConfig {
+                first_player: FirstPlayer::Seat(2),
+                ..GameConfig::default()
+            }),
+            Err(Error::InvalidFirstPlayer(2))
+        );
+        assert_eq!(
+            setup(GameConfig {
+                starting_deck: Some([("Gold".to_owned(), 6)].into()),
+                ..GameConfig::default()
+            }),
+            Err(Error::UnknownStarter("Gold".to_owned()))
+        );
+        assert_eq!(
+            setup(GameConfig {
+                starting_deck: Some([("Copper".to_owned(), 0)].into()),
+                ..GameConfig::default()
+            }),
+            Err(Error::EmptyStartingDeck)
+        );
+        assert_eq!(
+            setup(GameConfig {
+                starting_deck: Some(
+                    [("Copper".to_owned(), u32::MAX), ("Estate".to_owned(), 1)].into()
+                ),
+                ..GameConfig::default()
+            }),
+            Err(Error::StartingDeckTooBig(u64::from(u32::MAX) + 1))
+        );
+        assert_eq!(
+            setup(GameConfig {
+                hand_size: usize::MAX,
+                ..GameConfig::default()
+            }),
+            Err(Error::HandSizeTooBig(usize::MAX))
+        );
+        assert_eq!(
+            setup(GameConfig {
+                market_size: MAX_MARKET_SIZE + 1,
+                ..GameConfig::default()
+            }),
+            Err(Error::MarketSizeTooBig(MAX_MARKET_SIZE + 1))
+        );
+    }
+}
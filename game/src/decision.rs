@@ -0,0 +1,569 @@
+//! Decisions suspending the resolution of card abilities
+//!
+//! When an effect needs a player's input, the engine records a pending `Decision` and stops. The
+//! decision carries everything needed to continue - the effect waiting for the answer and all
+//! the steps of the ability left - so the game picks up exactly where it stopped once the
+//! matching `ResolveChoice` arrives, no matter how much later.
+
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+use crate::Game;
+use crate::action::{Choice, Rejection};
+use crate::card::CardId;
+use crate::effect::{Effect, Step, StepKind, moved_by};
+use crate::event::Event;
+use crate::player::{PlayerId, Zone};
+use crate::trigger::Registration;
+
+/// What the player is asked for
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Request {
+    /// Choose between `min` and `max` of the cards
+    Cards {
+        cards: Vec<CardId>,
+        min: u32,
+        max: u32,
+    },
+    /// Choose one of the options, each being a list of effects
+    Options(Vec<Vec<Effect>>),
+    /// Reveal one of the reaction cards in response to the attack, or none to not react
+    Reaction { cards: Vec<CardId> },
+    /// Choose which of the simultaneous triggers resolves first, by its index. The rest are
+    /// ordered the same way once it resolved.
+    Order(Vec<Registration>),
+}
+
+impl Request {
+    /// Checks if the choice is a valid answer. Chosen cards can be listed in any order.
+    pub fn accepts(&self, choice: &Choice) -> bool {
+        match (self, choice) {
+            (Request::Cards { cards, min, max }, Choice::Cards(chosen)) => {
+                let count = chosen.len() as u32;
+                (*min..=*max).contains(&count) && offered(chosen, cards)
+            }
+            (Request::Options(options), Choice::Option(idx)) => *idx < options.len(),
+            (Request::Order(triggers), Choice::Option(idx)) => *idx < triggers.len(),
+            (Request::Reaction { cards }, Choice::Cards(chosen)) => {
+                chosen.len() <= 1 && offered(chosen, cards)
+            }
+            _ => false,
+        }
+    }
+
+    /// Puts the chosen cards of the accepted choice into the order they are offered, so every
+    /// answer is recorded in a single canonical form
+    pub fn arrange(&self, choice: Choice) -> Choice {
+        match (self, choice) {
+            (
+                Request::Cards { cards, .. } | Request::Reaction { cards },
+                Choice::Cards(mut chosen),
+            ) => {
+                chosen.sort_by_key(|card| cards.iter().position(|c| c == card));
+                Choice::Cards(chosen)
+            }
+            (_, choice) => choice,
+        }
+    }
+
+    /// Answer used when the player cannot answer anymore: the first `min` cards, the first
+    /// option or trigger, or no reaction
+    pub fn default_choice(&self) -> Choice {
+        match self {
+            Request::Cards { cards, min, .. } => {
+                Choice::Cards(cards.iter().take(*min as usize).copied().collect())
+            }
+            Request::Options(_) | Request::Order(_) => Choice::Option(0),
+            Request::Reaction { .. } => Choice::Cards(vec![]),
+        }
+    }
+}
+
+/// Checks if the chosen cards are distinct and all of them are offered
+pub(crate) fn offered(chosen: &[CardId], cards: &[CardId]) -> bool {
+    chosen
+        .iter()
+        .enumerate()
+        .all(|(idx, card)| cards.contains(card) && !chosen[..idx].contains(card))
+}
+
+/// Decision the game waits for
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Decision {
+    /// What the player is asked for
+    request: Request,
+    /// Step waiting for the answer
+    step: Step,
+    /// Steps left after the one waiting
+    then: VecDeque<Step>,
+}
+
+impl Decision {
+    pub(crate) fn new(request: Request, step: Step, then: VecDeque<Step>) -> Self {
+        Self {
+            request,
+            step,
+            then,
+        }
+    }
+
+    /// Player who must answer
+    pub fn player(&self) -> PlayerId {
+        self.step.player
+    }
+
+    /// What the player is asked for
+    pub fn request(&self) -> &Request {
+        &self.request
+    }
+
+    /// Card whose ability waits for the decision
+    pub fn source(&self) -> CardId {
+        self.step.source
+    }
+
+    /// Checks if the decision is a window to react to an attack
+    pub fn is_reaction(&self) -> bool {
+        matches!(self.request, Request::Reaction { .. })
+    }
+}
+
+impl Game {
+    /// Decision the game waits for
+    pub fn pending(&self) -> Option<&Decision> {
+        self.pending.as_ref()
+    }
+
+    /// Closes the pending reaction window without a reaction. Meant for reacting players who
+    /// missed their deadline.
+    pub fn expire_reaction(&mut self) -> Result<Vec<Event>, Rejection> {
+        if !self.pending.as_ref().is_some_and(Decision::is_reaction) {
+            return Err(Rejection::NoReactionWindow);
+        }
+
+        let mut events = vec![];
+        self.decide(&mut events, Choice::Cards(vec![]));
+        self.check_end(&mut events);
+        Ok(events)
+    }
+
+    /// Resolves the pending decision with the already validated choice and continues the
+    /// suspended resolution
+    pub(crate) fn decide(&mut self, events: &mut Vec<Event>, choice: Choice) {
+        let Some(decision) = self.pending.clone() else {
+            return;
+        };
+
+        let Decision { step, mut then, .. } = decision;
+        let Step { player, source, .. } = step;
+
+        self.emit(
+            events,
+            Event::DecisionResolved {
+                player,
+                choice: choice.clone(),
+            },
+        );
+
+        match (step.kind, choice) {
+            (
+                StepKind::Effect(effect @ (Effect::Trash(_) | Effect::Discard(_))),
+                Choice::Cards(cards),
+            ) => {
+                let moved = moved_by(&effect);
+                for card in cards {
+                    self.emit(events, moved(player, card, Zone::Hand));
+                }
+            }
+            (StepKind::Effect(Effect::ChooseOne(mut options)), Choice::Option(idx)) => {
+                for effect in options.swap_remove(idx).into_iter().rev() {
+                    then.push_front(Step::effect(player, source, effect));
+                }
+            }
+            (StepKind::Order(mut triggers), Choice::Option(idx)) => {
+                let first = triggers.remove(idx);
+                let rest = self.trigger_steps(player, triggers);
+                for step in rest.into_iter().rev() {
+                    then.push_front(step);
+                }
+                then.push_front(Step {
+                    player,
+                    source: first.card,
+                    kind: StepKind::Trigger(first),
+                });
+            }
+            (StepKind::React, Choice::Cards(cards)) => {
+                if let [card] = cards[..] {
+                    self.emit(events, Event::ReactionRevealed { player, card });
+                    for effect in self.card(card).reaction.clone().into_iter().rev() {
+                        then.push_front(Step::effect(player, card, effect));
+                    }
+                }
+            }
+            _ => (),
+        }
+
+        self.run(events, then);
+        self.proceed(events);
+    }
+
+    /// Resolves decisions nobody can answer anymore with their default choices: ones owed by
+    /// players who left, and all of them once the active player left
+    pub(crate) fn settle_conceded(&mut self, events: &mut Vec<Event>) {
+        while let Some(decision) = &self.pending {
+            if !self.player(decision.player()).conceded && !self.player(self.active).conceded {
+                return;
+            }
+
+            let choice = decision.request.default_choice();
+            self.decide(events, choice);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::action::Action;
+    use crate::card::CardSet;
+    use crate::test_util::{give, p};
+    use crate::{Setup, loader};
+
+    fn setup() -> Setup {
+        let defs = loader::parse(
+            "test.toml",
+            r#"
+                [[card]]
+                name = "Copper"
+                cost = 0
+                effects = [{ resources = 1 }]
+                starter = 7
+                supply = 30
+
+                [[card]]
+                name = "Estate"
+                cost = 2
+                starter = 3
+                supply = 8
+
+                [[card]]
+                name = "Chapel"
+                cost = 2
+                effects = [{ trash = { choose = { max = 2 } } }, { draw = 1 }]
+
+                [[card]]
+                name = "Emissary"
+                cost = 3
+                effects = [{ choose_one = [[{ draw = 2 }], [{ resources = 2 }, { gain = { card = "Estate" } }]] }]
+
+                [[card]]
+                name = "Militia"
+                cost = 4
+                effects = [{ resources = 2 }, { force_discard = { choose = { min = 2, max = 2 } } }]
+
+                [[card]]
+                name = "Raider"
+                cost = 4
+                types = ["action", "attack"]
+                effects = [{ resources = 2 }, { force_discard = { top = 2 } }]
+
+                [[card]]
+                name = "Moat"
+                cost = 2
+                types = ["action", "reaction"]
+                effects = [{ draw = 2 }]
+                reaction = ["block", { draw = 1 }]
+            "#,
+        )
+        .unwrap();
+
+        // Seed with the first player `P1`
+        Setup::new(CardSet::new(defs), 2, 2)
+    }
+
+    /// Puts a new instance of the card into the active player's hand and plays it
+    fn play(game: &mut Game, name: &str) -> Vec<Event> {
+        let player = game.active();
+        let card = give(game, player, name);
+        game.apply(player, Action::PlayCard(card)).unwrap()
+    }
+
+    #[test]
+    fn choosing_cards() {
+        let mut game = Game::new(setup()).unwrap();
+        let hand = game.player(p(0)).hand().to_vec();
+
+        let events = play(&mut game, "Chapel");
+        let Some(Event::DecisionRequested { decision }) = events.last() else {
+            panic!("Unexpected events: {events:?}");
+        };
+        assert_eq!(decision.player(), p(0));
+        assert_eq!(
+            decision.request(),
+            &Request::Cards {
+                cards: hand.clone(),
+                min: 0,
+                max: 2
+            }
+        );
+        assert_eq!(game.pending(), Some(&**decision));
+
+        // Game waits for the decision
+        assert_eq!(
+            game.apply(p(0), Action::EndTurn),
+            Err(Rejection::ChoicePending)
+        );
+        assert_eq!(
+            game.apply(p(1), Action::ResolveChoice(Choice::Cards(vec![]))),
+            Err(Rejection::NotYourChoice(p(1)))
+        );
+        for invalid in [
+            Choice::Option(0),
+            Choice::Cards(hand[..3].to_vec()),
+            Choice::Cards(vec![hand[0], hand[0]]),
+            Choice::Cards(vec![game.player(p(1)).hand()[0]]),
+        ] {
+            assert_eq!(
+                game.apply(p(0), Action::ResolveChoice(invalid)),
+                Err(Rejection::InvalidChoice)
+            );
+        }
+
+        let events = game
+            .apply(
+                p(0),
+                Action::ResolveChoice(Choice::Cards(hand[..2].to_vec())),
+            )
+            .unwrap();
+        assert!(matches!(
+            events[..],
+            [
+                Event::DecisionResolved { .. },
+                Event::CardTrashed { .. },
+                Event::CardTrashed { .. },
+                Event::CardDrawn { .. },
+            ]
+        ));
+        assert_eq!(game.pending(), None);
+        assert_eq!(game.player(p(0)).trash(), &hand[..2]);
+        assert_eq!(game.player(p(0)).hand().len(), 4);
+        assert_eq!(
+            game.apply(p(0), Action::ResolveChoice(Choice::Cards(vec![]))),
+            Err(Rejection::NoPendingChoice)
+        );
+    }
+
+    #[test]
+    fn choosing_options() {
+        let mut game = Game::new(setup()).unwrap();
+        let estate = game.card_set().find("Estate").unwrap();
+
+        let events = play(&mut game, "Emissary");
+        assert!(matches!(
+            events.last(),
+            Some(Event::DecisionRequested { decision }) if matches!(decision.request(), Request::Options(options) if options.len() == 2)
+        ));
+
+        assert_eq!(
+            game.apply(p(0), Action::ResolveChoice(Choice::Option(2))),
+            Err(Rejection::InvalidChoice)
+        );
+        let events = game
+            .apply(p(0), Action::ResolveChoice(Choice::Option(1)))
+            .unwrap();
+        assert!(matches!(
+            events[..],
+            [
+                Event::DecisionResolved { .. },
+                Event::ResourcesGained { amount: 2, .. },
+                Event::CardGained { pile, .. }
+            ] if pile == estate
+        ));
+    }
+
+    #[test]
+    fn opponents_decide() {
+        let mut game = Game::new(setup()).unwrap();
+        let hand = game.player(p(1)).hand().to_vec();
+
+        play(&mut game, "Militia");
+        assert_eq!(game.pending().map(Decision::player), Some(p(1)));
+        assert_eq!(
+            game.apply(p(0), Action::EndTurn),
+            Err(Rejection::ChoicePending)
+        );
+        assert_eq!(
+            game.apply(
+                p(1),
+                Action::ResolveChoice(Choice::Cards(hand[..1].to_vec()))
+            ),
+            Err(Rejection::InvalidChoice)
+        );
+
+        // Cards can be chosen in any order, they are recorded in the offered one
+        let events = game
+            .apply(
+                p(1),
+                Action::ResolveChoice(Choice::Cards(vec![hand[2], hand[1]])),
+            )
+            .unwrap();
+        assert_eq!(
+            events[0],
+            Event::DecisionResolved {
+                player: p(1),
+                choice: Choice::Cards(hand[1..3].to_vec())
+            }
+        );
+        assert_eq!(game.player(p(1)).discard(), &hand[1..3]);
+        assert_eq!(game.pending(), None);
+        game.apply(p(0), Action::EndTurn).unwrap();
+    }
+
+    #[test]
+    fn pending_decision_survives_serialization() {
+        let mut game = Game::new(setup()).unwrap();
+        let hand = game.player(p(0)).hand().to_vec();
+        let mut log = play(&mut game, "Chapel");
+
+        // Played card is created outside of the log - the replayed game needs it as well
+        let mut replayed = Game::new(setup()).unwrap();
+        give(&mut replayed, p(0), "Chapel");
+
+        let serialized = serde_json::to_string(&log).unwrap();
+        let deserialized: Vec<Event> = serde_json::from_str(&serialized).unwrap();
+        for event in &deserialized {
+            replayed.fold(event).unwrap();
+        }
+        assert_eq!(replayed, game);
+
+        let choice = Action::ResolveChoice(Choice::Cards(vec![hand[4]]));
+        log = game.apply(p(0), choice.clone()).unwrap();
+        assert_eq!(replayed.apply(p(0), choice).unwrap(), log);
+        assert_eq!(replayed, game);
+    }
+
+    #[test]
+    fn conceding_settles_decisions() {
+        let mut game = Game::new(setup()).unwrap();
+        let hand = game.player(p(1)).hand().to_vec();
+
+        play(&mut game, "Militia");
+        let events = game.apply(p(1), Action::Concede).unwrap();
+        assert_eq!(
+            events[..2],
+            [
+                Event::Conceded { player: p(1) },
+                Event::DecisionResolved {
+                    player: p(1),
+                    choice: Choice::Cards(hand[..2].to_vec())
+                }
+            ]
+        );
+        assert_eq!(game.pending(), None);
+    }
+
+    #[test]
+    fn attacks_without_reactions_resolve_immediately() {
+        let mut game = Game::new(setup()).unwrap();
+
+        let events = play(&mut game, "Raider");
+        assert_eq!(game.pending(), None);
+        assert_eq!(
+            events
+                .iter()
+                .filter(
+                    |event| matches!(event, Event::CardDiscarded { player, .. } if *player == p(1))
+                )
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn reacting_to_attacks() {
+        let mut game = Game::new(setup()).unwrap();
+        let moat = give(&mut game, p(1), "Moat");
+
+        // Reaction window opens before the attack resolves
+        let events = play(&mut game, "Raider");
+        assert!(matches!(
+            events[..],
+            [Event::CardPlayed { .. }, Event::DecisionRequested { .. }]
+        ));
+        let decision = game.pending().unwrap();
+        assert!(decision.is_reaction());
+        assert_eq!(decision.player(), p(1));
+        assert_eq!(decision.request(), &Request::Reaction { cards: vec![moat] });
+        assert_eq!(
+            game.apply(p(0), Action::EndTurn),
+            Err(Rejection::ChoicePending)
+        );
+
+        let events = game
+            .apply(p(1), Action::ResolveChoice(Choice::Cards(vec![moat])))
+            .unwrap();
+        assert!(matches!(
+            events[..],
+            [
+                Event::DecisionResolved { .. },
+                Event::ReactionRevealed { card, .. },
+                Event::AttackBlocked { .. },
+                Event::CardDrawn { .. },
+                Event::ResourcesGained { amount: 2, .. },
+            ] if card == moat
+        ));
+        assert!(game.player(p(1)).discard().is_empty());
+        assert!(game.player(p(1)).hand().contains(&moat));
+
+        // Protection only lasts for the single attack
+        let events = play(&mut game, "Raider");
+        assert!(matches!(
+            events.last(),
+            Some(Event::DecisionRequested { .. })
+        ));
+        let events = game
+            .apply(p(1), Action::ResolveChoice(Choice::Cards(vec![])))
+            .unwrap();
+        assert!(
+            !events
+                .iter()
+                .any(|event| matches!(event, Event::AttackBlocked { .. }))
+        );
+        assert_eq!(
+            events
+                .iter()
+                .filter(|event| matches!(
+                    event,
+                    Event::CardDiscarded { player, from: Zone::Deck, .. } if *player == p(1)
+                ))
+                .count(),
+            2
+        );
+        assert_eq!(game.player(p(1)).discard().len(), 2);
+    }
+
+    #[test]
+    fn expiring_reactions() {
+        let mut game = Game::new(setup()).unwrap();
+        assert_eq!(game.expire_reaction(), Err(Rejection::NoReactionWindow));
+
+        play(&mut game, "Chapel");
+        assert_eq!(game.expire_reaction(), Err(Rejection::NoReactionWindow));
+        game.apply(p(0), Action::ResolveChoice(Choice::Cards(vec![])))
+            .unwrap();
+
+        give(&mut game, p(1), "Moat");
+        play(&mut game, "Raider");
+        let events = game.expire_reaction().unwrap();
+        assert_eq!(
+            events[0],
+            Event::DecisionResolved {
+                player: p(1),
+                choice: Choice::Cards(vec![])
+            }
+        );
+        assert_eq!(game.pending(), None);
+        assert_eq!(game.player(p(1)).discard().len(), 2);
+    }
+}
@@ -0,0 +1,797 @@
+//! Card abilities composed from effects
+//!
+//! Abilities are plain data, so card set files can combine effects freely:
+//!
+//! ```toml
+//! [[card]]
+//! name = "Trader"
+//! cost = 2
+//! effects = [
+//!     { resources = 1 },
+//!     { if = { condition = { in_play = { matching = { faction = "guild" } } }, then = [{ draw = 1 }] } },
+//! ]
+//! ```
+//!
+//! Effects are resolved in order, and every change they make is emitted as an event. Resolution
+//! stops at effects requiring a player's decision and continues once the decision is resolved.
+
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+use crate::Game;
+use crate::card::{CardDef, CardId, CardType};
+use crate::decision::{Decision, Request};
+use crate::event::Event;
+use crate::player::{PlayerId, Zone};
+use crate::trigger::Registration;
+
+/// Cards an effect applies to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Selection {
+    /// The card whose ability is resolved, if it is still in play or among the bases
+    This,
+    /// Up to `n` cards from the top of the deck
+    Top(u32),
+    /// Up to `n` cards picked at random from the hand
+    Random(u32),
+    /// Between `min` and `max` cards from the hand, chosen by the player
+    Choose {
+        #[serde(default)]
+        min: u32,
+        max: u32,
+    },
+}
+
+/// Cards matched by a condition. Empty filter matches every card.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CardFilter {
+    /// Required card type
+    #[serde(default, rename = "type")]
+    pub card_type: Option<CardType>,
+    /// Required faction
+    #[serde(default)]
+    pub faction: Option<String>,
+}
+
+impl CardFilter {
+    /// Checks if the card matches the filter
+    pub fn matches(&self, def: &CardDef) -> bool {
+        self.card_type.is_none_or(|card_type| def.is(card_type))
+            && self
+                .faction
+                .as_ref()
+                .is_none_or(|faction| def.in_faction(faction))
+    }
+}
+
+/// Condition of the player's board
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum Condition {
+    /// At least `at_least` cards matching the filter are in play, not counting the card whose
+    /// ability is resolved
+    InPlay {
+        #[serde(default)]
+        matching: CardFilter,
+        #[serde(default = "one")]
+        at_least: u32,
+    },
+}
+
+/// Players an effect is aimed at. Conceded players are never targeted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Target {
+    /// Every player not on the player's team
+    Opponents,
+    /// Every teammate of the player
+    Teammates,
+    /// Every other player, regardless of their team
+    Others,
+}
+
+/// Single step of a card ability
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum Effect {
+    /// Draws cards
+    Draw(u32),
+    /// Gains resources to spend this turn
+    Resources(u32),
+    /// Gains a card from its supply pile to the zone. Nothing happens if the pile is empty.
+    Gain {
+        card: String,
+        #[serde(default = "discard")]
+        to: Zone,
+    },
+    /// Trashes the selected cards
+    Trash(Selection),
+    /// Discards the selected cards
+    Discard(Selection),
+    /// Every opponent not protected from the attack discards the selected cards
+    ForceDiscard(Selection),
+    /// Deals damage to every opponent not protected from the attack. Their guarding bases take
+    /// it first.
+    Damage(u32),
+    /// Deals damage to the bases of every opponent not protected from the attack
+    Demolish(u32),
+    /// Player chooses one of the options and its effects are resolved
+    ChooseOne(Vec<Vec<Effect>>),
+    /// Makes the player unaffected by the attack being resolved. Meant for reactions.
+    Block,
+    /// Every targeted player resolves the effects for themselves, one after another in the seat
+    /// order. Players protected from the attack are skipped.
+    Each {
+        target: Target,
+        effects: Vec<Effect>,
+    },
+    /// Resolves `then` if the condition holds, `otherwise` if it doesn't
+    If {
+        condition: Condition,
+        then: Vec<Effect>,
+        #[serde(default)]
+        otherwise: Vec<Effect>,
+    },
+}
+
+impl Effect {
+    /// Names of all the cards the effect refers to
+    pub fn references(&self) -> Vec<&str> {
+        match self {
+            Effect::Gain { card, .. } => vec![card],
+            Effect::If {
+                then, otherwise, ..
+            } => then
+                .iter()
+                .chain(otherwise)
+                .flat_map(Effect::references)
+                .collect(),
+            Effect::ChooseOne(options) => options
+                .iter()
+                .flatten()
+                .flat_map(Effect::references)
+                .collect(),
+            Effect::Each { effects, .. } => effects.iter().flat_map(Effect::references).collect(),
+            _ => vec![],
+        }
+    }
+}
+
+fn one() -> u32 {
+    1
+}
+
+fn discard() -> Zone {
+    Zone::Discard
+}
+
+/// Event moving a card selected by the `Trash` or `Discard` effect
+pub(crate) fn moved_by(effect: &Effect) -> fn(PlayerId, CardId, Zone) -> Event {
+    match effect {
+        Effect::Trash(_) => |player, card, from| Event::CardTrashed { player, card, from },
+        _ => |player, card, from| Event::CardDiscarded { player, card, from },
+    }
+}
+
+/// Single step of resolving card abilities
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct Step {
+    /// Player the step is resolved for
+    pub player: PlayerId,
+    /// Card whose ability the step belongs to
+    pub source: CardId,
+    /// What happens in the step
+    pub kind: StepKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum StepKind {
+    /// Resolves the effect
+    Effect(Effect),
+    /// Opens the window for the player to react to the attack
+    React,
+    /// Resolves the triggered ability
+    Trigger(Registration),
+    /// Asks the player which of their simultaneous triggers resolves first
+    Order(Vec<Registration>),
+}
+
+impl Step {
+    pub(crate) fn effect(player: PlayerId, source: CardId, effect: Effect) -> Self {
+        Self {
+            player,
+            source,
+            kind: StepKind::Effect(effect),
+        }
+    }
+}
+
+impl Game {
+    /// Resolves the card's ability on behalf of the player.
+    ///
+    /// Before an attack resolves, every opponent holding a reaction card gets a window to react,
+    /// one by one in the seat order starting after the player.
+    pub(crate) fn resolve(
+        &mut self,
+        events: &mut Vec<Event>,
+        player: PlayerId,
+        card: CardId,
+        effects: &[Effect],
+    ) {
+        let mut steps = VecDeque::new();
+        if self.card(card).is(CardType::Attack) {
+            steps.extend(self.opponents(player).into_iter().map(|opponent| Step {
+                player: opponent,
+                source: card,
+                kind: StepKind::React,
+            }));
+        }
+
+        steps.extend(
+            effects
+                .iter()
+                .map(|effect| Step::effect(player, card, effect.clone())),
+        );
+        self.run(events, steps);
+    }
+
+    /// Resolves the steps in order, each of them followed by the triggers it fired.
+    ///
+    /// Stops at the first step requiring a decision, requesting it together with all the steps
+    /// left.
+    pub(crate) fn run(&mut self, events: &mut Vec<Event>, mut steps: VecDeque<Step>) {
+        loop {
+            self.schedule_fired(&mut steps);
+            let Some(step) = steps.pop_front() else {
+                return;
+            };
+            let Step { player, source, .. } = step;
+
+            let effect = match &step.kind {
+                StepKind::Effect(effect) => effect,
+                StepKind::React => {
+                    let cards: Vec<_> = self
+                        .player(player)
+                        .hand
+                        .iter()
+                        .copied()
+                        .filter(|card| {
+                            let def = self.card(*card);
+                            def.is(CardType::Reaction) && !def.reaction.is_empty()
+                        })
+                        .collect();
+
+                    if !cards.is_empty() {
+                        self.request(events, Request::Reaction { cards }, step, steps);
+                        return;
+                    }
+                    continue;
+                }
+                StepKind::Trigger(registration) => {
+                    let Registration { card, trigger, .. } = *registration;
+                    self.emit(
+                        events,
+                        Event::Triggered {
+                            player,
+                            card,
+                            trigger,
+                        },
+                    );
+                    let effects = self.card(card).triggers[trigger].effects.clone();
+                    for effect in effects.into_iter().rev() {
+                        steps.push_front(Step::effect(player, card, effect));
+                    }
+                    continue;
+                }
+                StepKind::Order(triggers) => {
+                    let request = Request::Order(triggers.clone());
+                    self.request(events, request, step, steps);
+                    return;
+                }
+            };
+
+            match *effect {
+                Effect::Draw(count) => self.draw(events, player, count as usize),
+                Effect::Resources(0) => (),
+                Effect::Resources(amount) => {
+                    self.emit(events, Event::ResourcesGained { player, amount })
+                }
+                Effect::Gain { ref card, to } => {
+                    // References are validated on setup
+                    let Some(pile) = self.card_set.find(card) else {
+                        continue;
+                    };
+
+                    if self.supply.pile(pile).is_some_and(|pile| pile.count > 0) {
+                        let card = self.cards.next_id();
+                        self.emit(
+                            events,
+                            Event::CardGained {
+                                player,
+                                card,
+                                pile,
+                                to,
+                            },
+                        );
+                    }
+                }
+                Effect::Trash(ref selection) | Effect::Discard(ref selection) => {
+                    let Selection::Choose { min, max } = *selection else {
+                        self.resolve_selection(events, player, source, selection, moved_by(effect));
+                        continue;
+                    };
+
+                    let cards = self.player(player).hand.clone();
+                    let max = max.min(cards.len() as u32);
+                    if max == 0 {
+                        continue;
+                    }
+
+                    let request = Request::Cards {
+                        cards,
+                        min: min.min(max),
+                        max,
+                    };
+                    self.request(events, request, step, steps);
+                    return;
+                }
+                Effect::ForceDiscard(ref selection) => {
+                    let discards: Vec<_> = self
+                        .targets(player, Target::Opponents)
+                        .into_iter()
+                        .map(|opponent| {
+                            Step::effect(opponent, source, Effect::Discard(selection.clone()))
+                        })
+                        .collect();
+                    for discard in discards.into_iter().rev() {
+                        steps.push_front(discard);
+                    }
+                }
+                Effect::Each {
+                    target,
+                    ref effects,
+                } => {
+                    let targeted: Vec<_> = self
+                        .targets(player, target)
+                        .into_iter()
+                        .flat_map(|target| {
+                            effects
+                                .iter()
+                                .map(move |effect| Step::effect(target, source, effect.clone()))
+                        })
+                        .collect();
+                    for step in targeted.into_iter().rev() {
+                        steps.push_front(step);
+                    }
+                }
+                Effect::Damage(amount) => {
+                    for target in self.targets(player, Target::Opponents) {
+                        let amount = self.damage_bases(events, target, amount, false);
+                        let amount = amount.min(self.player(target).health);
+                        if amount > 0 {
+                            self.emit(
+                                events,
+                                Event::DamageDealt {
+                                    player: target,
+                                    amount,
+                                },
+                            );
+                        }
+                    }
+                }
+                Effect::Demolish(amount) => {
+                    for target in self.targets(player, Target::Opponents) {
+                        self.damage_bases(events, target, amount, true);
+                    }
+                }
+                Effect::If {
+                    ref condition,
+                    ref then,
+                    ref otherwise,
+                } => {
+                    let branch = match self.holds(player, source, condition) {
+                        true => then,
+                        false => otherwise,
+                    };
+                    for effect in branch.iter().rev() {
+                        steps.push_front(Step::effect(player, source, effect.clone()));
+                    }
+                }
+                Effect::ChooseOne(ref options) => {
+                    if options.is_empty() {
+                        continue;
+                    }
+
+                    let request = Request::Options(options.clone());
+                    self.request(events, request, step, steps);
+                    return;
+                }
+                Effect::Block => self.emit(events, Event::AttackBlocked { player }),
+            }
+        }
+    }
+
+    /// Players targeted by the player's effect, skipping the ones protected from the attack
+    fn targets(&self, player: PlayerId, target: Target) -> Vec<PlayerId> {
+        let players = match target {
+            Target::Opponents => self.opponents(player),
+            Target::Teammates => self.teammates(player),
+            Target::Others => self.others(player),
+        };
+        players
+            .into_iter()
+            .filter(|player| !self.unaffected.contains(player))
+            .collect()
+    }
+
+    /// Suspends the resolution until the step's player answers the request
+    fn request(
+        &mut self,
+        events: &mut Vec<Event>,
+        request: Request,
+        step: Step,
+        mut then: VecDeque<Step>,
+    ) {
+        self.schedule_fired(&mut then);
+        let decision = Decision::new(request, step, then);
+        self.emit(
+            events,
+            Event::DecisionRequested {
+                decision: Box::new(decision),
+            },
+        );
+    }
+
+    /// Emits the event for every card selected from the player's zones
+    fn resolve_selection(
+        &mut self,
+        events: &mut Vec<Event>,
+        player: PlayerId,
+        source: CardId,
+        selection: &Selection,
+        event: fn(PlayerId, CardId, Zone) -> Event,
+    ) {
+        match *selection {
+            // Bases stay in their own zone, the other cards stay in play
+            Selection::This => {
+                let zone = [Zone::InPlay, Zone::Bases]
+                    .into_iter()
+                    .find(|zone| self.player(player).zone(*zone).contains(&source));
+                if let Some(zone) = zone {
+                    self.emit(events, event(player, source, zone));
+                }
+            }
+            Selection::Top(count) => {
+                for _ in 0..count {
+                    let Some(&card) = self.player(player).deck.last() else {
+                        return;
+                    };
+                    self.emit(events, event(player, card, Zone::Deck));
+                }
+            }
+            Selection::Random(count) => {
+                for _ in 0..count {
+                    let hand = &self.player(player).hand;
+                    if hand.is_empty() {
+                        return;
+                    }
+
+                    // The pick itself is made when the event is folded, this only predicts it
+                    let card = hand[self.rng.clone().below(hand.len())];
+                    self.emit(events, Event::RandomCardPicked { player, card });
+                    self.emit(events, event(player, card, Zone::Hand));
+                }
+            }
+            // Chosen cards are moved when the decision is resolved
+            Selection::Choose { .. } => (),
+        }
+    }
+
+    /// Checks the condition for the ability of the `source` card
+    fn holds(&self, player: PlayerId, source: CardId, condition: &Condition) -> bool {
+        match condition {
+            Condition::InPlay { matching, at_least } => {
+                let matches = self
+                    .player(player)
+                    .in_play
+                    .iter()
+                    .filter(|card| **card != source && matching.matches(self.card(**card)))
+                    .count();
+                matches >= *at_least as usize
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::action::{Action, Purchase};
+    use crate::card::CardSet;
+    use crate::test_util::{give, p};
+    use crate::{Setup, loader};
+
+    fn game(cards: &str) -> Game {
+        let mut defs = loader::parse(
+            "test.toml",
+            r#"
+                [[card]]
+                name = "Copper"
+                cost = 0
+                effects = [{ resources = 1 }]
+                starter = 7
+                supply = 30
+
+                [[card]]
+                name = "Estate"
+                cost = 2
+                starter = 3
+                supply = 8
+            "#,
+        )
+        .unwrap();
+        defs.extend(loader::parse("cards.toml", cards).unwrap());
+
+        // Seed with the first player `P1`
+        Game::new(Setup::new(CardSet::new(defs), 2, 2)).unwrap()
+    }
+
+    /// Puts a new instance of the card into the active player's hand and plays it
+    fn play(game: &mut Game, name: &str) -> (CardId, Vec<Event>) {
+        let player = game.active();
+        let card = give(game, player, name);
+        let events = game.apply(player, Action::PlayCard(card)).unwrap();
+        (card, events)
+    }
+
+    #[test]
+    fn parsing_effects() {
+        let defs = loader::parse(
+            "test.toml",
+            r#"
+                [[card]]
+                name = "Raider"
+                cost = 4
+                effects = [
+                    { draw = 2 },
+                    { gain = { card = "Copper", to = "hand" } },
+                    { trash = "this" },
+                    { discard = { top = 1 } },
+                    { force_discard = { random = 1 } },
+                    { if = { condition = { in_play = { matching = { type = "action" } } }, then = [{ resources = 2 }] } },
+                ]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            defs[0].effects,
+            [
+                Effect::Draw(2),
+                Effect::Gain {
+                    card: "Copper".to_owned(),
+                    to: Zone::Hand
+                },
+                Effect::Trash(Selection::This),
+                Effect::Discard(Selection::Top(1)),
+                Effect::ForceDiscard(Selection::Random(1)),
+                Effect::If {
+                    condition: Condition::InPlay {
+                        matching: CardFilter {
+                            card_type: Some(CardType::Action),
+                            faction: None
+                        },
+                        at_least: 1
+                    },
+                    then: vec![Effect::Resources(2)],
+                    otherwise: vec![]
+                }
+            ]
+        );
+        assert_eq!(defs[0].references(), ["Copper"]);
+
+        assert!(
+            loader::parse(
+                "test.toml",
+                "[[card]]\nname = \"X\"\ncost = 1\neffects = [{ fly = 1 }]"
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn drawing_and_gaining() {
+        let mut game = game(
+            r#"
+                [[card]]
+                name = "Smith"
+                cost = 4
+                effects = [{ draw = 2 }, { gain = { card = "Copper" } }, { gain = { card = "Estate", to = "hand" } }]
+            "#,
+        );
+        let copper = game.card_set().find("Copper").unwrap();
+        let estate = game.card_set().find("Estate").unwrap();
+        let top = game.player(p(0)).deck()[3..].to_vec();
+
+        let (_, events) = play(&mut game, "Smith");
+        assert!(matches!(
+            events[..],
+            [
+                Event::CardPlayed { .. },
+                Event::CardDrawn { .. },
+                Event::CardDrawn { .. },
+                Event::CardGained {
+                    pile,
+                    to: Zone::Discard,
+                    ..
+                },
+                Event::CardGained {
+                    pile: pile2,
+                    to: Zone::Hand,
+                    ..
+                },
+            ] if pile == copper && pile2 == estate
+        ));
+        assert!(
+            top.iter()
+                .all(|card| game.player(p(0)).hand().contains(card))
+        );
+        assert_eq!(game.player(p(0)).hand().len(), 8);
+        assert_eq!(game.player(p(0)).discard().len(), 1);
+        assert_eq!(game.supply().pile(copper).unwrap().count, 29);
+    }
+
+    #[test]
+    fn trashing_and_discarding() {
+        let mut game = game(
+            r#"
+                [[card]]
+                name = "Recycler"
+                cost = 3
+                effects = [{ trash = "this" }, { trash = { random = 1 } }, { discard = { top = 2 } }]
+
+                [[card]]
+                name = "Raider"
+                cost = 4
+                effects = [{ force_discard = { random = 2 } }]
+            "#,
+        );
+        let hand = game.player(p(0)).hand().to_vec();
+        let top = game.player(p(0)).deck()[3..].to_vec();
+
+        let (recycler, events) = play(&mut game, "Recycler");
+        let [
+            Event::CardPlayed { .. },
+            Event::CardTrashed {
+                card,
+                from: Zone::InPlay,
+                ..
+            },
+            Event::RandomCardPicked { card: picked, .. },
+            Event::CardTrashed {
+                card: trashed,
+                from: Zone::Hand,
+                ..
+            },
+            Event::CardDiscarded {
+                from: Zone::Deck, ..
+            },
+            Event::CardDiscarded {
+                from: Zone::Deck, ..
+            },
+        ] = events[..]
+        else {
+            panic!("Unexpected events: {events:?}");
+        };
+        assert_eq!(card, recycler);
+        assert_eq!(picked, trashed);
+        assert!(hand.contains(&trashed));
+        assert_eq!(game.player(p(0)).trash(), [recycler, trashed]);
+        assert_eq!(game.player(p(0)).discard().len(), 2);
+        assert!(
+            top.iter()
+                .all(|card| game.player(p(0)).discard().contains(card))
+        );
+
+        let (_, events) = play(&mut game, "Raider");
+        let discarded: Vec<_> = events
+            .iter()
+            .filter_map(|event| match event {
+                Event::CardDiscarded { player, card, .. } => Some((*player, *card)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(discarded.len(), 2);
+        assert!(discarded.iter().all(|(player, _)| *player == p(1)));
+        assert_eq!(game.player(p(1)).hand().len(), 3);
+        assert_eq!(game.player(p(1)).discard().len(), 2);
+    }
+
+    #[test]
+    fn bases_scrapping_themselves() {
+        let mut game = game(
+            r#"
+                [[card]]
+                name = "Salvage Yard"
+                cost = 3
+                types = ["base"]
+                durability = 3
+                triggers = [{ when = "turn_start", effects = [{ trash = "this" }, { resources = 2 }] }]
+            "#,
+        );
+        let (yard, _) = play(&mut game, "Salvage Yard");
+        assert_eq!(game.player(p(0)).zone(Zone::Bases), [yard]);
+
+        game.apply(p(0), Action::EndTurn).unwrap();
+        let events = game.apply(p(1), Action::EndTurn).unwrap();
+        assert!(events.contains(&Event::CardTrashed {
+            player: p(0),
+            card: yard,
+            from: Zone::Bases
+        }));
+        assert_eq!(game.player(p(0)).zone(Zone::Bases), []);
+        assert_eq!(game.player(p(0)).trash(), [yard]);
+        assert_eq!(game.player(p(0)).resources(), 2);
+        assert!(game.triggers().is_empty());
+    }
+
+    #[test]
+    fn conditions_on_cards_in_play() {
+        let mut game = game(
+            r#"
+                [[card]]
+                name = "Trader"
+                cost = 2
+                types = ["action"]
+                factions = ["guild"]
+                effects = [
+                    { if = { condition = { in_play = { matching = { faction = "guild" } } }, then = [{ resources = 2 }], otherwise = [{ resources = 1 }] } },
+                ]
+            "#,
+        );
+
+        play(&mut game, "Trader");
+        assert_eq!(game.player(p(0)).resources(), 1);
+        play(&mut game, "Trader");
+        assert_eq!(game.player(p(0)).resources(), 3);
+    }
+
+    #[test]
+    fn effects_are_replayable() {
+        let cards = r#"
+            [[card]]
+            name = "Raider"
+            cost = 4
+            effects = [{ trash = { random = 1 } }, { force_discard = { random = 1 } }, { gain = { card = "Copper", to = "deck" } }, { draw = 1 }]
+            market = 10
+        "#;
+        let mut game = game(cards);
+        let setup = Setup::new(game.card_set().clone(), 2, 2);
+        let mut log = vec![];
+
+        for _ in 0..8 {
+            let player = game.active();
+            for card in game.player(player).hand().to_vec() {
+                if game.player(player).hand().contains(&card) {
+                    log.extend(game.apply(player, Action::PlayCard(card)).unwrap());
+                }
+            }
+            log.extend(game.apply(player, Action::EndPhase).unwrap());
+            if let Some(&card) = game.supply().market().first()
+                && let Ok(events) = game.apply(player, Action::BuyCard(Purchase::Market(card)))
+            {
+                log.extend(events);
+            }
+            log.extend(game.apply(player, Action::EndTurn).unwrap());
+        }
+
+        assert!(
+            log.iter()
+                .any(|event| matches!(event, Event::RandomCardPicked { .. }))
+        );
+        assert_eq!(Game::replay(setup, &log).unwrap(), game);
+    }
+}
@@ -0,0 +1,413 @@
+//! End of the game - end conditions and the final scoring
+//!
+//! End conditions are checked after every action. Once any of them is met, the game ends with the
+//! final `GameResult` and no more actions are accepted. The game also ends when all the players
+//! but one conceded.
+//!
+//! In team games the teams are scored instead of the players: a team scores the points of all its
+//! players, and all of them share the victory.
+
+use std::cmp::Ordering;
+
+use serde::{Deserialize, Serialize};
+
+use crate::Game;
+use crate::card::CardId;
+use crate::event::Event;
+use crate::player::{PlayerId, Zone};
+
+/// Condition ending the game as soon as it is met
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EndCondition {
+    /// Given number of supply piles ran out
+    SupplyExhausted(u32),
+    /// Player, or team in team games, reached the score
+    Score(u32),
+    /// Player's health dropped to zero
+    Knockout,
+}
+
+/// Rule ordering players with the same score
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TieBreaker {
+    /// Player with more health left wins
+    Health,
+    /// Player who took fewer turns wins
+    FewerTurns,
+    /// Player owning fewer cards wins
+    FewerCards,
+}
+
+/// Why the game ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EndReason {
+    /// Enough supply piles ran out
+    SupplyExhausted,
+    /// Player reached the target score
+    Score,
+    /// Player was knocked out
+    Knockout,
+    /// All the other players, or teams, conceded
+    Concession,
+}
+
+/// Rules deciding when the game ends and who wins it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EndRules {
+    /// Game ends as soon as any of the conditions is met
+    pub conditions: Vec<EndCondition>,
+    /// Health every player starts with
+    pub health: u32,
+    /// Rules breaking ties of the highest score, applied in order
+    pub tie_breakers: Vec<TieBreaker>,
+}
+
+impl Default for EndRules {
+    fn default() -> Self {
+        Self {
+            conditions: vec![EndCondition::SupplyExhausted(1)],
+            health: 20,
+            tie_breakers: vec![TieBreaker::FewerTurns],
+        }
+    }
+}
+
+/// Final outcome of the game
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GameResult {
+    /// Winning player, `None` if the game ended in a tie. In team games it is the best player of
+    /// the winning team.
+    pub winner: Option<PlayerId>,
+    /// Winning team in team games
+    #[serde(default)]
+    pub team: Option<usize>,
+    /// Final scores of the players in the seat order
+    pub scores: Vec<u32>,
+    /// Why the game ended
+    pub reason: EndReason,
+}
+
+impl Game {
+    /// Rules deciding when the game ends
+    pub fn end_rules(&self) -> &EndRules {
+        &self.config.end
+    }
+
+    /// Outcome of the finished game
+    pub fn result(&self) -> Option<&GameResult> {
+        self.result.as_ref()
+    }
+
+    /// Victory points of all the cards the player owns. Trashed cards are not counted.
+    pub fn score(&self, player: PlayerId) -> u32 {
+        self.owned(player).map(|card| self.card(card).points).sum()
+    }
+
+    /// Players sharing the victory of the finished game
+    pub fn winners(&self) -> Vec<PlayerId> {
+        let Some(winner) = self.result.as_ref().and_then(|result| result.winner) else {
+            return vec![];
+        };
+        self.player_ids()
+            .filter(|player| self.allied(winner, *player))
+            .collect()
+    }
+
+    /// Ends the game if any of the end conditions is met
+    pub(crate) fn check_end(&mut self, events: &mut Vec<Event>) {
+        if self.is_over() {
+            return;
+        }
+
+        if let Some(reason) = self.end_reason() {
+            let result = self.final_result(reason);
+            self.emit(events, Event::GameEnded { result });
+        }
+    }
+
+    /// Finds the reason to end the game in the current state
+    fn end_reason(&self) -> Option<EndReason> {
+        let playing = |player: &PlayerId| !self.player(*player).conceded;
+        let sides: Vec<_> = self
+            .sides()
+            .into_iter()
+            .filter(|side| side.iter().any(playing))
+            .collect();
+        if sides.len() < 2 {
+            return Some(EndReason::Concession);
+        }
+
+        self.config.end.conditions.iter().find_map(|condition| {
+            let met = match *condition {
+                EndCondition::SupplyExhausted(piles) => {
+                    let empty = self.supply.piles().iter().filter(|pile| pile.count == 0);
+                    empty.count() >= piles as usize
+                }
+                EndCondition::Score(target) => sides
+                    .iter()
+                    .any(|side| self.total(side, |player| self.score(player)) >= target),
+                EndCondition::Knockout => sides
+                    .iter()
+                    .flatten()
+                    .any(|player| playing(player) && self.player(*player).health == 0),
+            };
+
+            met.then_some(match condition {
+                EndCondition::SupplyExhausted(_) => EndReason::SupplyExhausted,
+                EndCondition::Score(_) => EndReason::Score,
+                EndCondition::Knockout => EndReason::Knockout,
+            })
+        })
+    }
+
+    /// Scores the game. Sides where everybody conceded, or someone was knocked out, cannot win.
+    /// Among the others the highest score wins with ties broken by the tie-breakers. Sides still
+    /// tied after all of them share the victory, so nobody wins.
+    fn final_result(&self, reason: EndReason) -> GameResult {
+        let scores = self.player_ids().map(|player| self.score(player)).collect();
+
+        let contenders: Vec<_> = self
+            .sides()
+            .into_iter()
+            .filter(|side| {
+                side.iter().any(|player| !self.player(*player).conceded)
+                    && side.iter().all(|player| self.player(*player).health > 0)
+            })
+            .collect();
+        let best = contenders.iter().max_by(|a, b| self.compare(a, b));
+        let side = best.filter(|best| {
+            contenders
+                .iter()
+                .filter(|side| self.compare(side, best) == Ordering::Equal)
+                .count()
+                == 1
+        });
+        let winner = side.and_then(|side| {
+            side.iter()
+                .copied()
+                .max_by(|a, b| self.compare(&[*a], &[*b]))
+        });
+
+        GameResult {
+            winner,
+            team: winner.and_then(|winner| self.team(winner)),
+            scores,
+            reason,
+        }
+    }
+
+    /// Orders the sides by their final standing, better side is greater
+    fn compare(&self, a: &[PlayerId], b: &[PlayerId]) -> Ordering {
+        let by_score = self
+            .total(a, |player| self.score(player))
+            .cmp(&self.total(b, |player| self.score(player)));
+
+        self.config
+            .end
+            .tie_breakers
+            .iter()
+            .fold(by_score, |ordering, tie_breaker| {
+                ordering.then_with(|| {
+                    let total = |side: &[PlayerId]| {
+                        self.total(side, |player| match tie_breaker {
+                            TieBreaker::Health => self.player(player).health,
+                            TieBreaker::FewerTurns => self.player(player).turns,
+                            TieBreaker::FewerCards => self.owned(player).count() as u32,
+                        })
+                    };
+                    match tie_breaker {
+                        TieBreaker::Health => total(a).cmp(&total(b)),
+                        TieBreaker::FewerTurns | TieBreaker::FewerCards => total(b).cmp(&total(a)),
+                    }
+                })
+            })
+    }
+
+    /// Sums the players' values
+    fn total(&self, players: &[PlayerId], value: impl Fn(PlayerId) -> u32) -> u32 {
+        players.iter().map(|player| value(*player)).sum()
+    }
+
+    /// All the cards owned by the player
+    fn owned(&self, player: PlayerId) -> impl Iterator<Item = CardId> + '_ {
+        let state = self.player(player);
+        Zone::ALL
+            .into_iter()
+            .filter(|zone| *zone != Zone::Trash)
+            .flat_map(|zone| state.zone(zone).iter().copied())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Setup;
+    use crate::action::{Action, Purchase, Rejection};
+    use crate::card::{CardDef, CardSet, CardType};
+    use crate::config::GameConfig;
+    use crate::effect::Effect;
+    use crate::event::ReplayError;
+    use crate::test_util::{give, put};
+
+    fn card_set() -> CardSet {
+        CardSet::new([
+            CardDef::new("Copper", 0).resources(1).starter(7).supply(30),
+            CardDef::new("Estate", 0).points(1).starter(3).supply(2),
+            CardDef::new("Duchy", 0).points(3).supply(8),
+            CardDef::new("Cannon", 0)
+                .types([CardType::Action, CardType::Attack])
+                .effect(Effect::Damage(3))
+                .supply(10),
+        ])
+    }
+
+    fn game(end: EndRules) -> Game {
+        let config = GameConfig {
+            end,
+            ..GameConfig::default()
+        };
+        Game::new(Setup::new(card_set(), 2, 2).config(config)).unwrap()
+    }
+
+    fn buy(game: &mut Game, player: PlayerId, name: &str) -> Vec<Event> {
+        let pile = game.card_set().find(name).unwrap();
+        if game.phase() == crate::Phase::Action {
+            game.apply(player, Action::EndPhase).unwrap();
+        }
+        game.apply(player, Action::BuyCard(Purchase::Pile(pile)))
+            .unwrap()
+    }
+
+    #[test]
+    fn supply_running_out() {
+        let mut game = game(EndRules::default());
+        let player = game.active();
+        let other = game.next_player(player);
+        assert_eq!(game.score(player), 3);
+
+        buy(&mut game, player, "Estate");
+        assert!(!game.is_over());
+        let events = buy(&mut game, player, "Estate");
+
+        let result = GameResult {
+            winner: Some(player),
+            team: None,
+            scores: match player.seat() {
+                0 => vec![5, 3],
+                _ => vec![3, 5],
+            },
+            reason: EndReason::SupplyExhausted,
+        };
+        assert_eq!(
+            events.last(),
+            Some(&Event::GameEnded {
+                result: result.clone()
+            })
+        );
+        assert_eq!(game.result(), Some(&result));
+        assert_eq!(game.apply(other, Action::Concede), Err(Rejection::GameOver));
+        assert_eq!(game.legal_actions(player).actions, []);
+    }
+
+    #[test]
+    fn reaching_target_score() {
+        let mut game = game(EndRules {
+            conditions: vec![EndCondition::Score(9)],
+            ..EndRules::default()
+        });
+        let player = game.active();
+
+        buy(&mut game, player, "Duchy");
+        assert!(!game.is_over());
+        buy(&mut game, player, "Duchy");
+        let result = game.result().unwrap();
+        assert_eq!(result.reason, EndReason::Score);
+        assert_eq!(result.winner, Some(player));
+    }
+
+    #[test]
+    fn knocking_out() {
+        let mut game = game(EndRules {
+            conditions: vec![EndCondition::Knockout],
+            health: 5,
+            tie_breakers: vec![],
+        });
+        let player = game.active();
+        let other = game.next_player(player);
+        let cannons = [(); 2].map(|_| give(&mut game, player, "Cannon"));
+
+        let events = game.apply(player, Action::PlayCard(cannons[0])).unwrap();
+        assert!(events.contains(&Event::DamageDealt {
+            player: other,
+            amount: 3
+        }));
+        assert_eq!(game.player(other).health(), 2);
+
+        // Damage never takes more health than is left
+        let events = game.apply(player, Action::PlayCard(cannons[1])).unwrap();
+        assert!(events.contains(&Event::DamageDealt {
+            player: other,
+            amount: 2
+        }));
+        let result = game.result().unwrap();
+        assert_eq!(result.reason, EndReason::Knockout);
+        assert_eq!(result.winner, Some(player));
+    }
+
+    #[test]
+    fn breaking_ties() {
+        let tie_breakers = |tie_breakers: Vec<TieBreaker>| {
+            let mut game = game(EndRules {
+                conditions: vec![],
+                health: 10,
+                tie_breakers,
+            });
+            let player = game.active();
+            let other = game.next_player(player);
+            game.apply(player, Action::EndTurn).unwrap();
+            // Both players score 3 and took a turn each
+            game.players[other.seat()].health = 4;
+            put(&mut game, player, Zone::Discard, "Copper");
+
+            game.final_result(EndReason::Score).winner
+        };
+
+        let mut game = game(EndRules::default());
+        let first = game.active();
+        let second = game.next_player(first);
+        game.apply(first, Action::EndTurn).unwrap();
+        assert_eq!(game.player(first).turns(), 1);
+        assert_eq!(game.player(second).turns(), 1);
+
+        assert_eq!(tie_breakers(vec![]), None);
+        assert_eq!(tie_breakers(vec![TieBreaker::Health]), Some(first));
+        assert_eq!(tie_breakers(vec![TieBreaker::FewerCards]), Some(second));
+        assert_eq!(tie_breakers(vec![TieBreaker::FewerTurns]), None);
+        assert_eq!(
+            tie_breakers(vec![TieBreaker::FewerTurns, TieBreaker::FewerCards]),
+            Some(second)
+        );
+    }
+
+    #[test]
+    fn conceding_ends_the_game() {
+        let mut game = game(EndRules::default());
+        let player = game.active();
+        let other = game.next_player(player);
+        let setup = Setup::new(card_set(), 2, 2);
+
+        let events = game.apply(player, Action::Concede).unwrap();
+        let result = game.result().unwrap();
+        assert_eq!(result.reason, EndReason::Concession);
+        assert_eq!(result.winner, Some(other));
+        assert_eq!(Game::replay(setup, &events), Ok(game.clone()));
+        assert!(matches!(
+            Game::replay(Setup::new(card_set(), 2, 2), events.iter().chain(&events)),
+            Err(ReplayError::InvalidEvent { .. })
+        ));
+    }
+}
@@ -0,0 +1,425 @@
+//! Domain events describing changes of the game state
+//!
+//! The game is event sourced: events are the only way the game state changes. Every event is
+//! folded into the state the moment it is emitted, so folding the same ordered log of events
+//! over the same `Setup` always rebuilds exactly the same game.
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::action::{Choice, Purchase};
+use crate::card::{CardDefId, CardId, CardType};
+use crate::decision::Decision;
+use crate::end::GameResult;
+use crate::phase::Phase;
+use crate::player::{PlayerId, Zone};
+use crate::trigger::When;
+use crate::{Game, Setup};
+
+/// Something that happened in the game
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Event {
+    /// Card was played from the hand, registering its triggers while it stays in play
+    CardPlayed { player: PlayerId, card: CardId },
+    /// Player gained resources to spend this turn
+    ResourcesGained { player: PlayerId, amount: u32 },
+    /// Card was bought to the player's discard pile. For pile purchases `card` is the newly
+    /// created instance.
+    CardBought {
+        player: PlayerId,
+        card: CardId,
+        source: Purchase,
+    },
+    /// Top card of the market deck was revealed into the market row
+    MarketRefilled { card: CardId },
+    /// Player's discard pile was shuffled and became their deck
+    DeckReshuffled { player: PlayerId },
+    /// Top card of the player's deck was drawn
+    CardDrawn { player: PlayerId, card: CardId },
+    /// New instance of the card from the supply pile was put into the player's zone
+    CardGained {
+        player: PlayerId,
+        card: CardId,
+        pile: CardDefId,
+        to: Zone,
+    },
+    /// Card was moved from the player's zone to their trash
+    CardTrashed {
+        player: PlayerId,
+        card: CardId,
+        from: Zone,
+    },
+    /// Card was moved from the player's zone to their discard pile
+    CardDiscarded {
+        player: PlayerId,
+        card: CardId,
+        from: Zone,
+    },
+    /// Card was picked at random from the player's hand. The pick is made with the game RNG when
+    /// the event is folded, `card` only verifies it.
+    RandomCardPicked { player: PlayerId, card: CardId },
+    /// Turn ended - cards in play and in hand are discarded, unspent resources are lost
+    TurnEnded { player: PlayerId },
+    /// New turn started in its `Start` phase
+    TurnStarted { player: PlayerId, turn: u32 },
+    /// Turn moved on to the next phase
+    PhaseStarted { player: PlayerId, phase: Phase },
+    /// Player left the game
+    Conceded { player: PlayerId },
+    /// Card ability was suspended until the player makes the decision
+    DecisionRequested { decision: Box<Decision> },
+    /// Player answered the pending decision
+    DecisionResolved { player: PlayerId, choice: Choice },
+    /// Player revealed the reaction card from their hand in response to an attack
+    ReactionRevealed { player: PlayerId, card: CardId },
+    /// Player became unaffected by the attack being resolved
+    AttackBlocked { player: PlayerId },
+    /// Player lost health
+    DamageDealt { player: PlayerId, amount: u32 },
+    /// Played base moved from play to the player's bases, keeping its triggers registered
+    BaseDeployed { player: PlayerId, card: CardId },
+    /// Player's base took damage
+    BaseDamaged {
+        player: PlayerId,
+        card: CardId,
+        amount: u32,
+    },
+    /// Player's base was worn out and went to their discard pile, unregistering its triggers
+    BaseDestroyed { player: PlayerId, card: CardId },
+    /// Card's triggered ability started resolving on behalf of the player. Ally abilities don't
+    /// trigger again this turn.
+    Triggered {
+        player: PlayerId,
+        card: CardId,
+        trigger: usize,
+    },
+    /// Game is over, no more actions are accepted
+    GameEnded { result: GameResult },
+    /// Active player took back their actions since the last irreversible one
+    Undone { player: PlayerId },
+}
+
+/// Failure of rebuilding the game from the event log
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ReplayError {
+    #[error("Invalid setup: {0}")]
+    Setup(#[from] crate::Error),
+    #[error("Event #{index} cannot be applied: {event:?}")]
+    InvalidEvent { index: usize, event: Event },
+}
+
+/// Event that doesn't match the state it is folded into
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Inconsistent;
+
+impl Game {
+    /// Rebuilds the game by folding the ordered event log over the initial setup
+    pub fn replay<'a>(
+        setup: Setup,
+        events: impl IntoIterator<Item = &'a Event>,
+    ) -> Result<Self, ReplayError> {
+        Self::new(setup)?.advance(events)
+    }
+
+    /// Folds the further part of the event log into the state, eg. the events recorded after the
+    /// game was snapshotted. Event indices in errors are relative to `events`.
+    pub fn advance<'a>(
+        mut self,
+        events: impl IntoIterator<Item = &'a Event>,
+    ) -> Result<Self, ReplayError> {
+        for (index, event) in events.into_iter().enumerate() {
+            self.fold(event).map_err(|_| ReplayError::InvalidEvent {
+                index,
+                event: event.clone(),
+            })?;
+        }
+
+        Ok(self)
+    }
+
+    /// Applies the event to the state.
+    ///
+    /// On error the state might be partially modified and should be dropped.
+    pub(crate) fn fold(&mut self, event: &Event) -> Result<(), Inconsistent> {
+        self.track_checkpoint(event);
+        match *event {
+            Event::CardPlayed { player, card } => {
+                self.move_card(card, (player, Zone::Hand), (player, Zone::InPlay))?;
+                self.register(player, card);
+                self.unaffected.clear();
+            }
+            Event::ResourcesGained { player, amount } => {
+                self.player_mut(player)?.resources += amount;
+            }
+            Event::CardBought {
+                player,
+                card,
+                source,
+            } => {
+                match source {
+                    Purchase::Pile(def) => {
+                        check(self.supply.take_from_pile(def))?;
+                        check(self.cards.create(def) == card)?;
+                    }
+                    Purchase::Market(bought) => {
+                        check(bought == card)?;
+                        check(self.supply.take_from_market(card))?;
+                    }
+                }
+
+                let cost = self.card(card).cost;
+                let state = self.player_mut(player)?;
+                state.resources = state.resources.checked_sub(cost).ok_or(Inconsistent)?;
+                state.discard.push(card);
+            }
+            Event::MarketRefilled { card } => {
+                check(self.supply.market_deck().last() == Some(&card))?;
+                self.supply.reveal();
+            }
+            Event::DeckReshuffled { player } => {
+                let state = self.players.get_mut(player.seat()).ok_or(Inconsistent)?;
+                let mut discard = std::mem::take(&mut state.discard);
+                self.rng.shuffle(&mut discard);
+                state.deck.extend(discard);
+            }
+            Event::CardDrawn { player, card } => {
+                check(self.player_mut(player)?.deck.last() == Some(&card))?;
+                self.move_card(card, (player, Zone::Deck), (player, Zone::Hand))?;
+            }
+            Event::CardGained {
+                player,
+                card,
+                pile,
+                to,
+            } => {
+                self.player_mut(player)?;
+                check(self.supply.take_from_pile(pile))?;
+                check(self.cards.create(pile) == card)?;
+                self.player_mut(player)?.zone_mut(to).push(card);
+            }
+            Event::CardTrashed { player, card, from } => {
+                self.move_card(card, (player, from), (player, Zone::Trash))?;
+                self.player_mut(player)?.damage.remove(&card);
+                self.unregister(card);
+            }
+            Event::CardDiscarded { player, card, from } => {
+                self.move_card(card, (player, from), (player, Zone::Discard))?;
+                self.player_mut(player)?.damage.remove(&card);
+                self.unregister(card);
+            }
+            Event::RandomCardPicked { player, card } => {
+                let hand = &self.players.get(player.seat()).ok_or(Inconsistent)?.hand;
+                check(!hand.is_empty())?;
+                check(hand[self.rng.below(hand.len())] == card)?;
+            }
+            Event::TurnEnded { player } => {
+                check(player == self.active && self.phase == Phase::Cleanup)?;
+                check(self.pending.is_none())?;
+                let state = self.player_mut(player)?;
+                let in_play = std::mem::take(&mut state.in_play);
+                let hand = std::mem::take(&mut state.hand);
+                state.discard.extend(&in_play);
+                state.discard.extend(hand);
+                state.resources = 0;
+                self.triggers
+                    .retain(|registration| !in_play.contains(&registration.card));
+                self.unaffected.clear();
+                self.allied.clear();
+            }
+            Event::TurnStarted { player, turn } => {
+                self.player_mut(player)?;
+                check(self.phase == Phase::Cleanup)?;
+                self.player_mut(player)?.turns += 1;
+                self.active = player;
+                self.turn = turn;
+                self.phase = Phase::Start;
+            }
+            Event::PhaseStarted { player, phase } => {
+                check(player == self.active && phase == self.phase.next())?;
+                check(phase != Phase::Start)?;
+                self.phase = phase;
+            }
+            Event::Conceded { player } => {
+                self.player_mut(player)?.conceded = true;
+            }
+            Event::DecisionRequested { ref decision } => {
+                self.player_mut(decision.player())?;
+                check(self.pending.is_none())?;
+                self.pending = Some(Decision::clone(decision));
+            }
+            Event::DecisionResolved { player, ref choice } => {
+                let decision = self.pending.take().ok_or(Inconsistent)?;
+                check(decision.player() == player && decision.request().accepts(choice))?;
+            }
+            Event::ReactionRevealed { player, card } => {
+                check(self.player_mut(player)?.hand.contains(&card))?;
+            }
+            Event::AttackBlocked { player } => {
+                self.player_mut(player)?;
+                self.unaffected.push(player);
+            }
+            Event::DamageDealt { player, amount } => {
+                let state = self.player_mut(player)?;
+                state.health = state.health.checked_sub(amount).ok_or(Inconsistent)?;
+            }
+            Event::BaseDeployed { player, card } => {
+                check(self.card(card).is(CardType::Base))?;
+                self.move_card(card, (player, Zone::InPlay), (player, Zone::Bases))?;
+            }
+            Event::BaseDamaged {
+                player,
+                card,
+                amount,
+            } => {
+                check(self.player_mut(player)?.bases.contains(&card))?;
+                check(amount > 0 && amount <= self.durability(card))?;
+                *self.player_mut(player)?.damage.entry(card).or_default() += amount;
+            }
+            Event::BaseDestroyed { player, card } => {
+                check(self.player_mut(player)?.bases.contains(&card))?;
+                check(self.durability(card) == 0)?;
+                self.move_card(card, (player, Zone::Bases), (player, Zone::Discard))?;
+                self.player_mut(player)?.damage.remove(&card);
+                self.unregister(card);
+            }
+            Event::Triggered {
+                player,
+                card,
+                trigger,
+            } => {
+                self.player_mut(player)?;
+                check(card.index() < self.cards.len())?;
+                check(trigger < self.card(card).triggers.len())?;
+                let ally = self.card(card).triggers[trigger].when == When::Ally;
+                if ally && !self.allied.contains(&card) {
+                    self.allied.push(card);
+                }
+            }
+            Event::GameEnded { ref result } => {
+                check(self.result.is_none() && result.scores.len() == self.players.len())?;
+                self.pending = None;
+                self.result = Some(result.clone());
+            }
+            Event::Undone { player } => {
+                check(player == self.active && self.restore_checkpoint())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Folds the event into the state and records it, collecting the triggers it fires
+    pub(crate) fn emit(&mut self, events: &mut Vec<Event>, event: Event) {
+        self.fold(&event)
+            .expect("Emitted event must be consistent with the state");
+        let fired = self.fired_by(&event);
+        self.fired.extend(fired);
+        events.push(event);
+    }
+}
+
+/// Turns failed precondition into an error
+fn check(condition: bool) -> Result<(), Inconsistent> {
+    if condition { Ok(()) } else { Err(Inconsistent) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::action::Action;
+    use crate::card::{CardDef, CardSet};
+
+    fn setup() -> Setup {
+        Setup::new(
+            CardSet::new([
+                CardDef::new("Copper", 0).resources(1).starter(7).supply(30),
+                CardDef::new("Estate", 2).starter(3).supply(8),
+                CardDef::new("Silver", 3).resources(2).supply(30),
+                CardDef::new("Village", 2).market(6),
+            ]),
+            2,
+            7,
+        )
+    }
+
+    /// Plays a few turns buying whatever is affordable, returning the whole event log
+    fn play(game: &mut Game, turns: usize) -> Vec<Event> {
+        let silver = game.card_set().find("Silver").unwrap();
+        let mut log = vec![];
+
+        for _ in 0..turns {
+            let player = game.active();
+            for card in game.player(player).hand().to_vec() {
+                log.extend(game.apply(player, Action::PlayCard(card)).unwrap());
+            }
+
+            log.extend(game.apply(player, Action::EndPhase).unwrap());
+            let purchase = match game.supply().market().first() {
+                Some(&card) if game.player(player).resources() < 3 => Purchase::Market(card),
+                _ => Purchase::Pile(silver),
+            };
+            if let Ok(events) = game.apply(player, Action::BuyCard(purchase)) {
+                log.extend(events);
+            }
+
+            log.extend(game.apply(player, Action::EndTurn).unwrap());
+        }
+
+        log
+    }
+
+    #[test]
+    fn replay_rebuilds_identical_state() {
+        let mut game = Game::new(setup()).unwrap();
+        let log = play(&mut game, 12);
+
+        let replayed = Game::replay(setup(), &log).unwrap();
+        assert_eq!(replayed, game);
+    }
+
+    #[test]
+    fn replay_of_prefix_rebuilds_intermediate_state() {
+        let mut game = Game::new(setup()).unwrap();
+        let mut log = play(&mut game, 3);
+        let intermediate = game.clone();
+        let prefix = log.len();
+        log.extend(play(&mut game, 3));
+
+        let replayed = Game::replay(setup(), &log[..prefix]).unwrap();
+        assert_eq!(replayed, intermediate);
+    }
+
+    #[test]
+    fn log_survives_serialization() {
+        let mut game = Game::new(setup()).unwrap();
+        let log = play(&mut game, 6);
+
+        let serialized = serde_json::to_string(&log).unwrap();
+        let deserialized: Vec<Event> = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(deserialized, log);
+
+        let replayed = Game::replay(setup(), &deserialized).unwrap();
+        assert_eq!(replayed, game);
+    }
+
+    #[test]
+    fn inconsistent_log_is_rejected() {
+        let mut game = Game::new(setup()).unwrap();
+        let log = play(&mut game, 2);
+
+        let card = game.player(PlayerId::new(1)).hand()[0];
+        let bogus = Event::CardPlayed {
+            player: PlayerId::new(0),
+            card,
+        };
+        let log: Vec<_> = log.into_iter().chain([bogus.clone()]).collect();
+
+        assert_eq!(
+            Game::replay(setup(), &log),
+            Err(ReplayError::InvalidEvent {
+                index: log.len() - 1,
+                event: bogus
+            })
+        );
+    }
+}
@@ -0,0 +1,386 @@
+//! Enumeration of the legal actions
+//!
+//! Card choices are not enumerated, as a choice of any number of cards out of a big hand has
+//! exponentially many answers. They are described by the constraint every answer follows instead,
+//! and bots sample the answers they consider from it.
+
+use serde::{Deserialize, Serialize};
+
+use crate::Game;
+use crate::action::{Action, Choice, Purchase};
+use crate::card::CardId;
+use crate::decision::{Request, offered};
+use crate::phase::Phase;
+use crate::player::PlayerId;
+use crate::rng::Rng;
+
+/// Choice of any `min` to `max` distinct cards out of the offered ones, in any order
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CardChoice {
+    /// Cards to choose from
+    pub cards: Vec<CardId>,
+    /// Minimal number of the chosen cards
+    pub min: u32,
+    /// Maximal number of the chosen cards
+    pub max: u32,
+}
+
+impl CardChoice {
+    /// Checks if the cards are a valid choice
+    pub fn accepts(&self, chosen: &[CardId]) -> bool {
+        (self.min..=self.max).contains(&(chosen.len() as u32)) && offered(chosen, &self.cards)
+    }
+
+    /// Choice of the first `min` cards offered
+    pub fn first(&self) -> Choice {
+        Choice::Cards(self.cards[..self.min as usize].to_vec())
+    }
+
+    /// Random valid choice: the number of the cards is picked uniformly first, then the cards
+    pub fn sample(&self, rng: &mut Rng) -> Choice {
+        let count = self.min as usize + rng.below((self.max - self.min) as usize + 1);
+        let mut cards = self.cards.clone();
+        rng.shuffle(&mut cards);
+        cards.truncate(count);
+        cards.sort_by_key(|card| self.cards.iter().position(|c| c == card));
+        Choice::Cards(cards)
+    }
+}
+
+/// Actions the player can take
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LegalActions {
+    /// Every legal action but the card choices
+    pub actions: Vec<Action>,
+    /// Constraint of the card choices answering the pending decision
+    pub choice: Option<CardChoice>,
+}
+
+impl LegalActions {
+    /// Checks if the action is legal
+    pub fn contains(&self, action: &Action) -> bool {
+        match (action, &self.choice) {
+            (Action::ResolveChoice(Choice::Cards(chosen)), Some(choice)) => choice.accepts(chosen),
+            _ => self.actions.contains(action),
+        }
+    }
+
+    /// Lists the actions together with up to `samples` distinct card choices sampled from the
+    /// constraint
+    pub fn sample(&self, rng: &mut Rng, samples: usize) -> Vec<Action> {
+        let mut actions = self.actions.clone();
+        if let Some(choice) = &self.choice {
+            for _ in 0..samples {
+                let action = Action::ResolveChoice(choice.sample(rng));
+                if !actions.contains(&action) {
+                    actions.push(action);
+                }
+            }
+        }
+        actions
+    }
+}
+
+impl Game {
+    /// Every action the player can currently take: all of them are accepted by `apply`, and
+    /// every other action is rejected.
+    pub fn legal_actions(&self, player: PlayerId) -> LegalActions {
+        let mut legal = LegalActions::default();
+
+        if self.is_over() {
+            return legal;
+        }
+
+        let Some(state) = self.players.get(player.seat()) else {
+            return legal;
+        };
+
+        if state.conceded {
+            return legal;
+        }
+
+        let actions = &mut legal.actions;
+        match &self.pending {
+            Some(decision) if decision.player() == player => match decision.request() {
+                Request::Cards { cards, min, max } => {
+                    legal.choice = Some(CardChoice {
+                        cards: cards.clone(),
+                        min: *min,
+                        max: *max,
+                    });
+                }
+                Request::Options(options) => actions.extend(
+                    (0..options.len()).map(|idx| Action::ResolveChoice(Choice::Option(idx))),
+                ),
+                Request::Order(triggers) => actions.extend(
+                    (0..triggers.len()).map(|idx| Action::ResolveChoice(Choice::Option(idx))),
+                ),
+                Request::Reaction { cards } => actions.extend(
+                    std::iter::once(vec![])
+                        .chain(cards.iter().map(|card| vec![*card]))
+                        .map(|cards| Action::ResolveChoice(Choice::Cards(cards))),
+                ),
+            },
+            Some(_) => (),
+            None if player != self.active => (),
+            None => {
+                if self.phase == Phase::Action {
+                    actions.extend(state.hand.iter().map(|card| Action::PlayCard(*card)));
+                }
+
+                if self.phase == Phase::Buy {
+                    let affordable = |cost: u32| cost <= state.resources;
+                    let piles = self
+                        .supply
+                        .piles()
+                        .iter()
+                        .filter(|pile| {
+                            pile.count > 0 && affordable(self.card_set.get(pile.card).cost)
+                        })
+                        .map(|pile| Purchase::Pile(pile.card));
+                    let market = self
+                        .supply
+                        .market()
+                        .iter()
+                        .filter(|card| affordable(self.card(**card).cost))
+                        .map(|card| Purchase::Market(*card));
+                    actions.extend(piles.chain(market).map(Action::BuyCard));
+                }
+
+                if matches!(self.phase, Phase::Action | Phase::Buy) {
+                    actions.extend([Action::EndPhase, Action::EndTurn]);
+                }
+            }
+        }
+
+        if player == self.active && self.can_undo() {
+            actions.push(Action::Undo);
+        }
+        actions.push(Action::Concede);
+        legal
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::Setup;
+    use crate::card::CardSet;
+    use crate::player::Zone;
+    use crate::rng::Rng;
+    use crate::test_util::{game, give};
+
+    /// Basic card set with cards requiring every kind of decision added to the starting decks
+    fn card_set() -> CardSet {
+        CardSet::new(CardSet::basic().iter().map(|(_, def)| match &def.name[..] {
+            "Recycler" | "Emissary" | "Raider" | "Guard" => def.clone().starter(1),
+            _ => def.clone(),
+        }))
+    }
+
+    /// Generated action, with the cards picked by their index in the state it is tried in
+    #[derive(Debug, Clone)]
+    enum Probe {
+        Play(usize),
+        BuyPile(usize),
+        BuyMarket(usize),
+        EndPhase,
+        EndTurn,
+        Undo,
+        Concede,
+        Option(usize),
+        /// Cards from the player's hand, or any cards if the flag is set
+        Cards(Vec<(bool, usize)>),
+    }
+
+    fn probe() -> impl Strategy<Value = Probe> {
+        prop_oneof![
+            1 => any::<usize>().prop_map(Probe::Play),
+            1 => any::<usize>().prop_map(Probe::BuyPile),
+            1 => any::<usize>().prop_map(Probe::BuyMarket),
+            1 => Just(Probe::EndPhase),
+            1 => Just(Probe::EndTurn),
+            1 => Just(Probe::Undo),
+            1 => Just(Probe::Concede),
+            1 => (0..4usize).prop_map(Probe::Option),
+            3 => vec((any::<bool>(), any::<usize>()), 0..4).prop_map(Probe::Cards),
+        ]
+    }
+
+    impl Probe {
+        /// Turns the probe into the player's action in the game
+        fn action(&self, game: &Game, player: PlayerId) -> Action {
+            // Every card in the game and one which does not exist
+            let cards: Vec<CardId> = game
+                .players()
+                .iter()
+                .flat_map(|player| Zone::ALL.iter().flat_map(|zone| player.zone(*zone)))
+                .chain(game.supply().market())
+                .chain(game.supply().market_deck())
+                .copied()
+                .chain([game.cards().next_id()])
+                .collect();
+            let defs: Vec<_> = game.card_set().iter().map(|(def, _)| def).collect();
+            let hand = game.player(player).hand();
+            let pick = |(any, idx): (bool, usize)| match any || hand.is_empty() {
+                true => cards[idx % cards.len()],
+                false => hand[idx % hand.len()],
+            };
+
+            match self {
+                Probe::Play(idx) => Action::PlayCard(pick((false, *idx))),
+                Probe::BuyPile(idx) => Action::BuyCard(Purchase::Pile(defs[idx % defs.len()])),
+                Probe::BuyMarket(idx) => {
+                    let market = game.supply().market();
+                    match market.is_empty() {
+                        true => Action::BuyCard(Purchase::Market(pick((true, *idx)))),
+                        false => Action::BuyCard(Purchase::Market(market[idx % market.len()])),
+                    }
+                }
+                Probe::EndPhase => Action::EndPhase,
+                Probe::EndTurn => Action::EndTurn,
+                Probe::Undo => Action::Undo,
+                Probe::Concede => Action::Concede,
+                Probe::Option(idx) => Action::ResolveChoice(Choice::Option(*idx)),
+                Probe::Cards(picks) => {
+                    Action::ResolveChoice(Choice::Cards(picks.iter().copied().map(pick).collect()))
+                }
+            }
+        }
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn legal_actions_match_apply(
+            seed: u64,
+            players in 2..=4usize,
+            steps in vec(any::<u64>(), 0..80),
+            probes in vec(probe(), 1..40),
+        ) {
+            let mut game = Game::new(Setup::new(card_set(), players, seed)).unwrap();
+
+            for step in steps {
+                for player in game.player_ids() {
+                    let legal = game.legal_actions(player);
+
+                    let mut rng = Rng::new(step);
+                    for action in legal.sample(&mut rng, 4) {
+                        prop_assert!(
+                            game.clone().apply(player, action.clone()).is_ok(),
+                            "{player}: {action:?} in {legal:?}"
+                        );
+                    }
+
+                    for probe in &probes {
+                        let action = probe.action(&game, player);
+                        let accepted = game.clone().apply(player, action.clone()).is_ok();
+                        prop_assert_eq!(
+                            accepted,
+                            legal.contains(&action),
+                            "{}: {:?} in {:?}",
+                            player,
+                            action,
+                            legal
+                        );
+                    }
+                }
+
+                let Some(player) = game.waiting_for() else {
+                    break;
+                };
+
+                // Playing and buying cards is preferred to get to the more interesting states
+                let mut rng = Rng::new(step);
+                let (moves, ends): (Vec<_>, Vec<_>) = game
+                    .legal_actions(player)
+                    .sample(&mut rng, 1)
+                    .into_iter()
+                    .filter(|action| *action != Action::Concede)
+                    .partition(|action| !matches!(action, Action::EndPhase | Action::EndTurn));
+                let patience = match game.phase() {
+                    Phase::Buy => 2,
+                    _ => 6,
+                };
+                let legal = match moves.is_empty() || !ends.is_empty() && rng.below(patience) == 0 {
+                    true => ends,
+                    false => moves,
+                };
+                let action = legal[rng.below(legal.len())].clone();
+                game.apply(player, action).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn listing_actions() {
+        let mut game = Game::new(Setup::new(CardSet::basic(), 2, 2)).unwrap();
+        let player = game.active();
+        let other = game.next_player(player);
+
+        assert_eq!(game.legal_actions(other).actions, [Action::Concede]);
+        assert_eq!(
+            game.legal_actions(PlayerId::new(2)),
+            LegalActions::default()
+        );
+
+        let legal = game.legal_actions(player);
+        for card in game.player(player).hand() {
+            assert!(legal.contains(&Action::PlayCard(*card)));
+        }
+        assert!(legal.contains(&Action::EndTurn));
+
+        game.apply(player, Action::EndPhase).unwrap();
+        let copper = game.card_set().find("Copper").unwrap();
+        let gold = game.card_set().find("Gold").unwrap();
+        let legal = game.legal_actions(player);
+        assert!(legal.contains(&Action::BuyCard(Purchase::Pile(copper))));
+        assert!(!legal.contains(&Action::BuyCard(Purchase::Pile(gold))));
+
+        game.apply(other, Action::Concede).unwrap();
+        assert_eq!(game.legal_actions(player), LegalActions::default());
+    }
+
+    #[test]
+    fn card_choices_are_constrained() {
+        let mut game = game(CardSet::basic());
+        let player = game.active();
+        let card = give(&mut game, player, "Recycler");
+        let hand = game.player(player).hand()[..5].to_vec();
+        game.apply(player, Action::PlayCard(card)).unwrap();
+
+        let legal = game.legal_actions(player);
+        let choice = CardChoice {
+            cards: hand.clone(),
+            min: 0,
+            max: 2,
+        };
+        assert_eq!(legal.choice.as_ref(), Some(&choice));
+        assert!(
+            !legal
+                .actions
+                .iter()
+                .any(|action| matches!(action, Action::ResolveChoice(_)))
+        );
+
+        let chosen = |cards: Vec<CardId>| Action::ResolveChoice(Choice::Cards(cards));
+        assert!(legal.contains(&chosen(vec![])));
+        assert!(legal.contains(&chosen(vec![hand[3], hand[1]])));
+        assert!(!legal.contains(&chosen(vec![hand[1], hand[1]])));
+        assert!(!legal.contains(&chosen(hand[..3].to_vec())));
+        assert!(!legal.contains(&chosen(vec![card])));
+        assert_eq!(choice.first(), Choice::Cards(vec![]));
+
+        let mut rng = Rng::new(3);
+        for _ in 0..20 {
+            let Choice::Cards(sampled) = choice.sample(&mut rng) else {
+                panic!("Card choice expected");
+            };
+            assert!(choice.accepts(&sampled));
+        }
+    }
+}
@@ -1,4 +1,598 @@
 //! Deckbuilder game logic implementation.
+//!
+//! The crate is a pure, IO-free model of the game state. All the physical cards are instances of
+//! card definitions from the `CardSet` and are identified with stable `CardId`s. Every card is
+//! always in exactly one place: one of the players' zones, the market row or the market deck.
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::event::Inconsistent;
+
+pub mod action;
+pub mod base;
+pub mod bot;
+pub mod card;
+pub mod commitment;
+pub mod config;
+pub mod decision;
+pub mod effect;
+pub mod end;
+pub mod event;
+pub mod legal;
+pub mod loader;
+pub mod log;
+pub mod phase;
+pub mod player;
+pub mod rng;
+pub mod snapshot;
+pub mod supply;
+pub mod team;
+pub mod trigger;
+mod undo;
+pub mod view;
+
+pub use action::{Action, Choice, Purchase, Rejection};
+pub use bot::{Greedy, Mcts, Random, Strategy};
+pub use card::{CardDef, CardDefId, CardId, CardSet, CardType, Cards};
+pub use commitment::{Nonce, VerifyError};
+pub use config::{FirstPlayer, GameConfig, MAX_HAND_SIZE, MAX_MARKET_SIZE, MAX_STARTING_DECK};
+pub use decision::{Decision, Request};
+pub use effect::{CardFilter, Condition, Effect, Selection, Target};
+pub use end::{EndCondition, EndReason, EndRules, GameResult, TieBreaker};
+pub use event::{Event, ReplayError};
+pub use legal::{CardChoice, LegalActions};
+pub use loader::LoadError;
+pub use log::LogEntry;
+pub use phase::Phase;
+pub use player::{Player, PlayerId, Zone};
+pub use rng::Rng;
+pub use snapshot::SnapshotError;
+pub use supply::{Pile, Supply};
+pub use team::{Shared, Teams};
+pub use trigger::{Registration, Trigger, When};
+pub use view::{BaseView, CardView, DecisionView, PlayerView, SeatView, SupplyView};
+
+/// Minimal number of players in the game
+pub const MIN_PLAYERS: usize = 2;
+
+/// Maximal number of players in the game
+pub const MAX_PLAYERS: usize = 6;
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum Error {
+    #[error("Invalid number of players: {0}")]
+    InvalidPlayerCount(usize),
+    #[error("Card set has no starting deck")]
+    EmptyStartingDeck,
+    #[error("Card {card} refers to unknown card {missing}")]
+    UnknownCard { card: String, missing: String },
+    #[error("Starting deck refers to unknown card {0}")]
+    UnknownStarter(String),
+    #[error("No player in seat {0} to take the first turn")]
+    InvalidFirstPlayer(usize),
+    #[error("Teams have to cover every seat with at least two teams")]
+    InvalidTeams,
+    #[error("Starting deck of {0} cards is bigger than {MAX_STARTING_DECK}")]
+    StartingDeckTooBig(u64),
+    #[error("Hand size {0} is bigger than {MAX_HAND_SIZE}")]
+    HandSizeTooBig(usize),
+    #[error("Market size {0} is bigger than {MAX_MARKET_SIZE}")]
+    MarketSizeTooBig(usize),
+}
+
+/// Everything needed to create the initial state of a game
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Setup {
+    /// Card definitions the game is played with
+    pub card_set: CardSet,
+    /// Number of players
+    pub players: usize,
+    /// Seed of the game random number generator
+    pub seed: u64,
+    /// Rules variant of the game
+    #[serde(default)]
+    pub config: GameConfig,
+}
+
+impl Setup {
+    /// Creates the game setup
+    pub fn new(card_set: CardSet, players: usize, seed: u64) -> Self {
+        Self {
+            card_set,
+            players,
+            seed,
+            config: GameConfig::default(),
+        }
+    }
+
+    /// Sets the rules variant of the game
+    pub fn config(self, config: GameConfig) -> Self {
+        Self { config, ..self }
+    }
+}
 
 /// A single game instance. Contains all the game state.
-pub struct Game;
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Game {
+    /// Card definitions the game is played with
+    card_set: CardSet,
+    /// All card instances
+    cards: Cards,
+    /// Players in the seat order
+    players: Vec<Player>,
+    /// Supply piles and market
+    supply: Supply,
+    /// Player owning the current turn
+    active: PlayerId,
+    /// Current turn number, starting from `1`
+    turn: u32,
+    /// Phase of the current turn
+    phase: Phase,
+    /// Source of all the randomness in the game
+    rng: Rng,
+    /// Decision the game waits for
+    pending: Option<Decision>,
+    /// Players protected from the attack being resolved
+    unaffected: Vec<PlayerId>,
+    /// Triggers of the cards in play, in the order they were played
+    triggers: Vec<Registration>,
+    /// Triggers fired by the events emitted since they were last scheduled. Every action
+    /// schedules them all before it returns, so they are never a part of a snapshot.
+    #[serde(skip)]
+    fired: Vec<Registration>,
+    /// Cards whose ally abilities resolved this turn
+    allied: Vec<CardId>,
+    /// Rules variant of the game
+    config: GameConfig,
+    /// Outcome of the game, once it is over
+    result: Option<GameResult>,
+    /// State to return to on undo, from before the first action since the last irreversible one.
+    /// It is rebuilt by folding events, so it is never a part of a snapshot.
+    #[serde(skip)]
+    checkpoint: Option<Box<Game>>,
+}
+
+impl Game {
+    /// Sets up a new game.
+    ///
+    /// Every player gets their starting deck and draws an initial hand, supply piles are filled
+    /// and the market row is revealed, all as the config says. Starting decks and the market deck
+    /// are shuffled, and the first player and supply piles are chosen at random if the config asks
+    /// for it, all using the RNG seeded from the setup.
+    pub fn new(setup: Setup) -> Result<Self, Error> {
+        let Setup {
+            card_set,
+            players,
+            seed,
+            config,
+        } = setup;
+
+        if !(MIN_PLAYERS..=MAX_PLAYERS).contains(&players) {
+            return Err(Error::InvalidPlayerCount(players));
+        }
+
+        config.validate()?;
+
+        if let Some(deck) = &config.starting_deck
+            && let Some(missing) = deck.keys().find(|name| card_set.find(name).is_none())
+        {
+            return Err(Error::UnknownStarter(missing.clone()));
+        }
+
+        let starters: Vec<_> = card_set
+            .iter()
+            .map(|(id, def)| match &config.starting_deck {
+                Some(deck) => (id, deck.get(&def.name).copied().unwrap_or(0)),
+                None => (id, def.starter),
+            })
+            .collect();
+        if starters.iter().all(|(_, count)| *count == 0) {
+            return Err(Error::EmptyStartingDeck);
+        }
+
+        if let FirstPlayer::Seat(seat) = config.first_player
+            && seat >= players
+        {
+            return Err(Error::InvalidFirstPlayer(seat));
+        }
+
+        if let Some(teams) = &config.teams
+            && !teams.is_valid(players)
+        {
+            return Err(Error::InvalidTeams);
+        }
+
+        if let Some((card, missing)) = card_set.unknown_reference() {
+            return Err(Error::UnknownCard {
+                card: card.to_owned(),
+                missing: missing.to_owned(),
+            });
+        }
+
+        let mut rng = Rng::new(seed);
+        let active = match config.first_player {
+            FirstPlayer::Random => PlayerId::new(rng.below(players)),
+            FirstPlayer::Seat(seat) => PlayerId::new(seat),
+        };
+        let mut cards = Cards::default();
+
+        let players = (0..players)
+            .map(|seat| {
+                let mut player = Player {
+                    health: config.end.health,
+                    turns: (seat == active.seat()) as u32,
+                    ..Player::default()
+                };
+                let deck = player.zone_mut(Zone::Deck);
+                *deck = starters
+                    .iter()
+                    .flat_map(|(id, count)| std::iter::repeat_n(*id, *count as usize))
+                    .map(|def| cards.create(def))
+                    .collect();
+                rng.shuffle(deck);
+                player
+            })
+            .collect();
+
+        let mut piles: Vec<_> = card_set
+            .iter()
+            .filter(|(_, def)| def.supply > 0)
+            .map(|(card, def)| Pile {
+                card,
+                count: def.supply,
+            })
+            .collect();
+
+        let mut market_deck: Vec<_> = card_set
+            .iter()
+            .flat_map(|(id, def)| std::iter::repeat_n(id, def.market as usize))
+            .map(|def| cards.create(def))
+            .collect();
+        rng.shuffle(&mut market_deck);
+
+        if let Some(count) = config.supply_piles {
+            rng.shuffle(&mut piles);
+            piles.truncate(count);
+            piles.sort_by_key(|pile| pile.card);
+        }
+
+        let mut game = Self {
+            card_set,
+            cards,
+            players,
+            supply: Supply::new(piles, market_deck),
+            active,
+            turn: 1,
+            phase: Phase::Start,
+            rng,
+            pending: None,
+            unaffected: vec![],
+            triggers: vec![],
+            fired: vec![],
+            allied: vec![],
+            config,
+            result: None,
+            checkpoint: None,
+        };
+
+        let mut events = vec![];
+        for player in game.player_ids() {
+            game.draw(&mut events, player, game.config.hand_size);
+        }
+        game.refill_market(&mut events);
+        game.start_turn(&mut events);
+
+        Ok(game)
+    }
+
+    /// Rules variant the game is played with
+    pub fn config(&self) -> &GameConfig {
+        &self.config
+    }
+
+    /// Card definitions used in the game
+    pub fn card_set(&self) -> &CardSet {
+        &self.card_set
+    }
+
+    /// Returns the definition of the card instance
+    pub fn card(&self, card: CardId) -> &CardDef {
+        self.card_set.get(self.cards.def(card))
+    }
+
+    /// All the card instances
+    pub fn cards(&self) -> &Cards {
+        &self.cards
+    }
+
+    /// Returns the player state
+    pub fn player(&self, player: PlayerId) -> &Player {
+        &self.players[player.seat()]
+    }
+
+    /// Players in the seat order
+    pub fn players(&self) -> &[Player] {
+        &self.players
+    }
+
+    /// Ids of all the players in the seat order
+    pub fn player_ids(&self) -> impl Iterator<Item = PlayerId> + use<> {
+        (0..self.players.len()).map(PlayerId::new)
+    }
+
+    /// Shared supply
+    pub fn supply(&self) -> &Supply {
+        &self.supply
+    }
+
+    /// Player owning the current turn
+    pub fn active(&self) -> PlayerId {
+        self.active
+    }
+
+    /// Current turn number
+    pub fn turn(&self) -> u32 {
+        self.turn
+    }
+
+    /// Phase of the current turn
+    pub fn phase(&self) -> Phase {
+        self.phase
+    }
+
+    /// Non-conceded player seated after the given one
+    pub fn next_player(&self, player: PlayerId) -> PlayerId {
+        let seats = self.players.len();
+        (1..=seats)
+            .map(|offset| PlayerId::new((player.seat() + offset) % seats))
+            .find(|next| !self.player(*next).conceded)
+            .unwrap_or(player)
+    }
+
+    /// Non-conceded players other than the given one, in the seat order starting after them
+    pub fn others(&self, player: PlayerId) -> Vec<PlayerId> {
+        let mut others = vec![];
+        let mut next = self.next_player(player);
+        while next != player && !others.contains(&next) {
+            others.push(next);
+            next = self.next_player(next);
+        }
+        others
+    }
+
+    /// Non-conceded players not on the player's team, in the seat order starting after them
+    pub fn opponents(&self, player: PlayerId) -> Vec<PlayerId> {
+        let others = self.others(player).into_iter();
+        others
+            .filter(|other| !self.allied(player, *other))
+            .collect()
+    }
+
+    /// Checks if the game is over
+    pub fn is_over(&self) -> bool {
+        self.result.is_some()
+    }
+
+    /// Mutable access to the player state
+    fn player_mut(&mut self, player: PlayerId) -> Result<&mut Player, Inconsistent> {
+        self.players.get_mut(player.seat()).ok_or(Inconsistent)
+    }
+
+    /// Moves a card between zones. Fails if the card was not in the source zone.
+    fn move_card(
+        &mut self,
+        card: CardId,
+        from: (PlayerId, Zone),
+        to: (PlayerId, Zone),
+    ) -> Result<(), Inconsistent> {
+        self.player_mut(to.0)?;
+        if !self.player_mut(from.0)?.take(from.1, card) {
+            return Err(Inconsistent);
+        }
+
+        self.player_mut(to.0)?.zone_mut(to.1).push(card);
+        Ok(())
+    }
+}
+
+/// Fixtures shared by the tests of all the modules
+#[cfg(test)]
+mod test_util {
+    use crate::card::{CardId, CardSet};
+    use crate::config::{FirstPlayer, GameConfig};
+    use crate::{Game, PlayerId, Setup, Zone};
+
+    /// Two player game with the cards, started by the player in the first seat
+    pub(crate) fn game(cards: CardSet) -> Game {
+        let config = GameConfig {
+            first_player: FirstPlayer::Seat(0),
+            ..GameConfig::default()
+        };
+        Game::new(Setup::new(cards, 2, 4).config(config)).unwrap()
+    }
+
+    /// Gives the player a new instance of the card to their hand
+    pub(crate) fn give(game: &mut Game, player: PlayerId, name: &str) -> CardId {
+        put(game, player, Zone::Hand, name)
+    }
+
+    /// Puts a new instance of the card on top of the player's zone
+    pub(crate) fn put(game: &mut Game, player: PlayerId, zone: Zone, name: &str) -> CardId {
+        let def = game.card_set().find(name).unwrap();
+        let card = game.cards.create(def);
+        game.players[player.seat()].zone_mut(zone).push(card);
+        card
+    }
+
+    pub(crate) fn p(seat: usize) -> PlayerId {
+        PlayerId::new(seat)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::p;
+
+    fn card_set() -> CardSet {
+        CardSet::new([
+            CardDef::new("Copper", 0).starter(7).supply(30),
+            CardDef::new("Estate", 2).starter(3).supply(8),
+            CardDef::new("Smithy", 4).market(4),
+            CardDef::new("Village", 3).market(4),
+        ])
+    }
+
+    #[test]
+    fn setup() {
+        let game = Game::new(Setup::new(card_set(), 2, 1)).unwrap();
+        let GameConfig {
+            hand_size,
+            market_size,
+            ..
+        } = GameConfig::default();
+
+        assert_eq!(game.active(), p(1));
+        assert_eq!(game.turn(), 1);
+
+        for player in game.players() {
+            assert_eq!(player.hand().len(), hand_size);
+            assert_eq!(player.deck().len(), 10 - hand_size);
+            assert!(player.discard().is_empty());
+            assert!(player.in_play().is_empty());
+            assert!(player.trash().is_empty());
+        }
+
+        assert_eq!(game.supply().piles().len(), 2);
+        assert_eq!(game.supply().market().len(), market_size);
+        assert_eq!(game.supply().market_deck().len(), 8 - market_size);
+        assert_eq!(game.cards().len(), 2 * 10 + 8);
+    }
+
+    #[test]
+    fn invalid_setup() {
+        assert_eq!(
+            Game::new(Setup::new(card_set(), 1, 1)),
+            Err(Error::InvalidPlayerCount(1))
+        );
+
+        let no_starters = CardSet::new([CardDef::new("Copper", 0).supply(30)]);
+        assert_eq!(
+            Game::new(Setup::new(no_starters, 2, 1)),
+            Err(Error::EmptyStartingDeck)
+        );
+
+        let unknown = CardSet::new([CardDef::new("Copper", 0).starter(7).effect(Effect::Gain {
+            card: "Gold".to_owned(),
+            to: Zone::Hand,
+        })]);
+        assert_eq!(
+            Game::new(Setup::new(unknown, 2, 1)),
+            Err(Error::UnknownCard {
+                card: "Copper".to_owned(),
+                missing: "Gold".to_owned()
+            })
+        );
+    }
+
+    #[test]
+    fn every_card_in_exactly_one_place() {
+        let game = Game::new(Setup::new(card_set(), 2, 1)).unwrap();
+
+        let mut seen: Vec<CardId> = game
+            .players()
+            .iter()
+            .flat_map(|player| Zone::ALL.iter().flat_map(|zone| player.zone(*zone)))
+            .chain(game.supply().market())
+            .chain(game.supply().market_deck())
+            .copied()
+            .collect();
+        seen.sort();
+        seen.dedup();
+
+        assert_eq!(seen.len(), game.cards().len());
+    }
+
+    #[test]
+    fn turns_pass_around_the_table() {
+        let mut game = Game::new(Setup::new(card_set(), 2, 1)).unwrap();
+
+        game.apply(p(1), Action::EndTurn).unwrap();
+        assert_eq!(game.active(), p(0));
+        assert_eq!(game.turn(), 2);
+
+        game.apply(p(0), Action::EndTurn).unwrap();
+        assert_eq!(game.active(), p(1));
+        assert_eq!(game.turn(), 3);
+    }
+
+    #[test]
+    fn playing_with_more_players() {
+        let mut game = Game::new(Setup::new(card_set(), 4, 1)).unwrap();
+        let first = game.active();
+        let seat = |offset: usize| p((first.seat() + offset) % 4);
+        assert_eq!(game.players().len(), 4);
+        assert_eq!(game.supply().market().len(), 5);
+        assert_eq!(game.opponents(first), [seat(1), seat(2), seat(3)]);
+
+        game.apply(seat(0), Action::EndTurn).unwrap();
+        assert_eq!(game.active(), seat(1));
+
+        // Conceded players are skipped in the turn order
+        game.apply(seat(2), Action::Concede).unwrap();
+        game.apply(seat(1), Action::EndTurn).unwrap();
+        assert_eq!(game.active(), seat(3));
+        assert_eq!(game.opponents(seat(3)), [seat(0), seat(1)]);
+        assert!(!game.is_over());
+
+        assert_eq!(
+            Game::new(Setup::new(card_set(), 7, 1)),
+            Err(Error::InvalidPlayerCount(7))
+        );
+    }
+
+    #[test]
+    fn seed_determines_the_game() {
+        let game1 = Game::new(Setup::new(card_set(), 2, 5)).unwrap();
+        let game2 = Game::new(Setup::new(card_set(), 2, 5)).unwrap();
+        let game3 = Game::new(Setup::new(card_set(), 2, 6)).unwrap();
+
+        assert_eq!(game1, game2);
+        assert_ne!(game1, game3);
+    }
+
+    #[test]
+    fn pinned_seed_draws() {
+        let mut game = Game::new(Setup::new(card_set(), 2, 5)).unwrap();
+        let player = game.active();
+        let other = game.next_player(player);
+        let hand = |game: &Game| -> Vec<String> {
+            game.player(player)
+                .hand()
+                .iter()
+                .map(|card| game.card(*card).name.clone())
+                .collect()
+        };
+
+        assert_eq!(player, p(0));
+        assert_eq!(
+            hand(&game),
+            ["Copper", "Estate", "Copper", "Copper", "Estate"]
+        );
+
+        game.apply(player, Action::EndTurn).unwrap();
+        game.apply(other, Action::EndTurn).unwrap();
+        assert_eq!(
+            hand(&game),
+            ["Estate", "Copper", "Copper", "Copper", "Copper"]
+        );
+
+        // Deck is exhausted - this draw comes from the reshuffled discard pile
+        game.apply(player, Action::EndTurn).unwrap();
+        assert_eq!(
+            hand(&game),
+            ["Copper", "Copper", "Copper", "Copper", "Copper"]
+        );
+    }
+}
@@ -0,0 +1,310 @@
+//! Loading card definitions from TOML files
+//!
+//! Every card set file contains a list of `[[card]]` tables, each deserialized into a `CardDef`:
+//!
+//! ```toml
+//! [[card]]
+//! name = "Silver"
+//! cost = 3
+//! types = ["treasure"]
+//! effects = [{ resources = 2 }]
+//! supply = 40
+//! ```
+//!
+//! A card set directory is loaded by reading all its `*.toml` files in the file name order.
+
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+
+use crate::card::{CardDef, CardSet};
+
+#[derive(Debug, Error)]
+pub enum LoadError {
+    #[error("Cannot read {path}: {source}")]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("{file}: {message}")]
+    Syntax { file: PathBuf, message: String },
+    #[error("{file}: card {card}: {message}")]
+    InvalidCard {
+        file: PathBuf,
+        card: String,
+        message: String,
+    },
+    #[error("{file}: card {card} is already defined in {previous}")]
+    DuplicateCard {
+        file: PathBuf,
+        card: String,
+        previous: PathBuf,
+    },
+    #[error("{file}: card {card} refers to unknown card {missing}")]
+    UnknownCard {
+        file: PathBuf,
+        card: String,
+        missing: String,
+    },
+}
+
+/// Parses card definitions from the TOML source. `file` is only used for error reporting.
+pub fn parse(file: impl AsRef<Path>, source: &str) -> Result<Vec<CardDef>, LoadError> {
+    let file = file.as_ref();
+    let syntax = |message: String| LoadError::Syntax {
+        file: file.to_owned(),
+        message,
+    };
+
+    let mut table: toml::Table = source
+        .parse()
+        .map_err(|err: toml::de::Error| syntax(err.to_string()))?;
+
+    let cards = match table.remove("card") {
+        Some(toml::Value::Array(cards)) => cards,
+        Some(_) => return Err(syntax("`card` must be an array of tables".to_owned())),
+        None => vec![],
+    };
+
+    if let Some(key) = table.keys().next() {
+        return Err(syntax(format!("unexpected key `{key}`")));
+    }
+
+    let mut defs: Vec<CardDef> = vec![];
+    for (idx, card) in cards.into_iter().enumerate() {
+        // Name is extracted up front, so even malformed cards are reported by their name
+        let name = card
+            .get("name")
+            .and_then(|name| name.as_str())
+            .map(|name| name.to_owned())
+            .unwrap_or_else(|| format!("#{}", idx + 1));
+
+        let invalid = |message: String| LoadError::InvalidCard {
+            file: file.to_owned(),
+            card: name.clone(),
+            message,
+        };
+
+        let def: CardDef = card
+            .try_into()
+            .map_err(|err: toml::de::Error| invalid(err.message().to_owned()))?;
+
+        if def.name.trim().is_empty() {
+            return Err(invalid("name cannot be empty".to_owned()));
+        }
+
+        if defs.iter().any(|other| other.name == def.name) {
+            return Err(LoadError::DuplicateCard {
+                file: file.to_owned(),
+                card: def.name,
+                previous: file.to_owned(),
+            });
+        }
+
+        defs.push(def);
+    }
+
+    Ok(defs)
+}
+
+/// Loads the card set from all the `*.toml` files in the directory
+pub fn load_dir(dir: impl AsRef<Path>) -> Result<CardSet, LoadError> {
+    let dir = dir.as_ref();
+    let io = |path: &Path| {
+        let path = path.to_owned();
+        move |source| LoadError::Io { path, source }
+    };
+
+    let mut files = std::fs::read_dir(dir)
+        .map_err(io(dir))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(io(dir))?;
+    files.retain(|path| path.extension().is_some_and(|ext| ext == "toml"));
+    files.sort();
+
+    let mut defs: Vec<(PathBuf, CardDef)> = vec![];
+    for file in files {
+        let source = std::fs::read_to_string(&file).map_err(io(&file))?;
+
+        for def in parse(&file, &source)? {
+            if let Some((previous, _)) = defs.iter().find(|(_, other)| other.name == def.name) {
+                return Err(LoadError::DuplicateCard {
+                    file,
+                    card: def.name,
+                    previous: previous.clone(),
+                });
+            }
+
+            defs.push((file.clone(), def));
+        }
+    }
+
+    for (file, def) in &defs {
+        for name in def.references() {
+            if !defs.iter().any(|(_, other)| other.name == name) {
+                return Err(LoadError::UnknownCard {
+                    file: file.clone(),
+                    card: def.name.clone(),
+                    missing: name.to_owned(),
+                });
+            }
+        }
+    }
+
+    Ok(CardSet::new(defs.into_iter().map(|(_, def)| def)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::card::CardType;
+
+    #[test]
+    fn parsing_cards() {
+        let defs = parse(
+            "test.toml",
+            r#"
+                [[card]]
+                name = "Village"
+                cost = 3
+                types = ["action"]
+                factions = ["farmers"]
+                market = 10
+
+                [[card]]
+                name = "Copper"
+                cost = 0
+                effects = [{ resources = 1 }]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            defs,
+            [
+                CardDef::new("Village", 3)
+                    .types([CardType::Action])
+                    .faction("farmers")
+                    .market(10),
+                CardDef::new("Copper", 0).resources(1),
+            ]
+        );
+    }
+
+    #[test]
+    fn basic_set_is_valid() {
+        let set = CardSet::basic();
+        assert!(set.find("Copper").is_some());
+        assert_eq!(set.unknown_reference(), None);
+        assert!(set.iter().any(|(_, def)| def.starter > 0));
+    }
+
+    #[test]
+    fn errors_point_at_the_card() {
+        let err = parse(
+            "broken.toml",
+            r#"
+                [[card]]
+                name = "Village"
+                cost = 3
+
+                [[card]]
+                name = "Smithy"
+                cost = "four"
+            "#,
+        )
+        .unwrap_err();
+
+        let LoadError::InvalidCard { file, card, .. } = &err else {
+            panic!("Unexpected error: {err}");
+        };
+        assert_eq!(file, Path::new("broken.toml"));
+        assert_eq!(card, "Smithy");
+        assert!(err.to_string().starts_with("broken.toml: card Smithy: "));
+
+        let err = parse(
+            "broken.toml",
+            r#"
+                [[card]]
+                name = "Village"
+                cost = 3
+                kost = 4
+            "#,
+        )
+        .unwrap_err();
+        assert!(
+            matches!(&err, LoadError::InvalidCard { card, message, .. } if card == "Village" && message.contains("kost"))
+        );
+
+        let err = parse("broken.toml", "[[card]]\ncost = 3").unwrap_err();
+        assert!(matches!(&err, LoadError::InvalidCard { card, .. } if card == "#1"));
+
+        let err = parse("broken.toml", "[[card]\nname = ").unwrap_err();
+        assert!(matches!(err, LoadError::Syntax { .. }));
+    }
+
+    #[test]
+    fn duplicates_are_rejected() {
+        let err = parse(
+            "dup.toml",
+            r#"
+                [[card]]
+                name = "Village"
+                cost = 3
+
+                [[card]]
+                name = "Village"
+                cost = 4
+            "#,
+        )
+        .unwrap_err();
+
+        assert!(matches!(err, LoadError::DuplicateCard { card, .. } if card == "Village"));
+    }
+
+    #[test]
+    fn loading_directory() {
+        let dir = std::env::temp_dir().join(format!("card-set-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("a.toml"),
+            "[[card]]\nname = \"Copper\"\ncost = 0\nstarter = 7\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("b.toml"),
+            "[[card]]\nname = \"Estate\"\ncost = 2\nstarter = 3\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("notes.txt"), "not a card file").unwrap();
+
+        let set = load_dir(&dir).unwrap();
+        assert_eq!(
+            set.iter().map(|(_, def)| &def.name[..]).collect::<Vec<_>>(),
+            ["Copper", "Estate"]
+        );
+
+        std::fs::write(
+            dir.join("c.toml"),
+            "[[card]]\nname = \"Copper\"\ncost = 1\n",
+        )
+        .unwrap();
+        let err = load_dir(&dir).unwrap_err();
+        assert!(
+            matches!(&err, LoadError::DuplicateCard { file, previous, .. } if file.ends_with("c.toml") && previous.ends_with("a.toml"))
+        );
+
+        std::fs::write(
+            dir.join("c.toml"),
+            "[[card]]\nname = \"Mint\"\ncost = 5\neffects = [{ gain = { card = \"Gold\" } }]\n",
+        )
+        .unwrap();
+        let err = load_dir(&dir).unwrap_err();
+        assert!(
+            matches!(&err, LoadError::UnknownCard { card, missing, .. } if card == "Mint" && missing == "Gold")
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
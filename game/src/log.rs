@@ -0,0 +1,453 @@
+//! Human readable narrative of the game
+//!
+//! The event log is retold as sentences, roughly one per action, eg. "Alice played Smith and drew
+//! 2 cards". Like the views, the narrative is told to a single viewer: cards hidden from them are
+//! only counted, never named.
+
+use serde::{Deserialize, Serialize};
+
+use crate::action::{Choice, Purchase};
+use crate::card::CardId;
+use crate::decision::Request;
+use crate::event::{Event, ReplayError};
+use crate::phase::Phase;
+use crate::player::{PlayerId, Zone};
+use crate::team::Shared;
+use crate::{Game, Setup};
+
+/// Sentence of the narrative
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogEntry {
+    /// Index of the first event the sentence tells about in the event log
+    pub event: usize,
+    /// Turn the sentence belongs to
+    pub turn: u32,
+    /// The sentence itself
+    pub text: String,
+}
+
+/// Part of the sentence telling about a single event
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Clause {
+    /// Player did something
+    Did { player: PlayerId, text: String },
+    /// Player drew cards, named only if the viewer can see them
+    Drew {
+        player: PlayerId,
+        cards: Vec<Option<String>>,
+    },
+    /// Something happened with no player doing it
+    Happened(String),
+}
+
+/// Sentence being composed
+struct Sentence {
+    event: usize,
+    turn: u32,
+    clauses: Vec<Clause>,
+}
+
+impl Game {
+    /// Retells the event log folded over the setup as seen by the viewer. Without a viewer only
+    /// the public information is told. Players are called by their `names` in the seat order.
+    pub fn narrate<'a>(
+        setup: Setup,
+        events: impl IntoIterator<Item = &'a Event>,
+        viewer: Option<PlayerId>,
+        names: &[String],
+    ) -> Result<Vec<LogEntry>, ReplayError> {
+        let mut game = Self::new(setup)?;
+        let mut entries = vec![];
+        let mut sentence: Option<Sentence> = None;
+
+        for (index, event) in events.into_iter().enumerate() {
+            let clause = game.clause(event, viewer);
+            game.fold(event).map_err(|_| ReplayError::InvalidEvent {
+                index,
+                event: event.clone(),
+            })?;
+
+            if opens_sentence(event) || sentence.is_none() {
+                entries.extend(sentence.take().and_then(|s| s.finish(names)));
+                sentence = Some(Sentence {
+                    event: index,
+                    turn: game.turn,
+                    clauses: vec![],
+                });
+            }
+
+            if let (Some(sentence), Some(clause)) = (&mut sentence, clause) {
+                sentence.push(clause);
+            }
+        }
+
+        entries.extend(sentence.and_then(|s| s.finish(names)));
+        Ok(entries)
+    }
+
+    /// Tells what the event is about to do to the game, if it is worth telling
+    fn clause(&self, event: &Event, viewer: Option<PlayerId>) -> Option<Clause> {
+        let name = |card: CardId| self.card(card).name.clone();
+        let did = |player: PlayerId, text: String| Some(Clause::Did { player, text });
+
+        match *event {
+            Event::CardPlayed { player, card } => did(player, format!("played {}", name(card))),
+            Event::ResourcesGained { player, amount } => {
+                did(player, format!("gained {}", count(amount, "resource")))
+            }
+            Event::CardBought {
+                player,
+                card,
+                source,
+            } => {
+                let card = match source {
+                    Purchase::Pile(def) => self.card_set.get(def).name.clone(),
+                    Purchase::Market(_) => name(card),
+                };
+                did(player, format!("bought {card}"))
+            }
+            Event::MarketRefilled { card } => Some(Clause::Happened(format!(
+                "{} was revealed in the market",
+                name(card)
+            ))),
+            Event::DeckReshuffled { player } => did(
+                player,
+                "shuffled their discard pile into a new deck".to_owned(),
+            ),
+            Event::CardDrawn { player, card } => {
+                let visible = viewer.is_some_and(|v| self.shares(v, player, Shared::Hand));
+                Some(Clause::Drew {
+                    player,
+                    cards: vec![visible.then(|| name(card))],
+                })
+            }
+            Event::CardGained {
+                player, pile, to, ..
+            } => {
+                let place = match to {
+                    Zone::Hand => " into their hand",
+                    Zone::Deck => " onto their deck",
+                    _ => "",
+                };
+                let card = &self.card_set.get(pile).name;
+                did(player, format!("gained {card}{place}"))
+            }
+            Event::CardTrashed { player, card, .. } => {
+                did(player, format!("trashed {}", name(card)))
+            }
+            Event::CardDiscarded { player, card, .. } => {
+                did(player, format!("discarded {}", name(card)))
+            }
+            Event::RandomCardPicked { .. } => None,
+            Event::TurnEnded { player } => did(player, "ended their turn".to_owned()),
+            Event::TurnStarted { player, turn } => did(player, format!("started turn {turn}")),
+            Event::PhaseStarted { player, phase } => match phase {
+                Phase::Buy => did(player, "moved on to buying".to_owned()),
+                _ => None,
+            },
+            Event::Conceded { player } => did(player, "conceded".to_owned()),
+            Event::DecisionRequested { ref decision } => {
+                let source = name(decision.source());
+                let text = match decision.request() {
+                    Request::Reaction { .. } => format!("may react to {source}"),
+                    Request::Order(_) => "has to order their triggers".to_owned(),
+                    _ => format!("has to decide on {source}"),
+                };
+                did(decision.player(), text)
+            }
+            Event::DecisionResolved { player, ref choice } => {
+                let request = self.pending.as_ref().map(|d| d.request());
+                let reaction = matches!(request, Some(Request::Reaction { .. }));
+                match choice {
+                    Choice::Option(idx) if let Some(Request::Order(triggers)) = request => did(
+                        player,
+                        format!("resolved {} first", name(triggers[*idx].card)),
+                    ),
+                    Choice::Option(option) => did(player, format!("chose option {}", option + 1)),
+                    Choice::Cards(cards) if reaction && cards.is_empty() => {
+                        did(player, "did not react".to_owned())
+                    }
+                    Choice::Cards(_) => None,
+                }
+            }
+            Event::ReactionRevealed { player, card } => {
+                did(player, format!("revealed {}", name(card)))
+            }
+            Event::AttackBlocked { player } => {
+                did(player, "is unaffected by the attack".to_owned())
+            }
+            Event::DamageDealt { player, amount } => did(player, format!("lost {amount} health")),
+            // Always follows playing the base
+            Event::BaseDeployed { .. } => None,
+            Event::BaseDamaged {
+                player,
+                card,
+                amount,
+            } => did(
+                player,
+                format!("lost {amount} durability of {}", name(card)),
+            ),
+            Event::BaseDestroyed { player, card } => did(player, format!("lost {}", name(card))),
+            Event::Triggered { player, card, .. } => {
+                did(player, format!("triggered {}", name(card)))
+            }
+            Event::GameEnded { ref result } => match (result.winner, result.team) {
+                (Some(winner), Some(_)) => did(winner, "won the game with their team".to_owned()),
+                (Some(winner), None) => did(winner, "won the game".to_owned()),
+                (None, _) => Some(Clause::Happened("The game ended in a tie".to_owned())),
+            },
+            Event::Undone { player } => did(player, "took back their last actions".to_owned()),
+        }
+    }
+}
+
+/// Checks if the event starts a new sentence. The following events, up to the next such one,
+/// are its consequences.
+fn opens_sentence(event: &Event) -> bool {
+    matches!(
+        event,
+        Event::CardPlayed { .. }
+            | Event::CardBought { .. }
+            | Event::DecisionResolved { .. }
+            | Event::PhaseStarted { .. }
+            | Event::TurnEnded { .. }
+            | Event::TurnStarted { .. }
+            | Event::Conceded { .. }
+            | Event::GameEnded { .. }
+            | Event::Undone { .. }
+    )
+}
+
+impl Sentence {
+    /// Adds the clause, merging the cards drawn one after another into a single clause
+    fn push(&mut self, clause: Clause) {
+        if let (
+            Some(Clause::Drew {
+                player: last,
+                cards,
+            }),
+            Clause::Drew { player, cards: new },
+        ) = (self.clauses.last_mut(), &clause)
+            && *last == *player
+        {
+            cards.extend(new.iter().cloned());
+            return;
+        }
+
+        self.clauses.push(clause);
+    }
+
+    /// Turns the clauses into the log entry. Player's name is said only when the subject changes.
+    /// `None` if there is nothing to tell.
+    fn finish(self, names: &[String]) -> Option<LogEntry> {
+        let name = |player: PlayerId| {
+            names
+                .get(player.seat())
+                .cloned()
+                .unwrap_or_else(|| player.to_string())
+        };
+
+        let mut subject = None;
+        let parts: Vec<String> = self
+            .clauses
+            .into_iter()
+            .map(|clause| {
+                let (player, text) = match clause {
+                    Clause::Did { player, text } => (Some(player), text),
+                    Clause::Drew { player, cards } => (Some(player), drew(&cards)),
+                    Clause::Happened(text) => (None, text),
+                };
+
+                let said = std::mem::replace(&mut subject, player);
+                match player {
+                    Some(player) if said != Some(player) => format!("{} {text}", name(player)),
+                    _ => text,
+                }
+            })
+            .collect();
+
+        let text = match parts.as_slice() {
+            [] => return None,
+            [only] => only.clone(),
+            [init @ .., last] => format!("{} and {last}", init.join(", ")),
+        };
+
+        Some(LogEntry {
+            event: self.event,
+            turn: self.turn,
+            text,
+        })
+    }
+}
+
+/// Tells about the drawn cards, naming them if all of them are visible
+fn drew(cards: &[Option<String>]) -> String {
+    let names: Option<Vec<&str>> = cards.iter().map(Option::as_deref).collect();
+    match names {
+        Some(names) if names.len() == 1 => format!("drew {}", names[0]),
+        Some(names) => format!(
+            "drew {} ({})",
+            count(cards.len() as u32, "card"),
+            names.join(", ")
+        ),
+        None => format!("drew {}", count(cards.len() as u32, "card")),
+    }
+}
+
+/// Counted noun, eg. "a card" or "3 cards"
+fn count(count: u32, noun: &str) -> String {
+    match count {
+        1 => format!("a {noun}"),
+        _ => format!("{count} {noun}s"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::action::Action;
+    use crate::card::{CardDef, CardSet};
+    use crate::config::{FirstPlayer, GameConfig};
+    use crate::effect::Effect;
+    use crate::team::Teams;
+    use crate::test_util::p;
+
+    fn setup() -> Setup {
+        let cards = CardSet::new([
+            CardDef::new("Copper", 0).resources(1).starter(7).supply(30),
+            CardDef::new("Estate", 0).starter(3).supply(8),
+            CardDef::new("Smith", 0).effect(Effect::Draw(2)).starter(1),
+        ]);
+        let config = GameConfig {
+            first_player: FirstPlayer::Seat(0),
+            ..GameConfig::default()
+        };
+        Setup::new(cards, 2, 4).config(config)
+    }
+
+    /// Plays the first turn, playing Smith if it was drawn. Returns the event log.
+    fn play() -> Vec<Event> {
+        let mut game = Game::new(setup()).unwrap();
+        let player = game.active();
+        let mut log = vec![];
+
+        let smith = game.card_set().find("Smith").unwrap();
+        let hand = game.player(player).hand().to_vec();
+        if let Some(smith) = hand.iter().find(|card| game.cards().def(**card) == smith) {
+            log.extend(game.apply(player, Action::PlayCard(*smith)).unwrap());
+        }
+        log.extend(game.apply(player, Action::EndPhase).unwrap());
+        log.extend(game.apply(player, Action::EndTurn).unwrap());
+        log
+    }
+
+    fn texts(entries: &[LogEntry]) -> Vec<&str> {
+        entries.iter().map(|entry| entry.text.as_str()).collect()
+    }
+
+    #[test]
+    fn narrating_the_game() {
+        let names = ["Alice".to_owned(), "Bob".to_owned()];
+        let log = play();
+        assert!(matches!(log[0], Event::CardPlayed { .. }));
+
+        let entries = Game::narrate(setup(), &log, Some(p(1)), &names).unwrap();
+        assert_eq!(
+            texts(&entries),
+            [
+                "Alice played Smith and drew 2 cards",
+                "Alice moved on to buying",
+                "Alice ended their turn, drew 4 cards, shuffled their discard pile into a new deck \
+                 and drew a card",
+                "Bob started turn 2",
+            ]
+        );
+        assert_eq!(entries[0].event, 0);
+        assert_eq!(entries[3].turn, 2);
+
+        let entries = Game::narrate(setup(), &log, None, &names).unwrap();
+        assert_eq!(entries[0].text, "Alice played Smith and drew 2 cards");
+
+        let entries = Game::narrate(setup(), &log, Some(p(0)), &[]).unwrap();
+        let text = &entries[0].text;
+        assert!(
+            text.starts_with("P1 played Smith and drew 2 cards ("),
+            "{text}"
+        );
+    }
+
+    #[test]
+    fn drawn_cards_are_named_for_those_seeing_them() {
+        let cards = CardSet::new([
+            CardDef::new("Copper", 0).resources(1).starter(7).supply(30),
+            CardDef::new("Estate", 0).starter(3).supply(8),
+        ]);
+        // Seats 0 and 2 against 1 and 3, teammates seeing each other's hands
+        let config = GameConfig {
+            first_player: FirstPlayer::Seat(0),
+            teams: Some(Teams {
+                seats: vec![0, 1, 0, 1],
+                shared: vec![Shared::Hand],
+            }),
+            ..GameConfig::default()
+        };
+        let setup = Setup::new(cards, 4, 4).config(config);
+        let mut game = Game::new(setup.clone()).unwrap();
+        let log = game.apply(p(0), Action::EndTurn).unwrap();
+
+        let drawn: Vec<_> = game
+            .player(p(0))
+            .hand()
+            .iter()
+            .map(|card| game.card(*card).name.clone())
+            .collect();
+        let named = format!(
+            "P1 ended their turn and drew 5 cards ({})",
+            drawn.join(", ")
+        );
+        let counted = "P1 ended their turn and drew 5 cards";
+
+        let narrated = |viewer| Game::narrate(setup.clone(), &log, viewer, &[]).unwrap();
+        assert_eq!(texts(&narrated(Some(p(0))))[1], named);
+        assert_eq!(texts(&narrated(Some(p(2))))[1], named);
+        assert_eq!(texts(&narrated(Some(p(1))))[1], counted);
+        assert_eq!(texts(&narrated(Some(p(3))))[1], counted);
+        assert_eq!(texts(&narrated(None))[1], counted);
+    }
+
+    #[test]
+    fn sentences_group_the_consequences_of_actions() {
+        let mut game = Game::new(setup()).unwrap();
+        let player = game.active();
+        let copper = game.card_set().find("Copper").unwrap();
+        let coppers: Vec<_> = game
+            .player(player)
+            .hand()
+            .iter()
+            .copied()
+            .filter(|card| game.cards().def(*card) == copper)
+            .collect();
+        assert!(!coppers.is_empty());
+
+        let mut log = vec![];
+        let mut played = vec![];
+        for card in &coppers {
+            played.push(log.len());
+            log.extend(game.apply(player, Action::PlayCard(*card)).unwrap());
+        }
+        log.extend(game.apply(player, Action::EndPhase).unwrap());
+        let estate = game.card_set().find("Estate").unwrap();
+        log.extend(
+            game.apply(player, Action::BuyCard(Purchase::Pile(estate)))
+                .unwrap(),
+        );
+
+        let entries = Game::narrate(setup(), &log, None, &[]).unwrap();
+        let mut expected = vec!["P1 played Copper and gained a resource"; coppers.len()];
+        expected.extend(["P1 moved on to buying", "P1 bought Estate"]);
+        assert_eq!(texts(&entries), expected);
+        let events: Vec<_> = entries.iter().map(|entry| entry.event).collect();
+        assert_eq!(events[..coppers.len()], played);
+        assert!(entries.iter().all(|entry| entry.turn == 1));
+    }
+}
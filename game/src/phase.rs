@@ -0,0 +1,93 @@
+//! Turn phases
+
+use serde::{Deserialize, Serialize};
+
+use crate::action::Action;
+
+/// Phase of the active player's turn.
+///
+/// Phases always follow the same order: `Start`, `Action`, `Buy` and `Cleanup`, after which the
+/// next player's turn begins with its `Start` phase.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Phase {
+    /// Start-of-turn triggers are resolved. Players cannot act in this phase.
+    Start,
+    /// Cards are played from the hand
+    Action,
+    /// Cards are bought from the supply
+    Buy,
+    /// End-of-turn triggers are resolved, then played cards and the hand are discarded and a new
+    /// hand is drawn. Players cannot act in this phase.
+    Cleanup,
+}
+
+impl Phase {
+    /// Phase following this one. `Cleanup` is followed by the `Start` of the next turn.
+    pub fn next(self) -> Self {
+        match self {
+            Phase::Start => Phase::Action,
+            Phase::Action => Phase::Buy,
+            Phase::Buy => Phase::Cleanup,
+            Phase::Cleanup => Phase::Start,
+        }
+    }
+
+    /// Checks if the active player may take the action in this phase
+    pub fn permits(self, action: &Action) -> bool {
+        match action {
+            Action::PlayCard(_) => self == Phase::Action,
+            Action::BuyCard(_) => self == Phase::Buy,
+            Action::EndPhase | Action::EndTurn => matches!(self, Phase::Action | Phase::Buy),
+            Action::ResolveChoice(_) | Action::Undo | Action::Concede => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::action::Purchase;
+    use crate::card::Cards;
+
+    #[test]
+    fn phases_cycle() {
+        let mut phase = Phase::Start;
+        let mut order = vec![];
+        for _ in 0..5 {
+            order.push(phase);
+            phase = phase.next();
+        }
+
+        assert_eq!(
+            order,
+            [
+                Phase::Start,
+                Phase::Action,
+                Phase::Buy,
+                Phase::Cleanup,
+                Phase::Start
+            ]
+        );
+    }
+
+    #[test]
+    fn phase_specific_actions() {
+        let card = Cards::default().next_id();
+        let play = Action::PlayCard(card);
+        let buy = Action::BuyCard(Purchase::Market(card));
+
+        assert!(Phase::Action.permits(&play));
+        assert!(!Phase::Buy.permits(&play));
+        assert!(Phase::Buy.permits(&buy));
+        assert!(!Phase::Action.permits(&buy));
+
+        for action in [play, buy, Action::EndPhase, Action::EndTurn] {
+            assert!(!Phase::Start.permits(&action));
+            assert!(!Phase::Cleanup.permits(&action));
+        }
+
+        for phase in [Phase::Start, Phase::Action, Phase::Buy, Phase::Cleanup] {
+            assert!(phase.permits(&Action::Concede));
+        }
+    }
+}
@@ -0,0 +1,175 @@
+//! Per-player state and zones
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::card::CardId;
+
+/// Seat of a player in the game
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct PlayerId(usize);
+
+impl PlayerId {
+    /// Creates a player id for the given seat
+    pub fn new(seat: usize) -> Self {
+        Self(seat)
+    }
+
+    /// Returns the seat index
+    pub fn seat(self) -> usize {
+        self.0
+    }
+}
+
+impl std::fmt::Display for PlayerId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "P{}", self.0 + 1)
+    }
+}
+
+/// Zone owned by a player
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Zone {
+    /// Draw pile. The top card is the last one.
+    Deck,
+    /// Cards in hand
+    Hand,
+    /// Discard pile
+    Discard,
+    /// Cards played this turn
+    InPlay,
+    /// Cards removed from the game by this player
+    Trash,
+    /// Bases staying in play across turns
+    Bases,
+}
+
+impl Zone {
+    /// All the zones
+    pub const ALL: [Zone; 6] = [
+        Zone::Deck,
+        Zone::Hand,
+        Zone::Discard,
+        Zone::InPlay,
+        Zone::Trash,
+        Zone::Bases,
+    ];
+}
+
+/// State of a single player
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Player {
+    pub(crate) deck: Vec<CardId>,
+    pub(crate) hand: Vec<CardId>,
+    pub(crate) discard: Vec<CardId>,
+    pub(crate) in_play: Vec<CardId>,
+    pub(crate) trash: Vec<CardId>,
+    pub(crate) bases: Vec<CardId>,
+    /// Damage taken by the bases in play
+    pub(crate) damage: BTreeMap<CardId, u32>,
+    /// Resources left to spend this turn
+    pub(crate) resources: u32,
+    /// Player left the game
+    pub(crate) conceded: bool,
+    /// Health left, the player is knocked out at zero
+    pub(crate) health: u32,
+    /// Number of turns the player started
+    pub(crate) turns: u32,
+}
+
+impl Player {
+    /// Returns cards in the zone
+    pub fn zone(&self, zone: Zone) -> &[CardId] {
+        match zone {
+            Zone::Deck => &self.deck,
+            Zone::Hand => &self.hand,
+            Zone::Discard => &self.discard,
+            Zone::InPlay => &self.in_play,
+            Zone::Trash => &self.trash,
+            Zone::Bases => &self.bases,
+        }
+    }
+
+    pub(crate) fn zone_mut(&mut self, zone: Zone) -> &mut Vec<CardId> {
+        match zone {
+            Zone::Deck => &mut self.deck,
+            Zone::Hand => &mut self.hand,
+            Zone::Discard => &mut self.discard,
+            Zone::InPlay => &mut self.in_play,
+            Zone::Trash => &mut self.trash,
+            Zone::Bases => &mut self.bases,
+        }
+    }
+
+    /// Draw pile, top card last
+    pub fn deck(&self) -> &[CardId] {
+        &self.deck
+    }
+
+    /// Cards in hand
+    pub fn hand(&self) -> &[CardId] {
+        &self.hand
+    }
+
+    /// Discard pile
+    pub fn discard(&self) -> &[CardId] {
+        &self.discard
+    }
+
+    /// Cards played this turn
+    pub fn in_play(&self) -> &[CardId] {
+        &self.in_play
+    }
+
+    /// Trashed cards
+    pub fn trash(&self) -> &[CardId] {
+        &self.trash
+    }
+
+    /// Bases in play, in the order they were deployed
+    pub fn bases(&self) -> &[CardId] {
+        &self.bases
+    }
+
+    /// Resources left to spend this turn
+    pub fn resources(&self) -> u32 {
+        self.resources
+    }
+
+    /// Checks if the player conceded
+    pub fn conceded(&self) -> bool {
+        self.conceded
+    }
+
+    /// Health left
+    pub fn health(&self) -> u32 {
+        self.health
+    }
+
+    /// Number of turns the player started
+    pub fn turns(&self) -> u32 {
+        self.turns
+    }
+
+    /// Finds a zone containing the card
+    pub fn locate(&self, card: CardId) -> Option<Zone> {
+        Zone::ALL
+            .into_iter()
+            .find(|zone| self.zone(*zone).contains(&card))
+    }
+
+    /// Removes the card from the zone. Returns `false` if the card was not there.
+    pub(crate) fn take(&mut self, zone: Zone, card: CardId) -> bool {
+        let cards = self.zone_mut(zone);
+        match cards.iter().position(|c| *c == card) {
+            Some(idx) => {
+                cards.remove(idx);
+                true
+            }
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,103 @@
+//! Deterministic random number generator
+//!
+//! The generator is implemented in the crate (SplitMix64), so the sequence produced for a seed
+//! never changes with dependency upgrades and games can always be recreated from their seed.
+
+use serde::{Deserialize, Serialize};
+
+/// Seeded random number generator owned by the game state
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    /// Creates the generator from a seed
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// Returns next random number
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns uniformly distributed number in `0..bound`. `bound` must be positive.
+    pub fn below(&mut self, bound: usize) -> usize {
+        assert!(bound > 0, "Empty range");
+
+        let bound = bound as u64;
+        // Rejecting the biased tail of the `u64` range keeps the distribution uniform
+        let zone = u64::MAX - (u64::MAX - bound + 1) % bound;
+        loop {
+            let value = self.next_u64();
+            if value <= zone {
+                return (value % bound) as usize;
+            }
+        }
+    }
+
+    /// Shuffles the slice in place (Fisher-Yates)
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for idx in (1..items.len()).rev() {
+            let other = self.below(idx + 1);
+            items.swap(idx, other);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_same_sequence() {
+        let mut rng1 = Rng::new(42);
+        let mut rng2 = Rng::new(42);
+        let mut rng3 = Rng::new(43);
+
+        let seq1: Vec<_> = (0..16).map(|_| rng1.next_u64()).collect();
+        let seq2: Vec<_> = (0..16).map(|_| rng2.next_u64()).collect();
+        let seq3: Vec<_> = (0..16).map(|_| rng3.next_u64()).collect();
+
+        assert_eq!(seq1, seq2);
+        assert_ne!(seq1, seq3);
+    }
+
+    #[test]
+    fn known_sequence() {
+        // Reference values of SplitMix64 - guards the sequence against accidental changes
+        let mut rng = Rng::new(1234567);
+        assert_eq!(rng.next_u64(), 6457827717110365317);
+        assert_eq!(rng.next_u64(), 3203168211198807973);
+    }
+
+    #[test]
+    fn below_stays_in_range() {
+        let mut rng = Rng::new(7);
+        let mut seen = [false; 6];
+
+        for _ in 0..1000 {
+            let value = rng.below(6);
+            seen[value] = true;
+        }
+
+        assert!(seen.iter().all(|seen| *seen));
+        assert_eq!(Rng::new(7).below(1), 0);
+    }
+
+    #[test]
+    fn shuffle_permutes() {
+        let mut rng = Rng::new(3);
+        let mut items: Vec<u32> = (0..20).collect();
+        rng.shuffle(&mut items);
+
+        assert_ne!(items, (0..20).collect::<Vec<_>>());
+        items.sort();
+        assert_eq!(items, (0..20).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,291 @@
+//! Versioned snapshots of the game state
+//!
+//! A snapshot is a JSON document `{"version": N, "game": {...}}` holding the whole serialized
+//! `Game`. Whenever the serialized layout changes, an upgrade converting the previous layout is
+//! appended to `UPGRADES`, which bumps `VERSION`. Snapshots of any older version are brought up to
+//! date by running them through all the later upgrades before they are deserialized.
+//!
+//! The state checksum hashes the same serialized form, less the card set which never changes
+//! during the game, so checksums are stable as long as `VERSION` stays the same.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::commitment::sha3_hex;
+use crate::event::{Event, ReplayError};
+use crate::{
+    CardId, Cards, Decision, Game, GameConfig, GameResult, Phase, Player, PlayerId, Registration,
+    Rng, Supply,
+};
+
+/// Conversion of a serialized game from one snapshot version to the next one
+type Upgrade = fn(&mut Value) -> Result<(), SnapshotError>;
+
+/// Upgrades of the game layout. The first one will upgrade version `1` snapshots.
+const UPGRADES: &[Upgrade] = &[];
+
+/// Version of the snapshots created by this build
+pub const VERSION: u32 = UPGRADES.len() as u32 + 1;
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum SnapshotError {
+    #[error("Malformed snapshot: {0}")]
+    Malformed(String),
+    #[error("Snapshot version {version} is not supported, the newest known version is {supported}")]
+    UnsupportedVersion { version: u32, supported: u32 },
+}
+
+/// Serialized form of the snapshot
+#[derive(Serialize, Deserialize)]
+struct Envelope<G> {
+    version: u32,
+    game: G,
+}
+
+/// Part of the game state covered by the checksum, in the order of the `Game` fields
+#[derive(Serialize)]
+struct Canonical<'a> {
+    cards: &'a Cards,
+    players: &'a [Player],
+    supply: &'a Supply,
+    active: PlayerId,
+    turn: u32,
+    phase: Phase,
+    rng: &'a Rng,
+    pending: &'a Option<Decision>,
+    unaffected: &'a [PlayerId],
+    triggers: &'a [Registration],
+    allied: &'a [CardId],
+    config: &'a GameConfig,
+    result: &'a Option<GameResult>,
+}
+
+impl Game {
+    /// Serializes the whole game state into the current snapshot format
+    pub fn snapshot(&self) -> String {
+        let envelope = Envelope {
+            version: VERSION,
+            game: self,
+        };
+        serde_json::to_string(&envelope).expect("Game is always serializable")
+    }
+
+    /// Restores the game from the snapshot of the current or any older version
+    pub fn restore(snapshot: &str) -> Result<Self, SnapshotError> {
+        let malformed = |err: serde_json::Error| SnapshotError::Malformed(err.to_string());
+
+        let Envelope { version, mut game } =
+            serde_json::from_str::<Envelope<Value>>(snapshot).map_err(malformed)?;
+        upgrade(version, &mut game, UPGRADES)?;
+        serde_json::from_value(game).map_err(malformed)
+    }
+
+    /// SHA3-256 hash of the game state, hex encoded. Equal states, however they were reached,
+    /// have equal checksums. The card set is left out, as it is fixed by the setup.
+    pub fn checksum(&self) -> String {
+        let Game {
+            card_set: _,
+            cards,
+            players,
+            supply,
+            active,
+            turn,
+            phase,
+            rng,
+            pending,
+            unaffected,
+            triggers,
+            fired: _,
+            allied,
+            config,
+            result,
+            checkpoint: _,
+        } = self;
+        let canonical = Canonical {
+            cards,
+            players,
+            supply,
+            active: *active,
+            turn: *turn,
+            phase: *phase,
+            rng,
+            pending,
+            unaffected,
+            triggers,
+            allied,
+            config,
+            result,
+        };
+        let state = serde_json::to_string(&canonical).expect("Game is always serializable");
+        sha3_hex(state.as_bytes())
+    }
+
+    /// Folds the events into the state like `advance`, returning the checksum of the state after
+    /// each of them. Event indices in errors are relative to `events`.
+    ///
+    /// On error the state might be partially modified and should be dropped.
+    pub fn checksums<'a>(
+        &mut self,
+        events: impl IntoIterator<Item = &'a Event>,
+    ) -> Result<Vec<String>, ReplayError> {
+        events
+            .into_iter()
+            .enumerate()
+            .map(|(index, event)| {
+                self.fold(event).map_err(|_| ReplayError::InvalidEvent {
+                    index,
+                    event: event.clone(),
+                })?;
+                Ok(self.checksum())
+            })
+            .collect()
+    }
+}
+
+/// Brings the serialized game of the given version up to date with the upgrades
+fn upgrade(version: u32, game: &mut Value, upgrades: &[Upgrade]) -> Result<(), SnapshotError> {
+    let supported = upgrades.len() as u32 + 1;
+    if version == 0 || version > supported {
+        return Err(SnapshotError::UnsupportedVersion { version, supported });
+    }
+
+    for upgrade in &upgrades[version as usize - 1..] {
+        upgrade(game)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::Setup;
+    use crate::action::Action;
+    use crate::card::CardSet;
+    use crate::test_util::give;
+
+    #[test]
+    fn restoring_snapshots() {
+        let mut game = Game::new(Setup::new(CardSet::basic(), 2, 2)).unwrap();
+        let player = game.active();
+        let card = give(&mut game, player, "Recycler");
+        game.apply(player, Action::PlayCard(card)).unwrap();
+        assert!(game.pending().is_some());
+
+        // The undo checkpoint is left out
+        let snapshot = game.snapshot();
+        assert!(game.can_undo());
+        game.checkpoint = None;
+        assert_eq!(Game::restore(&snapshot), Ok(game.clone()));
+
+        let value: Value = serde_json::from_str(&snapshot).unwrap();
+        assert_eq!(value["version"], VERSION);
+    }
+
+    #[test]
+    fn checksums_follow_the_state() {
+        let setup = Setup::new(CardSet::basic(), 2, 2);
+        let mut game = Game::new(setup.clone()).unwrap();
+        let initial = game.checksum();
+        assert_eq!(initial.len(), 64);
+
+        let previous = game.clone();
+        let events = game.apply(game.active(), Action::EndTurn).unwrap();
+        assert_ne!(game.checksum(), initial);
+
+        let mut folded = previous.clone();
+        let checksums = folded.checksums(&events).unwrap();
+        assert_eq!(folded.checksum(), game.checksum());
+        assert_eq!(checksums.len(), events.len());
+        assert_eq!(checksums.last(), Some(&game.checksum()));
+        assert_ne!(checksums[0], checksums[1]);
+
+        let replayed = Game::replay(setup, &events).unwrap();
+        assert_eq!(replayed.checksum(), game.checksum());
+        let restored = Game::restore(&game.snapshot()).unwrap();
+        assert_eq!(restored.checksum(), game.checksum());
+
+        // Neither the card set nor the undo checkpoint are hashed
+        let mut altered = game.clone();
+        altered.card_set = CardSet::new([]);
+        altered.checkpoint = Some(Box::new(restored));
+        assert_eq!(altered.checksum(), game.checksum());
+    }
+
+    #[test]
+    fn future_versions_are_rejected() {
+        let game = Game::new(Setup::new(CardSet::basic(), 2, 2)).unwrap();
+        let mut value: Value = serde_json::from_str(&game.snapshot()).unwrap();
+        value["version"] = json!(VERSION + 1);
+
+        assert_eq!(
+            Game::restore(&value.to_string()),
+            Err(SnapshotError::UnsupportedVersion {
+                version: VERSION + 1,
+                supported: VERSION
+            })
+        );
+
+        value["version"] = json!(0);
+        assert!(matches!(
+            Game::restore(&value.to_string()),
+            Err(SnapshotError::UnsupportedVersion { version: 0, .. })
+        ));
+
+        assert!(matches!(
+            Game::restore("{\"version\": 1}"),
+            Err(SnapshotError::Malformed(_))
+        ));
+    }
+
+    #[test]
+    fn upgrading_old_versions() {
+        // Version 2 renamed `turn` to `round`, version 3 stored the seat of the active player
+        // instead of its id
+        let upgrades: &[Upgrade] = &[
+            |game| {
+                let game = game.as_object_mut().unwrap();
+                let turn = game.remove("turn").unwrap_or_default();
+                game.insert("round".to_owned(), turn);
+                Ok(())
+            },
+            |game| match game["active"].as_u64() {
+                Some(seat) => {
+                    game["active"] = json!({ "seat": seat });
+                    Ok(())
+                }
+                None => Err(SnapshotError::Malformed(
+                    "active is not a number".to_owned(),
+                )),
+            },
+        ];
+
+        let mut game = json!({ "turn": 4, "active": 1 });
+        upgrade(1, &mut game, upgrades).unwrap();
+        assert_eq!(game, json!({ "round": 4, "active": { "seat": 1 } }));
+
+        let mut game = json!({ "turn": 4, "active": 1 });
+        upgrade(2, &mut game, upgrades).unwrap();
+        assert_eq!(game, json!({ "turn": 4, "active": { "seat": 1 } }));
+
+        let mut game = json!({ "active": { "seat": 1 } });
+        upgrade(3, &mut game, upgrades).unwrap();
+        assert_eq!(game, json!({ "active": { "seat": 1 } }));
+
+        let mut game = json!({ "active": "first" });
+        assert!(matches!(
+            upgrade(2, &mut game, upgrades),
+            Err(SnapshotError::Malformed(_))
+        ));
+        assert_eq!(
+            upgrade(4, &mut game, upgrades),
+            Err(SnapshotError::UnsupportedVersion {
+                version: 4,
+                supported: 3
+            })
+        );
+    }
+}
@@ -0,0 +1,96 @@
+//! Shared supply piles and the market row
+
+use serde::{Deserialize, Serialize};
+
+use crate::card::{CardDefId, CardId};
+use crate::rng::Rng;
+
+/// Supply pile of identical cards
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Pile {
+    /// Card kept in the pile
+    pub card: CardDefId,
+    /// Cards left in the pile
+    pub count: u32,
+}
+
+/// Cards available for buying to all the players
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Supply {
+    /// Fixed supply piles
+    piles: Vec<Pile>,
+    /// Face-up market row
+    market: Vec<CardId>,
+    /// Face-down market deck refilling the market row. The top card is the last one.
+    market_deck: Vec<CardId>,
+}
+
+impl Supply {
+    /// Creates the supply
+    pub(crate) fn new(piles: Vec<Pile>, market_deck: Vec<CardId>) -> Self {
+        Self {
+            piles,
+            market: vec![],
+            market_deck,
+        }
+    }
+
+    /// Supply piles
+    pub fn piles(&self) -> &[Pile] {
+        &self.piles
+    }
+
+    /// Returns the pile of the given card
+    pub fn pile(&self, card: CardDefId) -> Option<&Pile> {
+        self.piles.iter().find(|pile| pile.card == card)
+    }
+
+    /// Face-up market row
+    pub fn market(&self) -> &[CardId] {
+        &self.market
+    }
+
+    /// Face-down market deck
+    pub fn market_deck(&self) -> &[CardId] {
+        &self.market_deck
+    }
+
+    /// Takes a single card from the pile. Returns `false` if the pile is empty or missing.
+    pub(crate) fn take_from_pile(&mut self, card: CardDefId) -> bool {
+        match self.piles.iter_mut().find(|pile| pile.card == card) {
+            Some(pile) if pile.count > 0 => {
+                pile.count -= 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Takes the card from the market row. Returns `false` if the card is not there.
+    pub(crate) fn take_from_market(&mut self, card: CardId) -> bool {
+        match self.market.iter().position(|c| *c == card) {
+            Some(idx) => {
+                self.market.remove(idx);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Shuffles the face-down market deck
+    pub(crate) fn shuffle_market_deck(&mut self, rng: &mut Rng) {
+        rng.shuffle(&mut self.market_deck);
+    }
+
+    /// Moves the top card of the market deck to the market row. Returns `false` if the market
+    /// deck is empty.
+    pub(crate) fn reveal(&mut self) -> bool {
+        match self.market_deck.pop() {
+            Some(card) => {
+                self.market.push(card);
+                true
+            }
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,227 @@
+//! Team play
+//!
+//! In team games every seat belongs to a team. Teammates are never opponents, so attacks pass
+//! them by, they share the victory, and they can see the part of each other's hidden state the
+//! config shares. Without teams every player plays for themselves.
+
+use serde::{Deserialize, Serialize};
+
+use crate::Game;
+use crate::player::PlayerId;
+
+/// Hidden information teammates see of each other
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Shared {
+    /// Cards in hand
+    Hand,
+    /// Requests of the decisions the teammate is asked for
+    Decisions,
+}
+
+/// Assignment of the seats to teams
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Teams {
+    /// Team of every seat, in the seat order. Teams are identified by any numbers, there have to
+    /// be at least two of them.
+    pub seats: Vec<usize>,
+    /// What teammates see of each other
+    pub shared: Vec<Shared>,
+}
+
+impl Teams {
+    /// Checks if the assignment covers exactly the number of players with at least two teams
+    pub(crate) fn is_valid(&self, players: usize) -> bool {
+        self.seats.len() == players && self.seats.iter().any(|team| *team != self.seats[0])
+    }
+}
+
+impl Game {
+    /// Team of the player, `None` if the game is not played in teams
+    pub fn team(&self, player: PlayerId) -> Option<usize> {
+        let teams = self.config.teams.as_ref()?;
+        teams.seats.get(player.seat()).copied()
+    }
+
+    /// Checks if the players are on the same side - it is the same player or their teammate
+    pub fn allied(&self, player: PlayerId, other: PlayerId) -> bool {
+        player == other
+            || self
+                .team(player)
+                .is_some_and(|team| self.team(other) == Some(team))
+    }
+
+    /// Non-conceded teammates of the player, in the seat order starting after them
+    pub fn teammates(&self, player: PlayerId) -> Vec<PlayerId> {
+        let others = self.others(player).into_iter();
+        others.filter(|other| self.allied(player, *other)).collect()
+    }
+
+    /// Checks if the viewer sees the hidden information of the player
+    pub(crate) fn shares(&self, viewer: PlayerId, player: PlayerId, shared: Shared) -> bool {
+        viewer == player
+            || (self.allied(viewer, player)
+                && self
+                    .config
+                    .teams
+                    .as_ref()
+                    .is_some_and(|teams| teams.shared.contains(&shared)))
+    }
+
+    /// Players grouped into sides, ordered by the first seat of every side. Without teams every
+    /// player is a side of their own.
+    pub(crate) fn sides(&self) -> Vec<Vec<PlayerId>> {
+        let mut sides: Vec<Vec<PlayerId>> = vec![];
+        for player in self.player_ids() {
+            match sides.iter_mut().find(|side| self.allied(side[0], player)) {
+                Some(side) => side.push(player),
+                None => sides.push(vec![player]),
+            }
+        }
+        sides
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::action::Action;
+    use crate::card::{CardDef, CardSet, CardType};
+    use crate::config::{FirstPlayer, GameConfig};
+    use crate::effect::{Effect, Target};
+    use crate::end::{EndCondition, EndReason, EndRules};
+    use crate::event::Event;
+    use crate::test_util::{give, p, put};
+    use crate::{Error, Setup, Zone};
+
+    fn card_set() -> CardSet {
+        CardSet::new([
+            CardDef::new("Copper", 0).resources(1).starter(7).supply(30),
+            CardDef::new("Estate", 0).points(1).starter(3).supply(8),
+            CardDef::new("Cannon", 0)
+                .types([CardType::Action, CardType::Attack])
+                .effect(Effect::Damage(3))
+                .supply(10),
+            CardDef::new("Banner", 0)
+                .effect(Effect::Each {
+                    target: Target::Teammates,
+                    effects: vec![Effect::Draw(1)],
+                })
+                .supply(10),
+        ])
+    }
+
+    /// 2v2 game, seats 0 and 2 against 1 and 3, starting with seat 0
+    fn game(shared: Vec<Shared>, end: EndRules) -> Game {
+        let config = GameConfig {
+            teams: Some(Teams {
+                seats: vec![0, 1, 0, 1],
+                shared,
+            }),
+            first_player: FirstPlayer::Seat(0),
+            end,
+            ..GameConfig::default()
+        };
+        Game::new(Setup::new(card_set(), 4, 3).config(config)).unwrap()
+    }
+
+    #[test]
+    fn teammates_are_not_opponents() {
+        let game = game(vec![], EndRules::default());
+
+        assert_eq!(game.team(p(2)), Some(0));
+        assert_eq!(game.teammates(p(0)), [p(2)]);
+        assert_eq!(game.opponents(p(0)), [p(1), p(3)]);
+        assert_eq!(game.others(p(3)), [p(0), p(1), p(2)]);
+
+        let solo = Game::new(Setup::new(card_set(), 3, 3)).unwrap();
+        assert_eq!(solo.team(p(0)), None);
+        assert_eq!(solo.teammates(p(0)), []);
+        assert_eq!(solo.opponents(p(0)), [p(1), p(2)]);
+    }
+
+    #[test]
+    fn invalid_teams() {
+        let setup = |seats| {
+            let config = GameConfig {
+                teams: Some(Teams {
+                    seats,
+                    shared: vec![],
+                }),
+                ..GameConfig::default()
+            };
+            Game::new(Setup::new(card_set(), 4, 3).config(config))
+        };
+
+        assert_eq!(setup(vec![0, 1, 0]), Err(Error::InvalidTeams));
+        assert_eq!(setup(vec![1, 1, 1, 1]), Err(Error::InvalidTeams));
+        assert!(setup(vec![0, 1, 2, 2]).is_ok());
+    }
+
+    #[test]
+    fn targeting_sides() {
+        let mut game = game(vec![], EndRules::default());
+        let cannon = give(&mut game, p(0), "Cannon");
+        let events = game.apply(p(0), Action::PlayCard(cannon)).unwrap();
+
+        let damaged: Vec<_> = events
+            .iter()
+            .filter_map(|event| match event {
+                Event::DamageDealt { player, .. } => Some(*player),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(damaged, [p(1), p(3)]);
+
+        let banner = give(&mut game, p(0), "Banner");
+        let hand = game.player(p(2)).hand().len();
+        game.apply(p(0), Action::PlayCard(banner)).unwrap();
+        assert_eq!(game.player(p(2)).hand().len(), hand + 1);
+        assert_eq!(game.player(p(1)).hand().len(), hand);
+    }
+
+    #[test]
+    fn sharing_hidden_information() {
+        let game = game(vec![Shared::Hand], EndRules::default());
+        let view = game.view(p(0));
+        assert!(view.players[2].hand.is_some());
+        assert_eq!(view.players[2].team, Some(0));
+        assert!(view.players[1].hand.is_none());
+        assert!(view.players[3].hand.is_none());
+
+        let game = self::game(vec![], EndRules::default());
+        assert!(game.view(p(0)).players[2].hand.is_none());
+    }
+
+    #[test]
+    fn sharing_the_victory() {
+        let mut game = game(
+            vec![],
+            EndRules {
+                conditions: vec![EndCondition::Score(10)],
+                ..EndRules::default()
+            },
+        );
+        // Both teams start with 6 points, seat 1 gains 4 more
+        for _ in 0..4 {
+            put(&mut game, p(1), Zone::Discard, "Estate");
+        }
+
+        game.apply(p(0), Action::EndTurn).unwrap();
+        let result = game.result().unwrap();
+        assert_eq!(result.reason, EndReason::Score);
+        assert_eq!(result.team, Some(1));
+        assert_eq!(result.winner, Some(p(1)));
+        assert_eq!(game.winners(), [p(1), p(3)]);
+
+        // Team stays in the game while any of its players does
+        let mut game = self::game(vec![], EndRules::default());
+        game.apply(p(1), Action::Concede).unwrap();
+        assert!(!game.is_over());
+        game.apply(p(3), Action::Concede).unwrap();
+        let result = game.result().unwrap();
+        assert_eq!(result.reason, EndReason::Concession);
+        assert_eq!(result.team, Some(0));
+    }
+}
@@ -0,0 +1,443 @@
+//! Triggered abilities
+//!
+//! Cards may have abilities resolved whenever their moment comes instead of when they are played:
+//! at the start or the end of their owner's turn, whenever the owner gains a card, when the card
+//! itself is trashed, whenever an opponent plays an attack, or when the card finds an ally - another
+//! card sharing a faction with it in play alongside it. Triggers of a card are registered
+//! while the card is in play - for a single turn, or for as long as a base stays deployed.
+//! Registrations are part of the game state, changed only by folding events, so snapshots and
+//! replays keep them. Only `trashed` triggers don't need the card in play.
+//!
+//! Every emitted event is checked for the triggers it fires. Triggers fired by a single step of
+//! resolution are simultaneous, and they resolve right after that step, before the following
+//! ones:
+//!
+//! - the active player's triggers go first, then the other players' in the seat order starting
+//!   after them,
+//! - each player's triggers resolve in the order they fired, and the triggers fired by a single
+//!   event in the order they were registered,
+//! - when the active player has several different triggers to resolve, they choose which one
+//!   resolves first, and again after it resolved, until a single one is left.
+//!
+//! Triggers fired while a trigger resolves are resolved before the triggers left.
+
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+use crate::Game;
+use crate::card::{CardId, CardType};
+use crate::effect::{Effect, Step, StepKind};
+use crate::event::Event;
+use crate::phase::Phase;
+use crate::player::PlayerId;
+
+/// Moment a triggered ability is resolved at
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum When {
+    /// Start of each of the owner's turns
+    TurnStart,
+    /// End of each of the owner's turns, right before the cleanup
+    TurnEnd,
+    /// Whenever the owner gains a card, buying it included
+    Gain,
+    /// When the card itself is trashed, from any zone
+    Trashed,
+    /// Whenever an opponent of the owner plays an attack, before it resolves
+    OpponentAttack,
+    /// Once per turn, as soon as another card sharing a faction with this one is in play
+    /// alongside it, either played after it or before it
+    Ally,
+}
+
+/// Triggered ability of a card
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Trigger {
+    /// Moment the ability is resolved at
+    pub when: When,
+    /// Effects resolved on behalf of the card's owner
+    #[serde(default)]
+    pub effects: Vec<Effect>,
+}
+
+/// Trigger of a particular card
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Registration {
+    /// Owner of the card
+    pub player: PlayerId,
+    /// The card
+    pub card: CardId,
+    /// Index of the trigger in the card's definition
+    pub trigger: usize,
+}
+
+impl Game {
+    /// Triggers of the cards in play, in the order they were played
+    pub fn triggers(&self) -> &[Registration] {
+        &self.triggers
+    }
+
+    /// Registers all the triggers of the card put into play
+    pub(crate) fn register(&mut self, player: PlayerId, card: CardId) {
+        let triggers = self.card(card).triggers.len();
+        self.triggers
+            .extend((0..triggers).map(|trigger| Registration {
+                player,
+                card,
+                trigger,
+            }));
+    }
+
+    /// Removes all the triggers of the card leaving play
+    pub(crate) fn unregister(&mut self, card: CardId) {
+        self.triggers
+            .retain(|registration| registration.card != card);
+    }
+
+    /// Moment the trigger is resolved at
+    fn when(&self, registration: &Registration) -> When {
+        self.card(registration.card).triggers[registration.trigger].when
+    }
+
+    /// Triggers fired by the event just folded. Players who conceded don't trigger anything.
+    pub(crate) fn fired_by(&self, event: &Event) -> Vec<Registration> {
+        let registered = |owners: &[PlayerId], when: When| -> Vec<Registration> {
+            self.triggers
+                .iter()
+                .filter(|registration| {
+                    owners.contains(&registration.player) && self.when(registration) == when
+                })
+                .copied()
+                .collect()
+        };
+
+        let fired = match *event {
+            Event::TurnStarted { player, .. } => registered(&[player], When::TurnStart),
+            Event::PhaseStarted {
+                player,
+                phase: Phase::Cleanup,
+            } => registered(&[player], When::TurnEnd),
+            Event::CardGained { player, .. } | Event::CardBought { player, .. } => {
+                registered(&[player], When::Gain)
+            }
+            Event::CardPlayed { player, card } => {
+                let mut fired = self.allies(player, card);
+                if self.card(card).is(CardType::Attack) {
+                    fired.extend(registered(&self.opponents(player), When::OpponentAttack));
+                }
+                fired
+            }
+            Event::CardTrashed { player, card, .. } => (self.card(card).triggers.iter())
+                .enumerate()
+                .filter(|(_, trigger)| trigger.when == When::Trashed)
+                .map(|(trigger, _)| Registration {
+                    player,
+                    card,
+                    trigger,
+                })
+                .collect(),
+            _ => vec![],
+        };
+
+        fired
+            .into_iter()
+            .filter(|registration| !self.player(registration.player).conceded)
+            .collect()
+    }
+
+    /// Ally triggers fired by the player's card put into play: its own if another card of its
+    /// faction is in play already, and the ones of the cards in play of its faction. Each card
+    /// allies once per turn.
+    fn allies(&self, player: PlayerId, played: CardId) -> Vec<Registration> {
+        let state = self.player(player);
+        let def = self.card(played);
+        let ally_in_play = state
+            .in_play
+            .iter()
+            .chain(&state.bases)
+            .any(|card| *card != played && self.card(*card).allied_with(def));
+
+        self.triggers
+            .iter()
+            .filter(|registration| {
+                registration.player == player
+                    && self.when(registration) == When::Ally
+                    && !self.allied.contains(&registration.card)
+            })
+            .filter(|registration| match registration.card == played {
+                true => ally_in_play,
+                false => self.card(registration.card).allied_with(def),
+            })
+            .copied()
+            .collect()
+    }
+
+    /// Puts the steps resolving all the fired triggers in front of the steps left, in the order
+    /// they resolve in
+    pub(crate) fn schedule_fired(&mut self, steps: &mut VecDeque<Step>) {
+        let fired = std::mem::take(&mut self.fired);
+        if fired.is_empty() {
+            return;
+        }
+
+        let scheduled: Vec<_> = std::iter::once(self.active)
+            .chain(self.others(self.active))
+            .flat_map(|player| {
+                let triggers = fired
+                    .iter()
+                    .filter(|registration| registration.player == player)
+                    .copied()
+                    .collect();
+                self.trigger_steps(player, triggers)
+            })
+            .collect();
+
+        for step in scheduled.into_iter().rev() {
+            steps.push_front(step);
+        }
+    }
+
+    /// Steps resolving the player's simultaneous triggers. The active player is asked to order
+    /// them first, unless they are all the same.
+    pub(crate) fn trigger_steps(&self, player: PlayerId, triggers: Vec<Registration>) -> Vec<Step> {
+        let Some(&first) = triggers.first() else {
+            return vec![];
+        };
+
+        let alike = triggers.iter().all(|registration| {
+            self.cards.def(registration.card) == self.cards.def(first.card)
+                && registration.trigger == first.trigger
+        });
+        if player == self.active && !alike {
+            return vec![Step {
+                player,
+                source: first.card,
+                kind: StepKind::Order(triggers),
+            }];
+        }
+
+        triggers
+            .into_iter()
+            .map(|registration| Step {
+                player,
+                source: registration.card,
+                kind: StepKind::Trigger(registration),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::action::{Action, Choice, Purchase};
+    use crate::card::CardSet;
+    use crate::decision::Request;
+    use crate::loader;
+    use crate::test_util::{game, give, p};
+
+    fn card_set() -> CardSet {
+        let defs = loader::parse(
+            "test.toml",
+            r#"
+                [[card]]
+                name = "Copper"
+                cost = 0
+                effects = [{ resources = 1 }]
+                starter = 7
+                supply = 30
+
+                [[card]]
+                name = "Estate"
+                cost = 0
+                starter = 3
+                supply = 8
+
+                [[card]]
+                name = "Tax Office"
+                cost = 3
+                types = ["base"]
+                durability = 3
+                triggers = [{ when = "gain", effects = [{ resources = 1 }] }]
+
+                [[card]]
+                name = "Granary"
+                cost = 3
+                types = ["base"]
+                durability = 3
+                triggers = [{ when = "gain", effects = [{ draw = 1 }] }]
+
+                [[card]]
+                name = "Relic"
+                cost = 2
+                effects = [{ trash = "this" }]
+                triggers = [{ when = "trashed", effects = [{ draw = 2 }] }]
+
+                [[card]]
+                name = "Caravan"
+                cost = 2
+                triggers = [{ when = "turn_end", effects = [{ gain = { card = "Estate", to = "deck" } }] }]
+
+                [[card]]
+                name = "Watchtower"
+                cost = 3
+                types = ["base"]
+                durability = 3
+                triggers = [{ when = "opponent_attack", effects = [{ draw = 1 }] }]
+
+                [[card]]
+                name = "Raider"
+                cost = 4
+                types = ["action", "attack"]
+                effects = [{ force_discard = { random = 1 } }]
+
+                [[card]]
+                name = "Clerk"
+                cost = 2
+                factions = ["guild"]
+                triggers = [{ when = "ally", effects = [{ resources = 1 }] }]
+
+                [[card]]
+                name = "Guildhall"
+                cost = 4
+                types = ["base"]
+                factions = ["guild"]
+                durability = 4
+            "#,
+        )
+        .unwrap();
+        CardSet::new(defs)
+    }
+
+    fn play(game: &mut Game, player: PlayerId, name: &str) -> (CardId, Vec<Event>) {
+        let card = give(game, player, name);
+        (card, game.apply(player, Action::PlayCard(card)).unwrap())
+    }
+
+    fn triggered(events: &[Event]) -> Vec<CardId> {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                Event::Triggered { card, .. } => Some(*card),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn buy_estate(game: &mut Game, player: PlayerId) -> Vec<Event> {
+        let estate = game.card_set().find("Estate").unwrap();
+        if game.phase() != Phase::Buy {
+            game.apply(player, Action::EndPhase).unwrap();
+        }
+        game.apply(player, Action::BuyCard(Purchase::Pile(estate)))
+            .unwrap()
+    }
+
+    #[test]
+    fn triggers_fire_on_events() {
+        let mut game = game(card_set());
+
+        // Trashing a card triggers it wherever it goes
+        let (relic, events) = play(&mut game, p(0), "Relic");
+        assert_eq!(triggered(&events), [relic]);
+        assert_eq!(game.player(p(0)).hand().len(), 7);
+
+        // Cards in play trigger for a single turn
+        let (caravan, _) = play(&mut game, p(0), "Caravan");
+        assert_eq!(game.triggers().len(), 1);
+        let events = game.apply(p(0), Action::EndTurn).unwrap();
+        assert_eq!(triggered(&events), [caravan]);
+        assert_eq!(game.triggers(), []);
+        let gained = events
+            .iter()
+            .position(|e| matches!(e, Event::CardGained { .. }));
+        let ended = events
+            .iter()
+            .position(|e| matches!(e, Event::TurnEnded { .. }));
+        assert!(gained < ended);
+
+        // Bases keep triggering for opponents' attacks
+        let (watchtower, _) = play(&mut game, p(1), "Watchtower");
+        let hand = game.player(p(1)).hand().len();
+        game.apply(p(1), Action::EndTurn).unwrap();
+        let (_, events) = play(&mut game, p(0), "Raider");
+        assert_eq!(triggered(&events), [watchtower]);
+        let drawn = events
+            .iter()
+            .position(|e| matches!(e, Event::CardDrawn { .. }));
+        let discarded = events
+            .iter()
+            .position(|e| matches!(e, Event::CardDiscarded { .. }));
+        assert!(drawn < discarded);
+        // Drew a card before discarding one
+        assert_eq!(game.player(p(1)).hand().len(), hand);
+
+        // Gaining a card
+        let (office, _) = play(&mut game, p(0), "Tax Office");
+        let events = buy_estate(&mut game, p(0));
+        assert_eq!(triggered(&events), [office]);
+        assert_eq!(game.player(p(0)).resources(), 1);
+    }
+
+    #[test]
+    fn active_player_orders_simultaneous_triggers() {
+        let mut game = game(card_set());
+        let (office, _) = play(&mut game, p(0), "Tax Office");
+        let (granary, _) = play(&mut game, p(0), "Granary");
+
+        let events = buy_estate(&mut game, p(0));
+        let Some(Event::DecisionRequested { decision }) = events.last() else {
+            panic!("Unexpected events: {events:?}");
+        };
+        assert_eq!(decision.player(), p(0));
+        let Request::Order(triggers) = decision.request() else {
+            panic!("Unexpected request: {:?}", decision.request());
+        };
+        assert_eq!(triggers.len(), 2);
+        assert!(
+            game.legal_actions(p(0))
+                .contains(&Action::ResolveChoice(Choice::Option(1)))
+        );
+
+        let events = game
+            .apply(p(0), Action::ResolveChoice(Choice::Option(1)))
+            .unwrap();
+        assert_eq!(triggered(&events), [granary, office]);
+        assert_eq!(game.pending(), None);
+        assert_eq!(game.player(p(0)).resources(), 1);
+
+        // Same triggers need no ordering
+        let mut game = self::game(card_set());
+        let (first, _) = play(&mut game, p(0), "Tax Office");
+        let (second, _) = play(&mut game, p(0), "Tax Office");
+        let events = buy_estate(&mut game, p(0));
+        assert_eq!(triggered(&events), [first, second]);
+        assert_eq!(game.player(p(0)).resources(), 2);
+
+        // Snapshots keep the registrations
+        let restored = Game::restore(&game.snapshot()).unwrap();
+        assert_eq!(restored.triggers, game.triggers);
+    }
+
+    #[test]
+    fn allies_trigger_once_per_turn() {
+        let mut game = game(card_set());
+        let (first, events) = play(&mut game, p(0), "Clerk");
+        assert_eq!(triggered(&events), []);
+
+        // Both the played card and the one in play find an ally
+        let (second, events) = play(&mut game, p(0), "Clerk");
+        assert_eq!(triggered(&events), [first, second]);
+        let (third, events) = play(&mut game, p(0), "Clerk");
+        assert_eq!(triggered(&events), [third]);
+        assert_eq!(game.player(p(0)).resources(), 3);
+
+        // Bases in play are allies in the following turns as well
+        play(&mut game, p(0), "Guildhall");
+        game.apply(p(0), Action::EndTurn).unwrap();
+        game.apply(p(1), Action::EndTurn).unwrap();
+        let (clerk, events) = play(&mut game, p(0), "Clerk");
+        assert_eq!(triggered(&events), [clerk]);
+    }
+}
@@ -0,0 +1,204 @@
+//! Taking back actions of the turn
+//!
+//! Actions can be undone as long as they revealed nothing new and affected nobody but the active
+//! player. Events drawing or revealing unknown cards, shuffling, or concerning other players are
+//! irreversible - once one is folded, the actions before it are final. The game keeps the state
+//! from right before the first action taken since the last irreversible event, and `Undo`
+//! returns to it: to the start of the turn, or to the last irreversible action in it.
+//!
+//! The checkpoint is kept while folding events, so replaying the log rebuilds it as well. It is not
+//! a part of snapshots: a game restored from a snapshot can only undo actions folded after it, so
+//! snapshots to resume the game from are best taken while there is nothing to undo.
+
+use crate::Game;
+use crate::event::Event;
+use crate::phase::Phase;
+use crate::player::PlayerId;
+
+impl Event {
+    /// Checks if the event makes the actions before it final
+    fn is_irreversible(&self, active: PlayerId) -> bool {
+        match *self {
+            Event::CardDrawn { .. }
+            | Event::DeckReshuffled { .. }
+            | Event::RandomCardPicked { .. }
+            | Event::MarketRefilled { .. }
+            | Event::TurnEnded { .. }
+            | Event::TurnStarted { .. }
+            | Event::Conceded { .. }
+            | Event::GameEnded { .. } => true,
+            Event::DecisionRequested { ref decision } => decision.player() != active,
+            Event::CardPlayed { player, .. }
+            | Event::ResourcesGained { player, .. }
+            | Event::CardBought { player, .. }
+            | Event::CardGained { player, .. }
+            | Event::CardTrashed { player, .. }
+            | Event::CardDiscarded { player, .. }
+            | Event::PhaseStarted { player, .. }
+            | Event::DecisionResolved { player, .. }
+            | Event::ReactionRevealed { player, .. }
+            | Event::AttackBlocked { player }
+            | Event::DamageDealt { player, .. }
+            | Event::BaseDeployed { player, .. }
+            | Event::BaseDamaged { player, .. }
+            | Event::BaseDestroyed { player, .. }
+            | Event::Triggered { player, .. }
+            | Event::Undone { player } => player != active,
+        }
+    }
+
+    /// Checks if the event is the first one of an action, so the state before it is a state
+    /// between two actions
+    fn opens_action(&self) -> bool {
+        match self {
+            Event::CardPlayed { .. } | Event::CardBought { .. } => true,
+            Event::DecisionResolved { .. } => true,
+            Event::PhaseStarted { phase, .. } => *phase != Phase::Action,
+            _ => false,
+        }
+    }
+}
+
+impl Game {
+    /// Checks if the active player can undo any of their actions
+    pub fn can_undo(&self) -> bool {
+        self.checkpoint.is_some()
+    }
+
+    /// Keeps the checkpoint up to date with the event about to be folded
+    pub(crate) fn track_checkpoint(&mut self, event: &Event) {
+        if event.is_irreversible(self.active) {
+            self.checkpoint = None;
+        } else if self.checkpoint.is_none() && event.opens_action() {
+            self.checkpoint = Some(Box::new(self.clone()));
+        }
+    }
+
+    /// Returns to the checkpoint. Returns `false` if there is none.
+    pub(crate) fn restore_checkpoint(&mut self) -> bool {
+        match self.checkpoint.take() {
+            Some(checkpoint) => {
+                *self = *checkpoint;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::action::{Action, Purchase, Rejection};
+    use crate::card::{CardDef, CardSet, CardType};
+    use crate::effect::{Effect, Selection};
+    use crate::test_util::{game, give, p};
+    use crate::{Choice, Setup};
+
+    fn card_set() -> CardSet {
+        CardSet::new([
+            CardDef::new("Copper", 0).resources(1).starter(7).supply(30),
+            CardDef::new("Estate", 0).starter(3).supply(8),
+            CardDef::new("Smith", 0).effect(Effect::Draw(2)).supply(8),
+            CardDef::new("Recycler", 0)
+                .effect(Effect::Trash(Selection::Choose { min: 0, max: 1 }))
+                .supply(8),
+            CardDef::new("Raider", 0)
+                .types([CardType::Action, CardType::Attack])
+                .effect(Effect::ForceDiscard(Selection::Random(1)))
+                .supply(8),
+            CardDef::new("Village", 2).market(8),
+        ])
+    }
+
+    #[test]
+    fn undoing_to_the_start_of_the_turn() {
+        let mut game = game(card_set());
+        let player = game.active();
+        let start = game.clone();
+        assert!(!game.can_undo());
+        assert_eq!(
+            game.apply(player, Action::Undo),
+            Err(Rejection::NothingToUndo)
+        );
+
+        for card in game.player(player).hand().to_vec() {
+            game.apply(player, Action::PlayCard(card)).unwrap();
+        }
+        game.apply(player, Action::EndPhase).unwrap();
+        let estate = game.card_set().find("Estate").unwrap();
+        game.apply(player, Action::BuyCard(Purchase::Pile(estate)))
+            .unwrap();
+        assert!(game.legal_actions(player).contains(&Action::Undo));
+        assert!(!game.legal_actions(p(1)).contains(&Action::Undo));
+        assert_eq!(
+            game.apply(p(1), Action::Undo),
+            Err(Rejection::NotYourTurn(p(1)))
+        );
+
+        let events = game.apply(player, Action::Undo).unwrap();
+        assert_eq!(events, [Event::Undone { player }]);
+        assert_eq!(game, start);
+    }
+
+    #[test]
+    fn irreversible_actions_are_final() {
+        let mut game = game(card_set());
+        let player = game.active();
+
+        // Drawing cards cannot be taken back
+        let smith = give(&mut game, player, "Smith");
+        game.apply(player, Action::PlayCard(smith)).unwrap();
+        assert!(!game.can_undo());
+
+        // Own decisions can
+        let recycler = give(&mut game, player, "Recycler");
+        let before = game.clone();
+        game.apply(player, Action::PlayCard(recycler)).unwrap();
+        let card = game.player(player).hand()[0];
+        let choice = Choice::Cards(vec![card]);
+        game.apply(player, Action::ResolveChoice(choice)).unwrap();
+        assert!(game.can_undo());
+        game.apply(player, Action::Undo).unwrap();
+        assert_eq!(game, before);
+
+        // Attacking the opponent cannot
+        let raider = give(&mut game, player, "Raider");
+        game.apply(player, Action::PlayCard(raider)).unwrap();
+        assert!(!game.can_undo());
+
+        // Buying from the market reveals the next market card
+        let mut game = self::game(card_set());
+        let village = game.supply().market()[0];
+        game.apply(player, Action::EndPhase).unwrap();
+        game.players[player.seat()].resources = 2;
+        assert!(game.can_undo());
+        game.apply(player, Action::BuyCard(Purchase::Market(village)))
+            .unwrap();
+        assert!(!game.can_undo());
+    }
+
+    #[test]
+    fn replay_rebuilds_the_checkpoint() {
+        let mut game = game(card_set());
+        let player = game.active();
+        let mut log = vec![];
+        let setup = Setup::new(game.card_set().clone(), 2, 4).config(game.config().clone());
+
+        let snapshot = game.snapshot();
+        let cards = game.player(player).hand().to_vec();
+        log.extend(game.apply(player, Action::PlayCard(cards[0])).unwrap());
+        log.extend(game.apply(player, Action::PlayCard(cards[1])).unwrap());
+        let replayed = Game::replay(setup.clone(), &log).unwrap();
+        assert_eq!(replayed, game);
+
+        // Snapshots leave the checkpoint out, folding the events after them brings it back
+        assert!(!Game::restore(&game.snapshot()).unwrap().can_undo());
+        let restored = Game::restore(&snapshot).unwrap().advance(&log).unwrap();
+        assert_eq!(restored, game);
+
+        log.extend(game.apply(player, Action::Undo).unwrap());
+        log.extend(game.apply(player, Action::PlayCard(cards[2])).unwrap());
+        assert_eq!(Game::replay(setup, &log), Ok(game));
+    }
+}
@@ -0,0 +1,292 @@
+//! Per-player projections of the game state
+//!
+//! The full `Game` knows every hidden card. A `PlayerView` is the part of it a single player is
+//! allowed to see: their own hand and all the public zones, plus whatever their teammates share
+//! with them. Other hands and all the draw piles are reduced to card counts, so neither their
+//! contents nor their order leak.
+
+use serde::{Deserialize, Serialize};
+
+use crate::Game;
+use crate::card::{CardDefId, CardId};
+use crate::commitment::sha3_hex;
+use crate::decision::Request;
+use crate::end::GameResult;
+use crate::legal::LegalActions;
+use crate::phase::Phase;
+use crate::player::PlayerId;
+use crate::supply::Pile;
+use crate::team::Shared;
+
+/// Face-up card
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CardView {
+    /// Card instance
+    pub id: CardId,
+    /// Definition of the card in the game's card set
+    pub def: CardDefId,
+}
+
+/// Base in play
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BaseView {
+    /// Card instance
+    pub id: CardId,
+    /// Definition of the card in the game's card set
+    pub def: CardDefId,
+    /// Durability the base has left
+    pub durability: u32,
+}
+
+/// Player's zones as seen by the viewer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SeatView {
+    /// Player seated here
+    pub player: PlayerId,
+    /// Team of the player in team games
+    pub team: Option<usize>,
+    /// Cards in hand, visible only to their owner and teammates the hand is shared with
+    pub hand: Option<Vec<CardView>>,
+    /// Number of cards in hand
+    pub hand_size: usize,
+    /// Number of cards in the deck
+    pub deck_size: usize,
+    /// Discard pile, top card last
+    pub discard: Vec<CardView>,
+    /// Cards played this turn
+    pub in_play: Vec<CardView>,
+    /// Bases in play, in the order they were deployed
+    pub bases: Vec<BaseView>,
+    /// Trashed cards
+    pub trash: Vec<CardView>,
+    /// Resources left to spend this turn
+    pub resources: u32,
+    /// Health left
+    pub health: u32,
+    /// Player left the game
+    pub conceded: bool,
+}
+
+/// Supply as seen by the players
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SupplyView {
+    /// Supply piles
+    pub piles: Vec<Pile>,
+    /// Face-up market row
+    pub market: Vec<CardView>,
+    /// Number of cards left in the market deck
+    pub market_deck_size: usize,
+}
+
+/// Decision the game waits for, as seen by the viewer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DecisionView {
+    /// Player who must answer
+    pub player: PlayerId,
+    /// Card whose ability waits for the decision
+    pub source: CardView,
+    /// Decision is a window to react to an attack
+    pub reaction: bool,
+    /// What the player is asked for, visible only to the answering player and teammates the
+    /// decisions are shared with
+    pub request: Option<Request>,
+}
+
+/// Game state as seen by a single player
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlayerView {
+    /// Player seeing the game
+    pub viewer: PlayerId,
+    /// Players in the seat order
+    pub players: Vec<SeatView>,
+    /// Shared supply
+    pub supply: SupplyView,
+    /// Player owning the current turn
+    pub active: PlayerId,
+    /// Current turn number
+    pub turn: u32,
+    /// Phase of the current turn
+    pub phase: Phase,
+    /// Decision the game waits for
+    pub pending: Option<DecisionView>,
+    /// Actions the viewer can take
+    pub legal_actions: LegalActions,
+    /// Outcome of the game, once it is over
+    pub result: Option<GameResult>,
+}
+
+impl PlayerView {
+    /// SHA3-256 hash of the view serialized to JSON, hex encoded. Anyone holding the view can
+    /// recompute it, eg. to compare the view received from the server with a predicted one.
+    pub fn checksum(&self) -> String {
+        let view = serde_json::to_string(self).expect("PlayerView is always serializable");
+        sha3_hex(view.as_bytes())
+    }
+}
+
+impl Game {
+    /// Projects the game state to what the player is allowed to see
+    pub fn view(&self, viewer: PlayerId) -> PlayerView {
+        let face_up = |cards: &[CardId]| -> Vec<CardView> {
+            cards
+                .iter()
+                .map(|card| CardView {
+                    id: *card,
+                    def: self.cards.def(*card),
+                })
+                .collect()
+        };
+
+        let players = self
+            .player_ids()
+            .zip(&self.players)
+            .map(|(player, state)| SeatView {
+                player,
+                team: self.team(player),
+                hand: self
+                    .shares(viewer, player, Shared::Hand)
+                    .then(|| face_up(&state.hand)),
+                hand_size: state.hand.len(),
+                deck_size: state.deck.len(),
+                discard: face_up(&state.discard),
+                in_play: face_up(&state.in_play),
+                bases: state
+                    .bases
+                    .iter()
+                    .map(|&id| BaseView {
+                        id,
+                        def: self.cards.def(id),
+                        durability: self.durability(id),
+                    })
+                    .collect(),
+                trash: face_up(&state.trash),
+                resources: state.resources,
+                health: state.health,
+                conceded: state.conceded,
+            })
+            .collect();
+
+        let supply = SupplyView {
+            piles: self.supply.piles().to_vec(),
+            market: face_up(self.supply.market()),
+            market_deck_size: self.supply.market_deck().len(),
+        };
+
+        let pending = self.pending.as_ref().map(|decision| DecisionView {
+            player: decision.player(),
+            source: face_up(&[decision.source()])[0],
+            reaction: decision.is_reaction(),
+            request: self
+                .shares(viewer, decision.player(), Shared::Decisions)
+                .then(|| decision.request().clone()),
+        });
+
+        PlayerView {
+            viewer,
+            players,
+            supply,
+            active: self.active,
+            turn: self.turn,
+            phase: self.phase,
+            pending,
+            legal_actions: self.legal_actions(viewer),
+            result: self.result.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::card::CardSet;
+    use crate::test_util::{give, p};
+    use crate::{Action, Choice, Setup};
+
+    #[test]
+    fn opponents_cards_are_hidden() {
+        let game = Game::new(Setup::new(CardSet::basic(), 2, 2)).unwrap();
+        let view = game.view(p(0));
+
+        assert_eq!(view.viewer, p(0));
+        let [me, opponent] = &view.players[..] else {
+            panic!("Unexpected players: {:?}", view.players);
+        };
+
+        let hand: Vec<_> = me.hand.iter().flatten().map(|card| card.id).collect();
+        assert_eq!(hand, game.player(p(0)).hand());
+        assert_eq!(me.deck_size, game.player(p(0)).deck().len());
+
+        assert_eq!(opponent.hand, None);
+        assert_eq!(opponent.hand_size, game.player(p(1)).hand().len());
+        assert_eq!(opponent.deck_size, game.player(p(1)).deck().len());
+
+        assert_eq!(
+            view.supply
+                .market
+                .iter()
+                .map(|card| card.id)
+                .collect::<Vec<_>>(),
+            game.supply().market()
+        );
+        assert_eq!(
+            view.supply.market_deck_size,
+            game.supply().market_deck().len()
+        );
+        assert_eq!(view.legal_actions, game.legal_actions(p(0)));
+        assert_eq!(game.view(p(1)).legal_actions.actions, [Action::Concede]);
+    }
+
+    #[test]
+    fn view_checksums_survive_serialization() {
+        let game = Game::new(Setup::new(CardSet::basic(), 2, 2)).unwrap();
+        let view = game.view(p(0));
+        let json = serde_json::to_value(&view).unwrap();
+        let received: PlayerView = serde_json::from_value(json).unwrap();
+        assert_eq!(received.checksum(), view.checksum());
+        assert_ne!(game.view(p(1)).checksum(), view.checksum());
+    }
+
+    #[test]
+    fn hidden_cards_do_not_leak() {
+        let game = Game::new(Setup::new(CardSet::basic(), 2, 2)).unwrap();
+
+        // Swap opponent's hand with their deck and reorder the viewer's deck
+        let mut other = game.clone();
+        let opponent = &mut other.players[1];
+        std::mem::swap(&mut opponent.hand, &mut opponent.deck);
+        other.players[0].deck.reverse();
+        assert_ne!(game, other);
+
+        assert_eq!(game.view(p(0)), other.view(p(0)));
+    }
+
+    #[test]
+    fn decisions_are_visible_to_the_answering_player() {
+        let mut game = Game::new(Setup::new(CardSet::basic(), 2, 2)).unwrap();
+        let card = give(&mut game, p(0), "Recycler");
+        game.apply(p(0), Action::PlayCard(card)).unwrap();
+
+        let pending = game.view(p(0)).pending.unwrap();
+        assert_eq!(pending.player, p(0));
+        assert_eq!(
+            pending.source,
+            CardView {
+                id: card,
+                def: game.cards().def(card)
+            }
+        );
+        assert_eq!(
+            pending.request.as_ref(),
+            game.pending().map(|d| d.request())
+        );
+        assert!(
+            game.view(p(0))
+                .legal_actions
+                .contains(&Action::ResolveChoice(Choice::Cards(vec![])))
+        );
+
+        let pending = game.view(p(1)).pending.unwrap();
+        assert_eq!(pending.player, p(0));
+        assert_eq!(pending.request, None);
+    }
+}
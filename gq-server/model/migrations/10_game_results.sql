@@ -0,0 +1,2 @@
+-- Outcome of the finished game, JSON encoded. Not set while the game is in progress.
+ALTER TABLE games ADD COLUMN result text;
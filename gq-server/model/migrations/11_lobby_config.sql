@@ -0,0 +1,3 @@
+-- Rules variant the lobby game will be played with, JSON encoded. Games without it are played
+-- with the standard rules.
+ALTER TABLE lobby ADD COLUMN config text;
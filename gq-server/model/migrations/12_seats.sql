@@ -0,0 +1,66 @@
+-- Games are played by 2 to 6 players. Instead of the fixed `player1` and `player2` columns, every
+-- lobby game has a number of seats, and players taking them are listed in the `seats` table.
+PRAGMA foreign_keys = OFF;
+
+-- Players seated at the game. A game keeps its id when it is started, so its seats stay the same
+-- both in the lobby and once it is ongoing.
+CREATE TABLE seats (
+  -- Lobby or ongoing game
+  game_id blob not null,
+  -- Position in the seat order, starting from 0
+  seat integer not null,
+  -- Player taking the seat
+  user_id blob references users(id) not null,
+  primary key (game_id, seat)
+);
+
+INSERT INTO seats (game_id, seat, user_id)
+SELECT id, 0, player1 FROM lobby WHERE player1 IS NOT NULL
+UNION ALL
+SELECT id, CASE WHEN player1 IS NULL THEN 0 ELSE 1 END, player2 FROM lobby WHERE player2 IS NOT NULL
+UNION ALL
+SELECT id, 0, player1 FROM games
+UNION ALL
+SELECT id, 1, player2 FROM games;
+
+CREATE TABLE lobby_new (
+  -- Created game ID
+  id blob primary key not null,
+  -- User that created the game
+  created_by blob references users(id) not null,
+  -- Rules variant the lobby game will be played with, JSON encoded
+  config text,
+  -- Number of players the game is started with
+  seats integer not null default 2
+);
+
+INSERT INTO lobby_new (id, created_by, config)
+SELECT id, created_by, config FROM lobby;
+
+DROP TABLE lobby;
+ALTER TABLE lobby_new RENAME TO lobby;
+
+CREATE TABLE games_new (
+  -- Created game ID
+  id blob primary key not null,
+  -- User that created the game
+  created_by blob references users(id) not null,
+  -- Engine setup of the game, JSON encoded
+  setup text not null,
+  -- Time until which the reacting player can respond to an attack
+  reaction_deadline TIMESTAMP,
+  -- Latest snapshot of the engine state, covering the first `snapshot_seq` events
+  snapshot text,
+  snapshot_seq integer not null default 0,
+  -- Outcome of the finished game, JSON encoded
+  result text
+);
+
+INSERT INTO games_new (id, created_by, setup, reaction_deadline, snapshot, snapshot_seq, result)
+SELECT id, created_by, setup, reaction_deadline, snapshot, snapshot_seq, result FROM games;
+
+DROP TABLE games;
+ALTER TABLE games_new RENAME TO games;
+
+PRAGMA foreign_keys = ON;
+PRAGMA foreign_key_check;
@@ -0,0 +1,6 @@
+-- Checksum of the engine state right after the event was folded, hex encoded. Not set for events
+-- recorded before checksums were introduced.
+ALTER TABLE game_events ADD COLUMN checksum text;
+-- Snapshot version of the engine which computed the checksum. Checksums of other versions are
+-- not comparable, as the state they hash is laid out differently.
+ALTER TABLE game_events ADD COLUMN checksum_version integer;
@@ -0,0 +1,8 @@
+-- Commitment of the RNG seed in the game setup, published when the game starts so the seed
+-- revealed after the game can be verified. Not set for games started before commitments were
+-- introduced.
+ALTER TABLE games ADD COLUMN seed_commitment text;
+-- Random nonce the seed was salted with before hashing it into the commitment, revealed together
+-- with the seed. Games without a commitment get the zero nonce.
+ALTER TABLE games ADD COLUMN seed_nonce blob not null
+  default x'0000000000000000000000000000000000000000000000000000000000000000';
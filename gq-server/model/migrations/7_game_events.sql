@@ -0,0 +1,35 @@
+-- Games are now played by the engine. Every game stores the setup it was created with, and all
+-- the events applied to it are appended to the `game_events` log. Games started before have no
+-- setup and cannot be restored, so they are dropped.
+PRAGMA foreign_keys = OFF;
+
+DELETE FROM games;
+
+CREATE TABLE games_new (
+  -- Created game ID
+  id blob primary key not null,
+  -- User that created the game
+  created_by blob references users(id) not null,
+  -- Player IDs - game already started so both are required
+  player1 blob references users(id) not null,
+  player2 blob references users(id) not null,
+  -- Engine setup of the game, JSON encoded
+  setup text not null
+);
+
+DROP TABLE games;
+ALTER TABLE games_new RENAME TO games;
+
+-- Append-only log of the game events
+CREATE TABLE game_events (
+  -- Game the event belongs to
+  game_id blob references games(id) not null,
+  -- Position of the event in the game log, starting from 0
+  seq integer not null,
+  -- The event, JSON encoded
+  event text not null,
+  primary key (game_id, seq)
+);
+
+PRAGMA foreign_keys = ON;
+PRAGMA foreign_key_check;
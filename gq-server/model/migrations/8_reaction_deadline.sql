@@ -0,0 +1,3 @@
+-- Time until which the reacting player can respond to an attack. Set only while a reaction
+-- window is open - once it passes, the window is closed without a reaction.
+ALTER TABLE games ADD COLUMN reaction_deadline TIMESTAMP;
@@ -0,0 +1,5 @@
+-- Latest snapshot of the engine state, JSON encoded in the versioned snapshot format. It covers
+-- the first `snapshot_seq` events of the log, only the later ones are folded on top of it when the
+-- game is loaded. Games without a snapshot are replayed from their setup.
+ALTER TABLE games ADD COLUMN snapshot text;
+ALTER TABLE games ADD COLUMN snapshot_seq integer not null default 0;
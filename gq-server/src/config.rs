@@ -6,6 +6,8 @@ use std::path::PathBuf;
 use serde::{Deserialize, Deserializer};
 use tracing_subscriber::filter::Directive;
 
+use crate::model::game::DEFAULT_REACTION_TIME;
+
 /// Logging output format
 #[derive(Debug, Clone, Copy, Deserialize, Default)]
 pub enum LogFormat {
@@ -95,10 +97,22 @@ pub struct Config {
     /// Database configuration
     #[serde(default)]
     pub db: Database,
+
+    /// Directory with the card set files. The basic card set is used if not provided.
+    #[serde(default)]
+    pub cards: Option<PathBuf>,
+
+    /// Time in seconds opponents have to react to an attack
+    #[serde(default = "Config::default_reaction_time")]
+    pub reaction_time: u32,
 }
 
 impl Config {
     fn default_host() -> SocketAddr {
         ([127, 0, 0, 1], 3030).into()
     }
+
+    fn default_reaction_time() -> u32 {
+        DEFAULT_REACTION_TIME.num_seconds() as u32
+    }
 }
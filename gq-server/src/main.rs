@@ -1,8 +1,10 @@
 //! GraphQL async deckbuilder interface
 
 use actix_web::{App, HttpServer};
+use chrono::TimeDelta;
 use clap::Parser;
 use color_eyre::Result;
+use game::CardSet;
 use std::io::read_to_string;
 use tracing::{info, warn};
 use tracing_actix_web::TracingLogger;
@@ -62,8 +64,15 @@ async fn main() -> Result<()> {
         "Tracing initialized, setting up a service"
     );
 
+    let card_set = match &config.cards {
+        Some(dir) => CardSet::load_dir(dir)?,
+        None => CardSet::basic(),
+    };
+    info!(cards = card_set.len(), dir = ?config.cards, "Card set loaded");
+
     let graphiql_enabled = config.graphiql;
-    let context = Model::with_config(config.db).await?;
+    let reaction_time = TimeDelta::seconds(config.reaction_time.into());
+    let context = Model::with_config(config.db, card_set, reaction_time).await?;
 
     {
         let context = context.clone();
@@ -2,6 +2,10 @@
 
 use std::path::PathBuf;
 
+use std::sync::Arc;
+
+use ::game::CardSet;
+use chrono::TimeDelta;
 use color_eyre::Result;
 
 pub mod auth;
@@ -33,6 +37,10 @@ pub enum Error {
 pub struct Model {
     /// Database access
     db: sqlx::SqlitePool,
+    /// Cards new games are played with
+    card_set: Arc<CardSet>,
+    /// Time opponents have to react to an attack
+    reaction_time: TimeDelta,
 }
 
 impl Model {
@@ -50,14 +58,22 @@ impl Model {
 
         sqlx::migrate!("model/migrations").run(&db).await.unwrap();
 
-        Ok(Self { db })
+        Ok(Self {
+            db,
+            card_set: Arc::new(CardSet::basic()),
+            reaction_time: game::DEFAULT_REACTION_TIME,
+        })
     }
 
     /// Context from configuration
     ///
     /// If the database is created in-memory, the migrations are being executed automatically. If database is
     /// file based migrations would be executed only if requested by configuration.
-    pub async fn with_config(config: config::Database) -> Result<Self> {
+    pub async fn with_config(
+        config: config::Database,
+        card_set: CardSet,
+        reaction_time: TimeDelta,
+    ) -> Result<Self> {
         use config::Database::*;
 
         let db = match config {
@@ -103,7 +119,11 @@ impl Model {
             }
         };
 
-        Ok(Self { db })
+        Ok(Self {
+            db,
+            card_set: Arc::new(card_set),
+            reaction_time,
+        })
     }
 
     /// Buids schema with attached context
@@ -118,6 +138,16 @@ impl Model {
         &self.db
     }
 
+    /// Card set new games are played with
+    pub fn card_set(&self) -> &CardSet {
+        &self.card_set
+    }
+
+    /// Time opponents have to react to an attack
+    pub fn reaction_time(&self) -> TimeDelta {
+        self.reaction_time
+    }
+
     /// Performs cleanup on the model
     pub async fn cleanup(&self) -> Result<()> {
         Session::cleanup(&self.db).await
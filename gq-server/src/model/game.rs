@@ -1,11 +1,17 @@
 //! Game model
 
 use async_graphql::scalar;
+use chrono::{DateTime, TimeDelta, Utc};
 use color_eyre::Result;
-use color_eyre::eyre::ensure;
+use color_eyre::eyre::{OptionExt, ensure};
+use game::{
+    Action, CardSet, Event, GameConfig, GameResult, LogEntry, Nonce, PlayerId, PlayerView,
+    Rejection, Setup,
+};
 use serde::{Deserialize, Serialize};
 use sqlx::prelude::Type;
 use thiserror::Error;
+use tracing::warn;
 use uuid::Uuid;
 
 use crate::model::users::UserId;
@@ -16,8 +22,30 @@ pub enum Error {
     CannotStartGame(GameId),
     #[error("Missing player")]
     MissingPlayer,
+    #[error("Invalid number of seats: {0}")]
+    InvalidSeatCount(usize),
+    #[error("User is not playing this game")]
+    NotAPlayer,
+    #[error("Action rejected: {0}")]
+    Rejected(#[from] Rejection),
+    #[error("Replaying game {0} does not reproduce the recorded state")]
+    ChecksumMismatch(GameId),
+    #[error("Game is not played in teams")]
+    NoTeams,
+    #[error("Invalid team {0}, teams are numbered below the number of seats")]
+    InvalidTeam(usize),
+    #[error("Seat {0} is not taken")]
+    EmptySeat(usize),
+    #[error("Game has to be played by at least two teams")]
+    SingleTeam,
+    #[error("Invalid rules variant: {0}")]
+    InvalidConfig(game::Error),
 }
 
+/// Time opponents have to react to an attack unless configured otherwise. Once it passes, the
+/// reaction window is closed without a reaction.
+pub const DEFAULT_REACTION_TIME: TimeDelta = TimeDelta::hours(1);
+
 /// Game ID newtype
 #[derive(Debug, Clone, Copy, PartialEq, Type, Serialize, Deserialize)]
 #[sqlx(transparent)]
@@ -32,6 +60,17 @@ impl std::fmt::Display for GameId {
 
 scalar!(GameId);
 
+/// Game as seen by a player, with its checksum. The checksum is the one of `PlayerView`, so clients
+/// can recompute it from the view alone, or compare it with the one of their own prediction to
+/// detect divergence.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CheckedView {
+    #[serde(flatten)]
+    pub view: PlayerView,
+    /// Checksum of the view
+    pub checksum: String,
+}
+
 /// Game in the lobby
 #[derive(Debug, Clone)]
 pub struct LobbyGame {
@@ -39,10 +78,12 @@ pub struct LobbyGame {
     id: GameId,
     /// Created by user ID
     created_by: UserId,
-    /// Player 1 ID
-    pub player1: Option<UserId>,
-    /// Player 2 ID
-    pub player2: Option<UserId>,
+    /// Players who took their seats, in the seat order
+    pub players: Vec<UserId>,
+    /// Number of players the game is started with
+    pub seats: usize,
+    /// Rules variant the game will be played with
+    pub config: GameConfig,
 }
 
 impl LobbyGame {
@@ -58,82 +99,199 @@ impl LobbyGame {
 
     /// Checks if the user is involved in a game
     pub fn is_involved(&self, user_id: UserId) -> bool {
-        self.created_by == user_id || self.player1 == Some(user_id) || self.player2 == Some(user_id)
+        self.created_by == user_id || self.players.contains(&user_id)
     }
 
-    /// Creates a new game in the lobby
+    /// Checks if all the seats are taken
+    pub fn is_full(&self) -> bool {
+        self.players.len() >= self.seats
+    }
+
+    /// Team chosen by every seated player, in the seat order. `None` if the game is not played in
+    /// teams.
+    pub fn teams(&self) -> Option<&[usize]> {
+        self.config.teams.as_ref().map(|teams| &teams.seats[..])
+    }
+
+    /// Assigns the team to the seat, either taken already or the one being taken next. Teams are
+    /// numbered below the number of seats, and the full game has to be split into at least two
+    /// of them.
+    pub fn assign_team(&mut self, seat: usize, team: usize) -> Result<(), Error> {
+        if team >= self.seats {
+            return Err(Error::InvalidTeam(team));
+        }
+
+        let seats = self.seats;
+        let teams = self.config.teams.as_mut().ok_or(Error::NoTeams)?;
+        let mut assigned = teams.seats.clone();
+        let taken = assigned.len();
+        match assigned.get_mut(seat) {
+            Some(assignment) => *assignment = team,
+            None if seat == taken && seat < seats => assigned.push(team),
+            None => return Err(Error::EmptySeat(seat)),
+        }
+
+        if assigned.len() == seats && assigned.iter().all(|team| *team == assigned[0]) {
+            return Err(Error::SingleTeam);
+        }
+
+        teams.seats = assigned;
+        Ok(())
+    }
+
+    /// Creates a new two player game in the lobby, played with the standard rules
     pub async fn create(
         db: impl sqlx::Executor<'_, Database = sqlx::Sqlite>,
         created_by: UserId,
     ) -> Result<Self> {
+        Self::create_with(db, created_by, 2, GameConfig::default()).await
+    }
+
+    /// Creates a new game in the lobby for the number of players, played with the rules variant.
+    /// If the variant is played in teams, the players choose their teams when taking the seats.
+    pub async fn create_with(
+        db: impl sqlx::Executor<'_, Database = sqlx::Sqlite>,
+        created_by: UserId,
+        seats: usize,
+        mut config: GameConfig,
+    ) -> Result<Self> {
+        ensure!(
+            (game::MIN_PLAYERS..=game::MAX_PLAYERS).contains(&seats),
+            Error::InvalidSeatCount(seats)
+        );
+        config.validate().map_err(Error::InvalidConfig)?;
+
+        // Teams are assigned as the players take their seats
+        if let Some(teams) = &mut config.teams {
+            teams.seats.clear();
+        }
+
         let id = GameId(Uuid::new_v4());
-        sqlx::query("insert into lobby(id, created_by) values (?, ?)")
+        sqlx::query("insert into lobby(id, created_by, seats, config) values (?, ?, ?, ?)")
             .bind(id)
             .bind(created_by)
+            .bind(seats as i64)
+            .bind(serde_json::to_string(&config)?)
             .execute(db)
             .await?;
 
         Ok(Self {
             id,
             created_by,
-            player1: None,
-            player2: None,
+            players: vec![],
+            seats,
+            config,
         })
     }
 
     /// Fetches the lobby game by it's id
     pub async fn fetch(
-        db: impl sqlx::Executor<'_, Database = sqlx::Sqlite>,
+        db: impl sqlx::Acquire<'_, Database = sqlx::Sqlite>,
         id: GameId,
     ) -> Result<Option<Self>> {
-        let row = sqlx::query_as("select id, created_by, player1, player2 from lobby where id = ?")
-            .bind(id)
-            .fetch_optional(db)
-            .await?;
+        let mut conn = db.acquire().await?;
+        let row: Option<(GameId, UserId, i64, Option<String>)> =
+            sqlx::query_as("select id, created_by, seats, config from lobby where id = ?")
+                .bind(id)
+                .fetch_optional(&mut *conn)
+                .await?;
+
+        let Some((id, created_by, seats, config)) = row else {
+            return Ok(None);
+        };
 
-        Ok(row.map(|(id, created_by, player1, player2)| Self {
+        Ok(Some(Self {
             id,
             created_by,
-            player1,
-            player2,
+            players: fetch_seats(&mut conn, id).await?,
+            seats: seats as usize,
+            config: match config {
+                Some(config) => serde_json::from_str(&config)?,
+                None => GameConfig::default(),
+            },
         }))
     }
 
     /// Updates the game state in DB
-    pub async fn update(&self, db: impl sqlx::Executor<'_, Database = sqlx::Sqlite>) -> Result<()> {
-        sqlx::query("update lobby set player1 = ?, player2 = ? where id = ?")
-            .bind(self.player1)
-            .bind(self.player2)
+    pub async fn update(&self, db: impl sqlx::Acquire<'_, Database = sqlx::Sqlite>) -> Result<()> {
+        let mut tx = db.begin().await?;
+
+        sqlx::query("update lobby set seats = ?, config = ? where id = ?")
+            .bind(self.seats as i64)
+            .bind(serde_json::to_string(&self.config)?)
             .bind(self.id)
-            .execute(db)
+            .execute(&mut *tx)
             .await?;
 
+        sqlx::query("delete from seats where game_id = ?")
+            .bind(self.id)
+            .execute(&mut *tx)
+            .await?;
+
+        for (seat, player) in self.players.iter().enumerate() {
+            sqlx::query("insert into seats (game_id, seat, user_id) values (?, ?, ?)")
+                .bind(self.id)
+                .bind(seat as i64)
+                .bind(player)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
         Ok(())
     }
 
-    /// Starts the game - creates an entry in `games` table and removing it from the `lobby`
-    pub async fn start(self, db: impl sqlx::Acquire<'_, Database = sqlx::Sqlite>) -> Result<Game> {
+    /// Starts the game - creates an entry in `games` table and removing it from the `lobby`.
+    ///
+    /// The game is played with the given card set, under the rules variant of the lobby game.
+    /// All the seats have to be taken. Commitment of the game's RNG seed is published right away,
+    /// the seed itself is revealed once the game is over.
+    pub async fn start(
+        self,
+        db: impl sqlx::Acquire<'_, Database = sqlx::Sqlite>,
+        card_set: &CardSet,
+    ) -> Result<Game> {
         let Self {
             id,
             created_by,
-            player1,
-            player2,
+            players,
+            seats,
+            config,
         } = self;
 
-        let player1 = player1.ok_or(Error::MissingPlayer)?;
-        let player2 = player2.ok_or(Error::MissingPlayer)?;
+        ensure!(players.len() == seats, Error::MissingPlayer);
 
-        let id = Game::start(db, id).await?;
+        let setup = Game::new_setup(card_set, players.len(), config)?;
+        let mut nonce = Nonce::default();
+        getrandom::fill(&mut nonce)?;
+        let id = Game::start_with(db, id, &setup, &nonce).await?;
 
         Ok(Game {
             id,
             created_by,
-            player1,
-            player2,
+            players,
+            seed_commitment: Some(setup.commitment(&nonce)),
+            seed_nonce: nonce,
+            setup,
+            reaction_time: DEFAULT_REACTION_TIME,
         })
     }
 }
 
+/// Fetches users seated at the lobby or ongoing game, in the seat order
+async fn fetch_seats(conn: &mut sqlx::SqliteConnection, id: GameId) -> Result<Vec<UserId>> {
+    let rows: Vec<(UserId,)> =
+        sqlx::query_as("select user_id from seats where game_id = ? order by seat")
+            .bind(id)
+            .fetch_all(conn)
+            .await?;
+
+    Ok(rows.into_iter().map(|(user,)| user).collect())
+}
+
+/// Row of the `games` table: id, creator, setup, seed commitment and its nonce
+type GameRow = (GameId, UserId, String, Option<String>, Vec<u8>);
+
 /// Ongoing game
 #[derive(Debug, Clone, PartialEq)]
 pub struct Game {
@@ -141,10 +299,16 @@ pub struct Game {
     id: GameId,
     /// User that created the game
     created_by: UserId,
-    /// Player 1 ID
-    player1: UserId,
-    /// Player 2 ID
-    player2: UserId,
+    /// Players in the seat order
+    players: Vec<UserId>,
+    /// Engine setup the game was started with
+    setup: Setup,
+    /// Commitment of the setup's seed, published when the game started
+    seed_commitment: Option<String>,
+    /// Nonce the seed was salted with in the commitment
+    seed_nonce: Nonce,
+    /// Time opponents have to react to an attack
+    reaction_time: TimeDelta,
 }
 
 impl Game {
@@ -156,12 +320,52 @@ impl Game {
         self.created_by
     }
 
-    pub fn player1(&self) -> UserId {
-        self.player1
+    /// Players in the seat order
+    pub fn players(&self) -> &[UserId] {
+        &self.players
     }
 
-    pub fn player2(&self) -> UserId {
-        self.player2
+    pub fn setup(&self) -> &Setup {
+        &self.setup
+    }
+
+    /// Sets the time opponents have to react to an attack played from now on
+    pub fn reaction_time(self, reaction_time: TimeDelta) -> Self {
+        Self {
+            reaction_time,
+            ..self
+        }
+    }
+
+    /// Commitment of the RNG seed. Not set for games started before the commitments were
+    /// published.
+    pub fn seed_commitment(&self) -> Option<&str> {
+        self.seed_commitment.as_deref()
+    }
+
+    /// Reveals the RNG seed the game was played with together with the nonce of its commitment,
+    /// once the game is over
+    pub async fn revealed_seed(
+        &self,
+        db: impl sqlx::Executor<'_, Database = sqlx::Sqlite>,
+    ) -> Result<Option<(u64, Nonce)>> {
+        let result = self.result(db).await?;
+        Ok(result.map(|_| (self.setup.seed, self.seed_nonce)))
+    }
+
+    /// Returns the seat of the user in the game
+    pub fn seat(&self, user_id: UserId) -> Option<PlayerId> {
+        self.players
+            .iter()
+            .position(|player| *player == user_id)
+            .map(PlayerId::new)
+    }
+
+    /// Engine setup for newly started games. Every game gets its own seed from the OS random
+    /// source, so it can be recreated exactly from the setup.
+    fn new_setup(card_set: &CardSet, players: usize, config: GameConfig) -> Result<Setup> {
+        let seed = getrandom::u64()?;
+        Ok(Setup::new(card_set.clone(), players, seed).config(config))
     }
 
     /// Starts a game without fetching it first from a lobby.
@@ -170,13 +374,37 @@ impl Game {
     pub async fn start(
         db: impl sqlx::Acquire<'_, Database = sqlx::Sqlite>,
         id: GameId,
+        card_set: &CardSet,
     ) -> Result<GameId> {
+        let mut conn = db.acquire().await?;
+        let lobby = LobbyGame::fetch(&mut *conn, id)
+            .await?
+            .ok_or(Error::CannotStartGame(id))?;
+        Ok(lobby.start(&mut *conn, card_set).await?.id())
+    }
+
+    /// Starts a game from the lobby with the given engine setup, committing to its seed salted
+    /// with the nonce. Seats are kept as they are, the game keeps the lobby game id.
+    async fn start_with(
+        db: impl sqlx::Acquire<'_, Database = sqlx::Sqlite>,
+        id: GameId,
+        setup: &Setup,
+        nonce: &Nonce,
+    ) -> Result<GameId> {
+        // Invalid rules variant fails here, not when the game is played
+        game::Game::new(setup.clone())?;
+
+        let commitment = setup.commitment(nonce);
+        let setup = serde_json::to_string(setup)?;
         let mut tx = db.begin().await?;
 
         let insert = sqlx::query(
-            "insert into games (id, created_by, player1, player2)\
-             select id, created_by, player1, player2 from lobby where id = ?",
+            "insert into games (id, created_by, setup, seed_commitment, seed_nonce) \
+             select id, created_by, ?, ?, ? from lobby where id = ?",
         )
+        .bind(setup)
+        .bind(commitment)
+        .bind(nonce.as_slice())
         .bind(id)
         .execute(&mut *tx)
         .await?;
@@ -195,23 +423,323 @@ impl Game {
         Ok(id)
     }
 
+    /// Fetches the game by it's id. Reaction windows opened while playing it last for the
+    /// reaction time.
     pub async fn fetch(
-        db: impl sqlx::Executor<'_, Database = sqlx::Sqlite>,
+        db: impl sqlx::Acquire<'_, Database = sqlx::Sqlite>,
         id: GameId,
+        reaction_time: TimeDelta,
     ) -> Result<Option<Self>> {
-        let game =
-            sqlx::query_as("select id, created_by, player1, player2 from games where id = ?")
+        let mut conn = db.acquire().await?;
+        let row: Option<GameRow> = sqlx::query_as(
+            "select id, created_by, setup, seed_commitment, seed_nonce from games where id = ?",
+        )
+        .bind(id)
+        .fetch_optional(&mut *conn)
+        .await?;
+
+        let Some((id, created_by, setup, seed_commitment, seed_nonce)) = row else {
+            return Ok(None);
+        };
+
+        Ok(Some(Game {
+            id,
+            created_by,
+            players: fetch_seats(&mut conn, id).await?,
+            setup: serde_json::from_str(&setup)?,
+            seed_commitment,
+            seed_nonce: Nonce::try_from(seed_nonce.as_slice())?,
+            reaction_time,
+        }))
+    }
+
+    /// Fetches the event log of the game
+    pub async fn events(
+        &self,
+        db: impl sqlx::Executor<'_, Database = sqlx::Sqlite>,
+    ) -> Result<Vec<Event>> {
+        let rows: Vec<(String,)> =
+            sqlx::query_as("select event from game_events where game_id = ? order by seq")
+                .bind(self.id)
+                .fetch_all(db)
+                .await?;
+
+        rows.into_iter()
+            .map(|(event,)| serde_json::from_str(&event).map_err(Into::into))
+            .collect()
+    }
+
+    /// Restores the engine state from the latest snapshot, folding the events recorded after it.
+    /// Reaction window with its deadline passed is closed without a reaction.
+    pub async fn state(
+        &self,
+        db: impl sqlx::Acquire<'_, Database = sqlx::Sqlite>,
+    ) -> Result<game::Game> {
+        let mut tx = db.begin().await?;
+        let (state, _) = self.settle(&mut tx, Utc::now()).await?;
+        tx.commit().await?;
+        Ok(state)
+    }
+
+    /// Rebuilds the game as seen by the user. This is the only form of the game state sent to
+    /// the clients, so no player ever learns hidden cards.
+    pub async fn view(
+        &self,
+        db: impl sqlx::Acquire<'_, Database = sqlx::Sqlite>,
+        user_id: UserId,
+    ) -> Result<CheckedView> {
+        let player = self.seat(user_id).ok_or_eyre(Error::NotAPlayer)?;
+        let view = self.state(db).await?.view(player);
+        let checksum = view.checksum();
+        Ok(CheckedView { view, checksum })
+    }
+
+    /// Retells the game as seen by the player, calling players by their nicknames. Without a player
+    /// only the public information is told. Only up to `limit` entries after the first `offset`
+    /// ones are returned, together with the number of all the entries.
+    ///
+    /// Like acting in the game, reading the log closes the reaction window if its deadline passed,
+    /// so the log tells how the window was closed.
+    pub async fn log(
+        &self,
+        db: impl sqlx::Acquire<'_, Database = sqlx::Sqlite>,
+        player: Option<PlayerId>,
+        offset: usize,
+        limit: usize,
+    ) -> Result<(usize, Vec<LogEntry>)> {
+        let mut conn = db.begin().await?;
+        self.settle(&mut conn, Utc::now()).await?;
+        let names: Vec<(String,)> = sqlx::query_as(
+            "select users.nickname from seats join users on users.id = seats.user_id \
+             where seats.game_id = ? order by seats.seat",
+        )
+        .bind(self.id)
+        .fetch_all(&mut *conn)
+        .await?;
+        let names: Vec<_> = names.into_iter().map(|(name,)| name).collect();
+
+        let events = self.events(&mut *conn).await?;
+        conn.commit().await?;
+        let log = game::Game::narrate(self.setup.clone(), &events, player, &names)?;
+        let total = log.len();
+        Ok((total, log.into_iter().skip(offset).take(limit).collect()))
+    }
+
+    /// Loads the engine state together with the length of the event log it covers.
+    ///
+    /// Snapshot which cannot be restored (eg. written by a newer server) is ignored, and the whole
+    /// log is replayed from the setup instead. The snapshot and the state after each event folded
+    /// into it are verified against the recorded checksums.
+    async fn load(&self, conn: &mut sqlx::SqliteConnection) -> Result<(game::Game, usize)> {
+        let (snapshot, seq): (Option<String>, i64) =
+            sqlx::query_as("select snapshot, snapshot_seq from games where id = ?")
+                .bind(self.id)
+                .fetch_one(&mut *conn)
+                .await?;
+
+        match snapshot.map(|snapshot| game::Game::restore(&snapshot)) {
+            Some(Ok(state)) => self.fold_log(conn, state, seq as usize).await,
+            Some(Err(err)) => {
+                warn!(game_id = ?self.id, %err, "Cannot restore the game snapshot");
+                self.replay(conn).await
+            }
+            None => self.replay(conn).await,
+        }
+    }
+
+    /// Replays the whole log from the setup, verifying the state after every event against the
+    /// recorded checksums. Unlike `load` it does not trust the snapshot, so events tampered with
+    /// before it are caught as well.
+    async fn replay(&self, conn: &mut sqlx::SqliteConnection) -> Result<(game::Game, usize)> {
+        let state = game::Game::new(self.setup.clone())?;
+        self.fold_log(conn, state, 0).await
+    }
+
+    /// Folds the events from `seq` on into the state covering the ones before, verifying the state
+    /// and the states after each of the events against the recorded checksums.
+    async fn fold_log(
+        &self,
+        conn: &mut sqlx::SqliteConnection,
+        mut state: game::Game,
+        seq: usize,
+    ) -> Result<(game::Game, usize)> {
+        if let Some(covered) = seq.checked_sub(1) {
+            let recorded: Option<(Option<String>, Option<u32>)> = sqlx::query_as(
+                "select checksum, checksum_version from game_events where game_id = ? and seq = ?",
+            )
+            .bind(self.id)
+            .bind(covered as i64)
+            .fetch_optional(&mut *conn)
+            .await?;
+            if let Some((checksum, version)) = recorded {
+                self.verify(&state.checksum(), checksum, version)?;
+            }
+        }
+
+        let rows: Vec<(String, Option<String>, Option<u32>)> = sqlx::query_as(
+            "select event, checksum, checksum_version from game_events \
+             where game_id = ? and seq >= ? order by seq",
+        )
+        .bind(self.id)
+        .bind(seq as i64)
+        .fetch_all(&mut *conn)
+        .await?;
+        let events = rows
+            .iter()
+            .map(|(event, _, _)| serde_json::from_str(event))
+            .collect::<Result<Vec<Event>, _>>()?;
+
+        let checksums = state.checksums(&events)?;
+        for ((_, recorded, version), checksum) in rows.into_iter().zip(checksums) {
+            self.verify(&checksum, recorded, version)?;
+        }
+
+        Ok((state, seq + events.len()))
+    }
+
+    /// Fails if the checksum differs from the recorded one. Checksums computed by an engine of
+    /// another version are not comparable, so they are skipped.
+    fn verify(&self, checksum: &str, recorded: Option<String>, version: Option<u32>) -> Result<()> {
+        if let (Some(recorded), Some(game::snapshot::VERSION)) = (recorded, version)
+            && recorded != checksum
+        {
+            warn!(game_id = ?self.id, "Game state does not match the recorded checksum");
+            return Err(Error::ChecksumMismatch(self.id).into());
+        }
+
+        Ok(())
+    }
+
+    /// Loads the engine state like `load`, closing the reaction window without a reaction first
+    /// if its deadline passed. Events closing the window are recorded right away.
+    async fn settle(
+        &self,
+        conn: &mut sqlx::SqliteConnection,
+        now: DateTime<Utc>,
+    ) -> Result<(game::Game, usize)> {
+        let (mut state, len) = self.load(&mut *conn).await?;
+        if Self::reaction_deadline(&mut *conn, self.id)
+            .await?
+            .is_none_or(|deadline| deadline > now)
+        {
+            return Ok((state, len));
+        }
+
+        let previous = state.clone();
+        let events = state.expire_reaction().unwrap_or_default();
+        self.record(conn, &previous, &state, len, &events, now)
+            .await?;
+        Ok((state, len + events.len()))
+    }
+
+    /// Performs the action on behalf of the user, appending resulting events to the game log and
+    /// storing the snapshot of the resulting state. Reaction window with its deadline passed is
+    /// closed without a reaction first. Once the game is over, its result is recorded.
+    ///
+    /// Returns the events of the action.
+    pub async fn apply(
+        &self,
+        db: impl sqlx::Acquire<'_, Database = sqlx::Sqlite>,
+        user_id: UserId,
+        action: Action,
+    ) -> Result<Vec<Event>> {
+        let player = self.seat(user_id).ok_or_eyre(Error::NotAPlayer)?;
+
+        let mut tx = db.begin().await?;
+        let now = Utc::now();
+        let (mut state, len) = self.settle(&mut tx, now).await?;
+
+        let previous = state.clone();
+        let events = state.apply(player, action).map_err(Error::Rejected)?;
+        self.record(&mut tx, &previous, &state, len, &events, now)
+            .await?;
+
+        tx.commit().await?;
+        Ok(events)
+    }
+
+    /// Appends the events leading from the previous state to the state to the game log, after the
+    /// first `len` ones. Every event is recorded with the checksum of the state it leads to. The
+    /// snapshot of the state is stored as well, but snapshots leave the undo checkpoint out, so
+    /// while there are actions to undo the previous snapshot is kept, and the checkpoint is rebuilt
+    /// by folding the events after it. Reaction window left open gets its deadline counted from
+    /// `now`. Before the result of the game is recorded, the whole log is replayed and verified.
+    async fn record(
+        &self,
+        conn: &mut sqlx::SqliteConnection,
+        previous: &game::Game,
+        state: &game::Game,
+        len: usize,
+        events: &[Event],
+        now: DateTime<Utc>,
+    ) -> Result<()> {
+        let checksums = previous.clone().checksums(events)?;
+        for ((seq, event), checksum) in (len..).zip(events).zip(checksums) {
+            sqlx::query(
+                "insert into game_events (game_id, seq, event, checksum, checksum_version) \
+                 values (?, ?, ?, ?, ?)",
+            )
+            .bind(self.id)
+            .bind(seq as i64)
+            .bind(serde_json::to_string(event)?)
+            .bind(checksum)
+            .bind(game::snapshot::VERSION)
+            .execute(&mut *conn)
+            .await?;
+        }
+        if previous.result().is_none() && state.result().is_some() {
+            self.replay(&mut *conn).await?;
+        }
+
+        let deadline = state
+            .pending()
+            .filter(|decision| decision.is_reaction())
+            .map(|_| now + self.reaction_time);
+        let result = state.result().map(serde_json::to_string).transpose()?;
+        let snapshot = (!state.can_undo()).then(|| (state.snapshot(), (len + events.len()) as i64));
+        let (snapshot, seq) = snapshot.unzip();
+        sqlx::query(
+            "update games set reaction_deadline = ?, snapshot = coalesce(?, snapshot), \
+             snapshot_seq = coalesce(?, snapshot_seq), result = ? where id = ?",
+        )
+        .bind(deadline)
+        .bind(snapshot)
+        .bind(seq)
+        .bind(result)
+        .bind(self.id)
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Fetches the recorded outcome of the game. `None` until the game is over.
+    pub async fn result(
+        &self,
+        db: impl sqlx::Executor<'_, Database = sqlx::Sqlite>,
+    ) -> Result<Option<GameResult>> {
+        let (result,): (Option<String>,) = sqlx::query_as("select result from games where id = ?")
+            .bind(self.id)
+            .fetch_one(db)
+            .await?;
+
+        Ok(result
+            .map(|result| serde_json::from_str(&result))
+            .transpose()?)
+    }
+
+    /// Fetches the deadline of the open reaction window
+    pub async fn reaction_deadline(
+        db: impl sqlx::Executor<'_, Database = sqlx::Sqlite>,
+        id: GameId,
+    ) -> Result<Option<DateTime<Utc>>> {
+        let (deadline,): (Option<DateTime<Utc>>,) =
+            sqlx::query_as("select reaction_deadline from games where id = ?")
                 .bind(id)
-                .fetch_optional(db)
-                .await?
-                .map(|(id, created_by, player1, player2)| Game {
-                    id,
-                    created_by,
-                    player1,
-                    player2,
-                });
+                .fetch_one(db)
+                .await?;
 
-        Ok(game)
+        Ok(deadline)
     }
 }
 
@@ -220,6 +748,7 @@ mod tests {
     use crate::model::users::User;
 
     use super::*;
+    use serde_json::json;
     use sqlx::SqlitePool;
 
     async fn setup_pool() -> SqlitePool {
@@ -236,45 +765,45 @@ mod tests {
 
         let game1 = LobbyGame::create(&pool, user).await.unwrap();
         assert_eq!(game1.created_by, user);
-        assert_eq!(game1.player1, None);
-        assert_eq!(game1.player2, None);
+        assert_eq!(game1.players, []);
+        assert_eq!(game1.seats, 2);
 
         let fetched1 = LobbyGame::fetch(&pool, game1.id).await.unwrap();
         let fetched1 = fetched1.expect("game1 should exist");
         assert_eq!(fetched1.created_by, user);
-        assert_eq!(fetched1.player1, None);
-        assert_eq!(fetched1.player2, None);
+        assert_eq!(fetched1.players, []);
+        assert_eq!(fetched1.seats, 2);
         assert_eq!(fetched1.id, game1.id);
 
         let game2 = LobbyGame::create(&pool, user).await.unwrap();
         assert_eq!(game2.created_by, user);
-        assert_eq!(game2.player1, None);
-        assert_eq!(game2.player2, None);
+        assert_eq!(game2.players, []);
+        assert_eq!(game2.seats, 2);
 
         let fetched2 = LobbyGame::fetch(&pool, game2.id).await.unwrap();
         let fetched2 = fetched2.expect("game2 should exist");
         assert_eq!(fetched2.created_by, user);
-        assert_eq!(fetched2.player1, None);
-        assert_eq!(fetched2.player2, None);
+        assert_eq!(fetched2.players, []);
+        assert_eq!(fetched2.seats, 2);
         assert_eq!(fetched2.id, game2.id);
 
         assert_ne!(game1.id, game2.id);
 
-        let (created_by, player1, player2): (UserId, Option<UserId>, Option<UserId>) =
-            sqlx::query_as("select created_by, player1, player2 from lobby where id = ?")
+        let (created_by, seats): (UserId, i64) =
+            sqlx::query_as("select created_by, seats from lobby where id = ?")
                 .bind(game1.id)
                 .fetch_one(&pool)
                 .await
                 .unwrap();
-        assert_eq!((user, None, None), (created_by, player1, player2));
+        assert_eq!((user, 2), (created_by, seats));
 
-        let (created_by, player1, player2): (UserId, Option<UserId>, Option<UserId>) =
-            sqlx::query_as("select created_by, player1, player2 from lobby where id = ?")
+        let (created_by, seats): (UserId, i64) =
+            sqlx::query_as("select created_by, seats from lobby where id = ?")
                 .bind(game2.id)
                 .fetch_one(&pool)
                 .await
                 .unwrap();
-        assert_eq!((user, None, None), (created_by, player1, player2));
+        assert_eq!((user, 2), (created_by, seats));
 
         let (count,): (i64,) = sqlx::query_as("select count(*) from lobby")
             .fetch_one(&pool)
@@ -291,16 +820,14 @@ mod tests {
         let player2 = User::new("player2").create(&pool).await.unwrap();
 
         let mut lobby_game = LobbyGame::create(&pool, player1).await.unwrap();
-        lobby_game.player1 = Some(player1);
-        lobby_game.player2 = Some(player2);
+        lobby_game.players = vec![player1, player2];
         lobby_game.update(&pool).await.unwrap();
 
         let game_id = lobby_game.id();
-        let started_game = lobby_game.start(&pool).await.unwrap();
+        let started_game = lobby_game.start(&pool, &CardSet::basic()).await.unwrap();
         assert_eq!(started_game.id(), game_id);
         assert_eq!(started_game.created_by(), player1);
-        assert_eq!(started_game.player1(), player1);
-        assert_eq!(started_game.player2(), player2);
+        assert_eq!(started_game.players(), [player1, player2]);
 
         let lobby_row: Option<(GameId,)> = sqlx::query_as("select id from lobby where id = ?")
             .bind(game_id)
@@ -309,20 +836,21 @@ mod tests {
             .unwrap();
         assert!(lobby_row.is_none());
 
-        let game_row: (GameId, UserId, UserId, UserId) =
-            sqlx::query_as("select id, created_by, player1, player2 from games where id = ?")
+        let game_row: (GameId, UserId) =
+            sqlx::query_as("select id, created_by from games where id = ?")
                 .bind(game_id)
                 .fetch_one(&pool)
                 .await
                 .unwrap();
-        assert_eq!(game_row, (game_id, player1, player1, player2));
+        assert_eq!(game_row, (game_id, player1));
 
-        let fetched_game = Game::fetch(&pool, game_id).await.unwrap();
+        let fetched_game = Game::fetch(&pool, game_id, DEFAULT_REACTION_TIME)
+            .await
+            .unwrap();
         let fetched_game = fetched_game.unwrap();
         assert_eq!(fetched_game.id(), game_id);
         assert_eq!(fetched_game.created_by(), player1);
-        assert_eq!(fetched_game.player1(), player1);
-        assert_eq!(fetched_game.player2(), player2);
+        assert_eq!(fetched_game.players(), [player1, player2]);
     }
 
     #[tokio::test]
@@ -332,13 +860,22 @@ mod tests {
         let player1 = User::new("player1").create(&pool).await.unwrap();
         let player2 = User::new("player2").create(&pool).await.unwrap();
 
-        let mut lobby_game = LobbyGame::create(&pool, player1).await.unwrap();
-        lobby_game.player1 = Some(player1);
-        lobby_game.player2 = Some(player2);
+        let config = GameConfig {
+            hand_size: 4,
+            ..GameConfig::default()
+        };
+        let mut lobby_game = LobbyGame::create_with(&pool, player1, 2, config.clone())
+            .await
+            .unwrap();
+        lobby_game.players = vec![player1, player2];
         lobby_game.update(&pool).await.unwrap();
+        let fetched = LobbyGame::fetch(&pool, lobby_game.id()).await.unwrap();
+        assert_eq!(fetched.unwrap().config, config);
 
         let game_id = lobby_game.id();
-        let started_game_id = Game::start(&pool, game_id).await.unwrap();
+        let started_game_id = Game::start(&pool, game_id, &CardSet::basic())
+            .await
+            .unwrap();
         assert_eq!(started_game_id, game_id);
 
         let lobby_row: Option<(GameId,)> = sqlx::query_as("select id from lobby where id = ?")
@@ -348,20 +885,555 @@ mod tests {
             .unwrap();
         assert!(lobby_row.is_none());
 
-        let game_row: (GameId, UserId, UserId, UserId) =
-            sqlx::query_as("select id, created_by, player1, player2 from games where id = ?")
+        let game_row: (GameId, UserId) =
+            sqlx::query_as("select id, created_by from games where id = ?")
                 .bind(game_id)
                 .fetch_one(&pool)
                 .await
                 .unwrap();
-        assert_eq!(game_row, (game_id, player1, player1, player2));
+        assert_eq!(game_row, (game_id, player1));
 
-        let fetched_game = Game::fetch(&pool, game_id).await.unwrap();
+        let fetched_game = Game::fetch(&pool, game_id, DEFAULT_REACTION_TIME)
+            .await
+            .unwrap();
         let fetched_game = fetched_game.unwrap();
         assert_eq!(fetched_game.id(), game_id);
         assert_eq!(fetched_game.created_by(), player1);
-        assert_eq!(fetched_game.player1(), player1);
-        assert_eq!(fetched_game.player2(), player2);
+        assert_eq!(fetched_game.players(), [player1, player2]);
+        assert_eq!(fetched_game.setup().config, config);
+    }
+
+    #[tokio::test]
+    async fn applying_actions_appends_events() {
+        let pool = setup_pool().await;
+
+        let player1 = User::new("player1").create(&pool).await.unwrap();
+        let player2 = User::new("player2").create(&pool).await.unwrap();
+        let outsider = User::new("outsider").create(&pool).await.unwrap();
+
+        let mut lobby_game = LobbyGame::create(&pool, player1).await.unwrap();
+        lobby_game.players = vec![player1, player2];
+        lobby_game.update(&pool).await.unwrap();
+        let game = lobby_game.start(&pool, &CardSet::basic()).await.unwrap();
+
+        let mut expected = game::Game::new(game.setup().clone()).unwrap();
+        assert_eq!(game.state(&pool).await.unwrap(), expected);
+
+        let (first, second) = match expected.active() {
+            seat if seat == PlayerId::new(0) => (player1, player2),
+            _ => (player2, player1),
+        };
+
+        assert!(game.apply(&pool, second, Action::EndTurn).await.is_err());
+        assert!(game.apply(&pool, outsider, Action::EndTurn).await.is_err());
+
+        let events = game.apply(&pool, first, Action::EndTurn).await.unwrap();
+        let active = expected.active();
+        assert_eq!(events, expected.apply(active, Action::EndTurn).unwrap());
+
+        let events = game.apply(&pool, second, Action::EndTurn).await.unwrap();
+        let active = expected.active();
+        assert_eq!(events, expected.apply(active, Action::EndTurn).unwrap());
+
+        let fetched = Game::fetch(&pool, game.id(), DEFAULT_REACTION_TIME)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(fetched.state(&pool).await.unwrap(), expected);
+        let view = fetched.view(&pool, first).await.unwrap();
+        assert_eq!(view.view, expected.view(expected.active()));
+        assert_eq!(view.checksum, expected.view(expected.active()).checksum());
+        assert!(fetched.view(&pool, outsider).await.is_err());
+
+        let (count,): (i64,) = sqlx::query_as("select count(*) from game_events where game_id = ?")
+            .bind(game.id())
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(count as usize, fetched.events(&pool).await.unwrap().len());
+    }
+
+    #[tokio::test]
+    async fn tampered_games_fail_to_load() {
+        let pool = setup_pool().await;
+
+        let player1 = User::new("player1").create(&pool).await.unwrap();
+        let player2 = User::new("player2").create(&pool).await.unwrap();
+
+        let mut lobby_game = LobbyGame::create(&pool, player1).await.unwrap();
+        lobby_game.players = vec![player1, player2];
+        lobby_game.update(&pool).await.unwrap();
+        let game = lobby_game.start(&pool, &CardSet::basic()).await.unwrap();
+
+        let previous = game.state(&pool).await.unwrap();
+        let first = game.players()[previous.active().seat()];
+        let events = game.apply(&pool, first, Action::EndTurn).await.unwrap();
+        let state = game.state(&pool).await.unwrap();
+
+        // Every event is recorded with the checksum of the state it leads to
+        let checksums: Vec<(String,)> =
+            sqlx::query_as("select checksum from game_events where game_id = ? order by seq")
+                .bind(game.id())
+                .fetch_all(&pool)
+                .await
+                .unwrap();
+        let checksums: Vec<_> = checksums.into_iter().map(|(checksum,)| checksum).collect();
+        assert_eq!(checksums, previous.clone().checksums(&events).unwrap());
+        assert_eq!(checksums.last(), Some(&state.checksum()));
+
+        // Snapshot no longer matching the log
+        let mut tampered = state.clone();
+        tampered.apply(tampered.active(), Action::EndTurn).unwrap();
+        sqlx::query("update games set snapshot = ? where id = ?")
+            .bind(tampered.snapshot())
+            .bind(game.id())
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert!(game.state(&pool).await.is_err());
+
+        // Unrestorable snapshot is replayed from the log, which still matches
+        sqlx::query("update games set snapshot = '{}' where id = ?")
+            .bind(game.id())
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert_eq!(game.state(&pool).await.unwrap(), state);
+    }
+
+    #[tokio::test]
+    async fn tampered_history_is_caught_before_the_result() {
+        let pool = setup_pool().await;
+
+        let player1 = User::new("player1").create(&pool).await.unwrap();
+        let player2 = User::new("player2").create(&pool).await.unwrap();
+
+        let mut lobby_game = LobbyGame::create(&pool, player1).await.unwrap();
+        lobby_game.players = vec![player1, player2];
+        lobby_game.update(&pool).await.unwrap();
+        let game = lobby_game.start(&pool, &CardSet::basic()).await.unwrap();
+
+        let state = game.state(&pool).await.unwrap();
+        let first = game.players()[state.active().seat()];
+        game.apply(&pool, first, Action::EndTurn).await.unwrap();
+        let state = game.state(&pool).await.unwrap();
+
+        // The first event is covered by the snapshot, so loading the game does not notice
+        let events = game.events(&pool).await.unwrap();
+        sqlx::query("update game_events set event = ? where game_id = ? and seq = 0")
+            .bind(serde_json::to_string(events.last().unwrap()).unwrap())
+            .bind(game.id())
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert_eq!(game.state(&pool).await.unwrap(), state);
+
+        let mut conn = pool.acquire().await.unwrap();
+        assert!(game.replay(&mut conn).await.is_err());
+        drop(conn);
+
+        // Replaying the whole log keeps the game from ending
+        assert!(game.apply(&pool, first, Action::Concede).await.is_err());
+        assert_eq!(game.result(&pool).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn seed_is_revealed_after_the_game() {
+        let pool = setup_pool().await;
+
+        let player1 = User::new("player1").create(&pool).await.unwrap();
+        let player2 = User::new("player2").create(&pool).await.unwrap();
+
+        let mut lobby_game = LobbyGame::create(&pool, player1).await.unwrap();
+        lobby_game.players = vec![player1, player2];
+        lobby_game.update(&pool).await.unwrap();
+        let game = lobby_game.start(&pool, &CardSet::basic()).await.unwrap();
+        let game = Game::fetch(&pool, game.id(), DEFAULT_REACTION_TIME)
+            .await
+            .unwrap()
+            .unwrap();
+        let commitment = game.seed_commitment().unwrap().to_owned();
+
+        let state = game.state(&pool).await.unwrap();
+        let first = game.players()[state.active().seat()];
+        game.apply(&pool, first, Action::EndTurn).await.unwrap();
+        assert_eq!(game.revealed_seed(&pool).await.unwrap(), None);
+
+        game.apply(&pool, player1, Action::Concede).await.unwrap();
+        let (seed, nonce) = game.revealed_seed(&pool).await.unwrap().unwrap();
+        assert_ne!(nonce, Nonce::default());
+
+        let setup = Setup::new(CardSet::basic(), 2, seed);
+        let events = game.events(&pool).await.unwrap();
+        let verified = game::Game::verify(setup, &nonce, &commitment, &events).unwrap();
+        assert_eq!(verified, game.state(&pool).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn reaction_windows_expire() {
+        let pool = setup_pool().await;
+
+        let player1 = User::new("player1").create(&pool).await.unwrap();
+        let player2 = User::new("player2").create(&pool).await.unwrap();
+
+        // Every card is both an attack and a reaction to it
+        let card_set = CardSet::new([game::CardDef::new("Ambusher", 3)
+            .types([game::CardType::Attack, game::CardType::Reaction])
+            .starter(10)
+            .effect(game::Effect::ForceDiscard(game::Selection::Top(1)))
+            .reaction(game::Effect::Block)]);
+
+        let mut lobby_game = LobbyGame::create(&pool, player1).await.unwrap();
+        lobby_game.players = vec![player1, player2];
+        lobby_game.update(&pool).await.unwrap();
+        let game = lobby_game.start(&pool, &card_set).await.unwrap();
+        let game = game.reaction_time(TimeDelta::minutes(5));
+
+        let state = game.state(&pool).await.unwrap();
+        let first = match state.active() {
+            seat if seat == PlayerId::new(0) => player1,
+            _ => player2,
+        };
+        let card = state.player(state.active()).hand()[0];
+
+        game.apply(&pool, first, Action::PlayCard(card))
+            .await
+            .unwrap();
+        let deadline = Game::reaction_deadline(&pool, game.id())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(deadline > Utc::now());
+        assert!(deadline <= Utc::now() + TimeDelta::minutes(5));
+
+        // Active player cannot move on until the window closes
+        assert!(game.apply(&pool, first, Action::EndTurn).await.is_err());
+
+        sqlx::query("update games set reaction_deadline = ? where id = ?")
+            .bind(Utc::now() - TimeDelta::seconds(1))
+            .bind(game.id())
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        // Window is closed once the game is loaded, before any other action comes in
+        let len = game.events(&pool).await.unwrap().len();
+        let state = game.state(&pool).await.unwrap();
+        assert_eq!(state.pending(), None);
+        let events = game.events(&pool).await.unwrap();
+        assert!(matches!(
+            events[len..],
+            [
+                Event::DecisionResolved { .. },
+                Event::CardDiscarded { .. },
+                ..
+            ]
+        ));
+        assert_eq!(
+            Game::reaction_deadline(&pool, game.id()).await.unwrap(),
+            None
+        );
+
+        game.apply(&pool, first, Action::EndTurn).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn reopened_reaction_windows_last_the_reaction_time() {
+        let pool = setup_pool().await;
+
+        let mut users = vec![];
+        for idx in 0..3 {
+            let user = User::new(format!("player{idx}"))
+                .create(&pool)
+                .await
+                .unwrap();
+            users.push(user);
+        }
+
+        // Every card is both an attack and a reaction to it
+        let card_set = CardSet::new([game::CardDef::new("Ambusher", 3)
+            .types([game::CardType::Attack, game::CardType::Reaction])
+            .starter(10)
+            .effect(game::Effect::ForceDiscard(game::Selection::Top(1)))
+            .reaction(game::Effect::Block)]);
+
+        let mut lobby_game = LobbyGame::create_with(&pool, users[0], 3, GameConfig::default())
+            .await
+            .unwrap();
+        lobby_game.players = users.clone();
+        lobby_game.update(&pool).await.unwrap();
+        let id = lobby_game.start(&pool, &card_set).await.unwrap().id();
+        let game = Game::fetch(&pool, id, TimeDelta::minutes(5))
+            .await
+            .unwrap()
+            .unwrap();
+
+        let state = game.state(&pool).await.unwrap();
+        let active = state.active();
+        let card = state.player(active).hand()[0];
+        game.apply(&pool, users[active.seat()], Action::PlayCard(card))
+            .await
+            .unwrap();
+        let first = game.state(&pool).await.unwrap().pending().unwrap().player();
+
+        sqlx::query("update games set reaction_deadline = ? where id = ?")
+            .bind(Utc::now() - TimeDelta::seconds(1))
+            .bind(id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        // Viewing the game closes the expired window and opens the next opponent's one
+        let view = game.view(&pool, users[active.seat()]).await.unwrap().view;
+        let pending = view.pending.unwrap();
+        assert!(pending.reaction);
+        assert_ne!(pending.player, first);
+        let deadline = Game::reaction_deadline(&pool, id).await.unwrap().unwrap();
+        assert!(deadline > Utc::now() + TimeDelta::minutes(4));
+        assert!(deadline <= Utc::now() + TimeDelta::minutes(5));
+    }
+
+    #[tokio::test]
+    async fn games_are_restored_from_snapshots() {
+        let pool = setup_pool().await;
+
+        let player1 = User::new("player1").create(&pool).await.unwrap();
+        let player2 = User::new("player2").create(&pool).await.unwrap();
+
+        let mut lobby_game = LobbyGame::create(&pool, player1).await.unwrap();
+        lobby_game.players = vec![player1, player2];
+        lobby_game.update(&pool).await.unwrap();
+        let game = lobby_game.start(&pool, &CardSet::basic()).await.unwrap();
+
+        let mut expected = game::Game::new(game.setup().clone()).unwrap();
+        let (first, second) = match expected.active() {
+            seat if seat == PlayerId::new(0) => (player1, player2),
+            _ => (player2, player1),
+        };
+        for user in [first, second] {
+            game.apply(&pool, user, Action::EndTurn).await.unwrap();
+            let active = expected.active();
+            expected.apply(active, Action::EndTurn).unwrap();
+        }
+
+        let (snapshot, seq): (Option<String>, i64) =
+            sqlx::query_as("select snapshot, snapshot_seq from games where id = ?")
+                .bind(game.id())
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(seq as usize, game.events(&pool).await.unwrap().len());
+        assert_eq!(
+            game::Game::restore(&snapshot.unwrap()),
+            Ok(expected.clone())
+        );
+
+        // Events covered by the snapshot are not needed to restore the game
+        sqlx::query("delete from game_events where game_id = ?")
+            .bind(game.id())
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert_eq!(game.state(&pool).await.unwrap(), expected);
+
+        let events = game.apply(&pool, first, Action::EndTurn).await.unwrap();
+        let active = expected.active();
+        assert_eq!(events, expected.apply(active, Action::EndTurn).unwrap());
+        assert_eq!(game.state(&pool).await.unwrap(), expected);
+
+        // Snapshot of an unknown version is ignored in favour of replaying the log
+        let mut lobby_game = LobbyGame::create(&pool, player1).await.unwrap();
+        lobby_game.players = vec![player1, player2];
+        lobby_game.update(&pool).await.unwrap();
+        let game = lobby_game.start(&pool, &CardSet::basic()).await.unwrap();
+        let expected = game::Game::new(game.setup().clone()).unwrap();
+
+        sqlx::query("update games set snapshot = ?, snapshot_seq = 0 where id = ?")
+            .bind(r#"{"version": 999, "game": {}}"#)
+            .bind(game.id())
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert_eq!(game.state(&pool).await.unwrap(), expected);
+    }
+
+    #[tokio::test]
+    async fn finished_games_record_results() {
+        let pool = setup_pool().await;
+
+        let player1 = User::new("player1").create(&pool).await.unwrap();
+        let player2 = User::new("player2").create(&pool).await.unwrap();
+
+        let mut lobby_game = LobbyGame::create(&pool, player1).await.unwrap();
+        lobby_game.players = vec![player1, player2];
+        lobby_game.update(&pool).await.unwrap();
+        let game = lobby_game.start(&pool, &CardSet::basic()).await.unwrap();
+
+        assert_eq!(game.result(&pool).await.unwrap(), None);
+
+        game.apply(&pool, player2, Action::Concede).await.unwrap();
+        let result = game.result(&pool).await.unwrap().unwrap();
+        assert_eq!(result.winner, Some(PlayerId::new(0)));
+        assert_eq!(result.reason, game::EndReason::Concession);
+        assert_eq!(game.state(&pool).await.unwrap().result(), Some(&result));
+
+        let err = game
+            .apply(&pool, player1, Action::EndTurn)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(Error::Rejected(Rejection::GameOver))
+        ));
+    }
+
+    #[tokio::test]
+    async fn assigning_teams() {
+        let pool = setup_pool().await;
+        let user = User::new("user1").create(&pool).await.unwrap();
+        let config = GameConfig {
+            teams: Some(game::Teams::default()),
+            ..GameConfig::default()
+        };
+        let mut game = LobbyGame::create_with(&pool, user, 3, config)
+            .await
+            .unwrap();
+
+        assert!(matches!(game.assign_team(0, 3), Err(Error::InvalidTeam(3))));
+        assert!(matches!(game.assign_team(1, 0), Err(Error::EmptySeat(1))));
+        game.assign_team(0, 0).unwrap();
+        game.assign_team(1, 0).unwrap();
+        assert!(matches!(game.assign_team(2, 0), Err(Error::SingleTeam)));
+        game.assign_team(2, 2).unwrap();
+        game.assign_team(1, 2).unwrap();
+        assert!(matches!(game.assign_team(3, 1), Err(Error::EmptySeat(3))));
+        assert_eq!(game.teams(), Some(&[0, 2, 2][..]));
+
+        let mut solo = LobbyGame::create(&pool, user).await.unwrap();
+        assert!(matches!(solo.assign_team(0, 0), Err(Error::NoTeams)));
+    }
+
+    #[tokio::test]
+    async fn playing_with_more_seats() {
+        let pool = setup_pool().await;
+
+        let mut users = vec![];
+        for idx in 0..4 {
+            let user = User::new(format!("player{idx}"))
+                .create(&pool)
+                .await
+                .unwrap();
+            users.push(user);
+        }
+
+        assert!(
+            LobbyGame::create_with(&pool, users[0], 7, GameConfig::default())
+                .await
+                .is_err()
+        );
+        let huge_deck = GameConfig {
+            starting_deck: Some([("Copper".to_owned(), u32::MAX)].into()),
+            ..GameConfig::default()
+        };
+        assert!(
+            LobbyGame::create_with(&pool, users[0], 2, huge_deck)
+                .await
+                .is_err()
+        );
+        let huge_hand = GameConfig {
+            hand_size: 1 << 40,
+            ..GameConfig::default()
+        };
+        assert!(
+            LobbyGame::create_with(&pool, users[0], 2, huge_hand)
+                .await
+                .is_err()
+        );
+
+        let mut lobby_game = LobbyGame::create_with(&pool, users[0], 4, GameConfig::default())
+            .await
+            .unwrap();
+        lobby_game.players = users[..3].to_vec();
+        lobby_game.update(&pool).await.unwrap();
+
+        let fetched = LobbyGame::fetch(&pool, lobby_game.id())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(fetched.players, users[..3]);
+        assert!(!fetched.is_full());
+        assert!(fetched.start(&pool, &CardSet::basic()).await.is_err());
+
+        lobby_game.players = users.clone();
+        lobby_game.update(&pool).await.unwrap();
+        let game = lobby_game.start(&pool, &CardSet::basic()).await.unwrap();
+
+        let fetched = Game::fetch(&pool, game.id(), DEFAULT_REACTION_TIME)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(fetched.players(), users);
+        assert_eq!(fetched.seat(users[3]), Some(PlayerId::new(3)));
+
+        let state = fetched.state(&pool).await.unwrap();
+        assert_eq!(state.players().len(), 4);
+        let active = users[state.active().seat()];
+        game.apply(&pool, active, Action::EndTurn).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn two_player_rows_are_migrated_to_seats() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        let mut migrator = sqlx::migrate!("model/migrations");
+        let all = migrator.migrations.clone();
+        migrator.migrations = all.iter().filter(|m| m.version < 12).cloned().collect();
+        migrator.run(&pool).await.unwrap();
+
+        let users: Vec<UserId> = [Uuid::new_v4(), Uuid::new_v4()]
+            .into_iter()
+            .map(|id| serde_json::from_value(json!(id)).unwrap())
+            .collect();
+        for user in &users {
+            sqlx::query("insert into users (id, nickname) values (?, 'player')")
+                .bind(user)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+
+        let (lobby, game) = (GameId(Uuid::new_v4()), GameId(Uuid::new_v4()));
+        sqlx::query("insert into lobby (id, created_by, player1) values (?, ?, ?)")
+            .bind(lobby)
+            .bind(users[0])
+            .bind(users[1])
+            .execute(&pool)
+            .await
+            .unwrap();
+        let setup = serde_json::to_string(&Setup::new(CardSet::basic(), 2, 1)).unwrap();
+        sqlx::query(
+            "insert into games (id, created_by, player1, player2, setup) values (?, ?, ?, ?, ?)",
+        )
+        .bind(game)
+        .bind(users[0])
+        .bind(users[1])
+        .bind(users[0])
+        .bind(setup)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        migrator.migrations = all;
+        migrator.run(&pool).await.unwrap();
+
+        let lobby = LobbyGame::fetch(&pool, lobby).await.unwrap().unwrap();
+        assert_eq!(lobby.players, users[1..]);
+        assert_eq!(lobby.seats, 2);
+
+        let game = Game::fetch(&pool, game, DEFAULT_REACTION_TIME)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(game.players(), [users[1], users[0]]);
     }
 
     #[tokio::test]
@@ -373,7 +1445,9 @@ mod tests {
         let lobby_game = LobbyGame::fetch(&pool, game_id).await.unwrap();
         assert!(lobby_game.is_none());
 
-        let game = Game::fetch(&pool, game_id).await.unwrap();
+        let game = Game::fetch(&pool, game_id, DEFAULT_REACTION_TIME)
+            .await
+            .unwrap();
         assert!(game.is_none());
     }
 }
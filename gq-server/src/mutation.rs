@@ -3,6 +3,7 @@
 use async_graphql::Object;
 use derivative::Derivative;
 
+mod game;
 mod lobby;
 mod users;
 
@@ -11,6 +12,7 @@ mod users;
 pub struct Mutation {
     users: users::UsersMutations,
     lobby: lobby::LobbyMutations,
+    game: game::GameMutations,
 }
 
 #[Object]
@@ -22,4 +24,8 @@ impl Mutation {
     async fn lobby(&self) -> &lobby::LobbyMutations {
         &self.lobby
     }
+
+    async fn game(&self) -> &game::GameMutations {
+        &self.game
+    }
 }
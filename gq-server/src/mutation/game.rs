@@ -0,0 +1,57 @@
+//! Ongoing game mutations
+
+use async_graphql::{Context, Json, Object, Result};
+use game::Action;
+use tracing::{info, instrument};
+
+use crate::model::Model;
+use crate::model::auth::Session;
+use crate::model::game::{CheckedView, Game, GameId};
+
+#[derive(Debug, Default)]
+pub struct GameMutations;
+
+#[Object]
+impl GameMutations {
+    /// Performs an action in the game on behalf of the current user. Returns the game as seen by
+    /// the user after the action.
+    #[instrument(skip(self, ctx))]
+    pub async fn apply(
+        &self,
+        ctx: &Context<'_>,
+        game_id: GameId,
+        action: Json<Action>,
+    ) -> Result<Json<CheckedView>> {
+        let session: &Session = ctx.data_opt().ok_or("Unauthorized")?;
+        let model: &Model = ctx.data()?;
+        let db = model.db();
+        let game = Game::fetch(db, game_id, model.reaction_time())
+            .await?
+            .ok_or("Game not found")?;
+
+        let events = game.apply(db, session.user_id, action.0).await?;
+        info!(?game_id, events = events.len(), "Applied action");
+
+        let view = game.view(db, session.user_id).await?;
+        Ok(Json(view))
+    }
+
+    /// Takes back the current user's actions since the start of their turn or its last
+    /// irreversible action. Only the player whose turn it is can undo. Returns the game as seen
+    /// by the user afterwards.
+    #[instrument(skip(self, ctx))]
+    pub async fn undo(&self, ctx: &Context<'_>, game_id: GameId) -> Result<Json<CheckedView>> {
+        let session: &Session = ctx.data_opt().ok_or("Unauthorized")?;
+        let model: &Model = ctx.data()?;
+        let db = model.db();
+        let game = Game::fetch(db, game_id, model.reaction_time())
+            .await?
+            .ok_or("Game not found")?;
+
+        game.apply(db, session.user_id, Action::Undo).await?;
+        info!(?game_id, "Undone actions");
+
+        let view = game.view(db, session.user_id).await?;
+        Ok(Json(view))
+    }
+}
@@ -1,6 +1,7 @@
 //! Lobby related mutations
 
-use async_graphql::{Context, Object, Result};
+use async_graphql::{Context, Json, Object, Result};
+use game::GameConfig;
 use tracing::{info, instrument};
 
 use crate::model::Model;
@@ -14,23 +15,37 @@ pub struct LobbyMutations;
 impl LobbyMutations {
     /// Creates a new game in the lobby. Returns created game id. Game id should be passed to players
     /// so they can join the game.
+    ///
+    /// The game is played by two players with the standard rules, unless the number of seats or
+    /// a rules variant is given.
     #[instrument(skip(self, ctx))]
-    pub async fn create_game(&self, ctx: &Context<'_>) -> Result<GameId> {
+    pub async fn create_game(
+        &self,
+        ctx: &Context<'_>,
+        seats: Option<usize>,
+        config: Option<Json<GameConfig>>,
+    ) -> Result<GameId> {
         let session: &Session = ctx.data_opt().ok_or("Unauthorized")?;
         let model: &Model = ctx.data()?;
         let db = model.db();
 
-        let game = LobbyGame::create(db, session.user_id).await?;
+        let config = config.map(|config| config.0).unwrap_or_default();
+        let game = LobbyGame::create_with(db, session.user_id, seats.unwrap_or(2), config).await?;
         info!(?game, "Created game in the lobby");
 
         Ok(game.id())
     }
 
-    /// Takes a seat in the lobby game.
+    /// Takes a seat in the lobby game. Team has to be chosen if the game is played in teams.
     ///
     /// Game id is returned as a result.
     #[instrument(skip(self, ctx))]
-    pub async fn join_game(&self, ctx: &Context<'_>, game_id: GameId) -> Result<GameId> {
+    pub async fn join_game(
+        &self,
+        ctx: &Context<'_>,
+        game_id: GameId,
+        team: Option<usize>,
+    ) -> Result<GameId> {
         let session: &Session = ctx.data_opt().ok_or("Unauthorized")?;
         let model: &Model = ctx.data()?;
         let db = model.db();
@@ -38,25 +53,60 @@ impl LobbyMutations {
             .await?
             .ok_or("Game not found")?;
 
-        if game.player1.is_none() {
-            game.player1 = Some(session.user_id);
-        } else if game.player2.is_none() {
-            game.player2 = Some(session.user_id);
-        } else {
+        if game.players.contains(&session.user_id) {
+            return Err("Already seated at the game".into());
+        }
+        if game.is_full() {
             return Err("Game is full".into());
         }
 
+        match (game.config.teams.is_some(), team) {
+            (true, Some(team)) => game.assign_team(game.players.len(), team)?,
+            (true, None) => return Err("Team has to be chosen to join the game".into()),
+            (false, Some(_)) => return Err("Game is not played in teams".into()),
+            (false, None) => (),
+        }
+        game.players.push(session.user_id);
         game.update(db).await?;
 
         info!(?game, "Joined game in the lobby");
 
-        if game.player1.is_some() && game.player2.is_some() {
+        if game.is_full() {
             info!(?game, "Game is ready to start");
         }
 
         Ok(game.id())
     }
 
+    /// Moves the seated player to another team of the lobby game.
+    ///
+    /// Game id is returned as a result.
+    #[instrument(skip(self, ctx))]
+    pub async fn choose_team(
+        &self,
+        ctx: &Context<'_>,
+        game_id: GameId,
+        team: usize,
+    ) -> Result<GameId> {
+        let session: &Session = ctx.data_opt().ok_or("Unauthorized")?;
+        let model: &Model = ctx.data()?;
+        let db = model.db();
+        let mut game = LobbyGame::fetch(db, game_id)
+            .await?
+            .ok_or("Game not found")?;
+
+        let seat = game
+            .players
+            .iter()
+            .position(|player| *player == session.user_id)
+            .ok_or("Not seated at the game")?;
+        game.assign_team(seat, team)?;
+        game.update(db).await?;
+
+        info!(?game, "Chose team in the lobby");
+        Ok(game.id())
+    }
+
     #[instrument(skip(self, ctx))]
     pub async fn start_game(&self, ctx: &Context<'_>, game_id: GameId) -> Result<GameId> {
         let session: &Session = ctx.data_opt().ok_or("Unauthorized")?;
@@ -70,7 +120,7 @@ impl LobbyMutations {
             return Err("Only players involved in the game can start it".into());
         }
 
-        let id = game.start(db).await?.id();
+        let id = game.start(db, model.card_set()).await?.id();
 
         info!(?game_id, "Started game");
         Ok(id)
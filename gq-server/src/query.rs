@@ -1,18 +1,85 @@
 //! Main query entry point
 
-use async_graphql::{Context, Object, Result, SimpleObject};
+use async_graphql::{ComplexObject, Context, Json, Object, Result, SimpleObject};
+use chrono::{DateTime, Utc};
+use game::LogEntry;
 
 use crate::model::Model;
-use crate::model::game::{Game, GameId, LobbyGame};
+use crate::model::auth::Session;
+use crate::model::game::{CheckedView, Game, GameId, LobbyGame};
 use crate::model::users::{User, UserId};
 
 #[derive(Debug, Default)]
 pub struct Query;
 
+/// Most log entries returned at once
+pub(crate) const MAX_LOG_PAGE: usize = 200;
+
 #[derive(Debug, Clone, SimpleObject)]
+#[graphql(complex)]
 pub struct GameInfo {
+    #[graphql(skip)]
+    pub id: GameId,
     pub created_by: UserId,
+    /// Players in the seat order
     pub players: Vec<UserId>,
+    /// Number of players the game is played by
+    pub seats: usize,
+    /// Team of every seated player, in the seat order. Not set if the game is not played in
+    /// teams.
+    pub teams: Option<Vec<usize>>,
+    /// Hash commitment of the game's RNG seed, published when the game starts
+    pub seed_commitment: Option<String>,
+    /// RNG seed of the game, revealed once the game is over. Decimal encoded, as it does not fit
+    /// the GraphQL `Int`.
+    pub seed: Option<String>,
+    /// Nonce the seed was salted with in the commitment, hex encoded. Revealed together with the
+    /// seed.
+    pub seed_nonce: Option<String>,
+    /// Time until which the reacting player can respond to an attack, while a reaction window is
+    /// open. Once it passes, the window is closed without a reaction.
+    pub reaction_deadline: Option<DateTime<Utc>>,
+}
+
+/// Page of the game log
+#[derive(Debug, Clone, SimpleObject)]
+pub struct GameLog {
+    /// Number of all the entries in the log
+    pub total: usize,
+    /// Entries of the page, the oldest first
+    pub entries: Json<Vec<LogEntry>>,
+}
+
+#[ComplexObject]
+impl GameInfo {
+    /// Readable log of the game as seen by the current user, paged from the oldest entry. Users
+    /// not playing the game see only the public information. Not set until the game starts.
+    /// Reading the log closes the reaction window whose deadline passed.
+    async fn log<'c>(
+        &self,
+        ctx: &Context<'c>,
+        #[graphql(default)] offset: usize,
+        #[graphql(default = 50)] limit: usize,
+    ) -> Result<Option<GameLog>> {
+        let model: &Model = ctx.data()?;
+        let db = model.db();
+
+        let Some(game) = Game::fetch(db, self.id, model.reaction_time()).await? else {
+            return Ok(None);
+        };
+
+        let viewer = ctx
+            .data_opt::<Session>()
+            .and_then(|session| game.seat(session.user_id));
+        let (total, entries) = game
+            .log(db, viewer, offset, limit.min(MAX_LOG_PAGE))
+            .await?;
+
+        Ok(Some(GameLog {
+            total,
+            entries: Json(entries),
+        }))
+    }
 }
 
 #[Object]
@@ -33,11 +100,15 @@ impl Query {
 
         let game = LobbyGame::fetch(db, id).await?;
         let info = game.map(|game| GameInfo {
+            id: game.id(),
             created_by: game.created_by(),
-            players: [game.player1.into_iter(), game.player2.into_iter()]
-                .into_iter()
-                .flatten()
-                .collect(),
+            seats: game.seats,
+            teams: game.teams().map(<[_]>::to_vec),
+            players: game.players,
+            seed_commitment: None,
+            seed: None,
+            seed_nonce: None,
+            reaction_deadline: None,
         });
 
         Ok(info)
@@ -48,12 +119,47 @@ impl Query {
         let model: &Model = ctx.data()?;
         let db = model.db();
 
-        let game = Game::fetch(db, id).await?;
-        let info = game.map(|game| GameInfo {
+        let Some(game) = Game::fetch(db, id, model.reaction_time()).await? else {
+            return Ok(None);
+        };
+
+        let (seed, nonce) = game.revealed_seed(db).await?.unzip();
+        let reaction_deadline = Game::reaction_deadline(db, id)
+            .await?
+            .filter(|deadline| *deadline > Utc::now());
+        Ok(Some(GameInfo {
+            id: game.id(),
             created_by: game.created_by(),
-            players: vec![game.player1(), game.player2()],
-        });
+            seats: game.players().len(),
+            teams: game
+                .setup()
+                .config
+                .teams
+                .as_ref()
+                .map(|teams| teams.seats.clone()),
+            players: game.players().to_vec(),
+            seed_commitment: game.seed_commitment().map(str::to_owned),
+            seed: seed.map(|seed| seed.to_string()),
+            seed_nonce: nonce.map(|nonce| nonce.iter().map(|byte| format!("{byte:02x}")).collect()),
+            reaction_deadline,
+        }))
+    }
 
-        Ok(info)
+    /// Gets the game in progress as seen by the current user, with the checksum of the view
+    pub async fn view<'c>(
+        &self,
+        ctx: &Context<'c>,
+        id: GameId,
+    ) -> Result<Option<Json<CheckedView>>> {
+        let session: &Session = ctx.data_opt().ok_or("Unauthorized")?;
+        let model: &Model = ctx.data()?;
+        let db = model.db();
+
+        let Some(game) = Game::fetch(db, id, model.reaction_time()).await? else {
+            return Ok(None);
+        };
+
+        let view = game.view(db, session.user_id).await?;
+        Ok(Some(Json(view)))
     }
 }
@@ -10,6 +10,7 @@ use serde::Deserialize;
 use serde::de::DeserializeOwned;
 use serde_json::{Value, from_value, json};
 
+mod game;
 mod lobby;
 mod users;
 
@@ -0,0 +1,512 @@
+//! Ongoing game API tests
+
+use actix_http::Request;
+use actix_web::body::MessageBody;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::{App, test};
+use game::{Action, LogEntry, PlayerView};
+use serde_json::json;
+
+use crate::model::Model;
+use crate::query::MAX_LOG_PAGE;
+use crate::service;
+use crate::service::tests::{GraphQLResp, gql};
+
+/// Player taking part in a test game
+struct TestPlayer {
+    token: String,
+}
+
+/// Creates two players and starts a game between them with the rules variant, `null` for the
+/// standard rules. Returns the game id and players in seat order.
+async fn start_game<S, B>(app: &S, config: serde_json::Value) -> (String, [TestPlayer; 2])
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody,
+{
+    let resp = gql(r#"mutation($name1: String!, $name2: String!) {
+                users {
+                    u1: createAdhoc(nickname: $name1) {
+                        token
+                        user
+                    },
+                    u2: createAdhoc(nickname: $name2) {
+                        token
+                        user
+                    }
+                }
+            }"#)
+    .variables(json!({ "name1": "player1", "name2": "player2" }))
+    .call(app)
+    .await
+    .unwrap();
+
+    assert_eq!(resp.errors, None);
+    let player1 = TestPlayer {
+        token: resp.data("users.u1.token").unwrap(),
+    };
+    let player2 = TestPlayer {
+        token: resp.data("users.u2.token").unwrap(),
+    };
+
+    let resp = gql(r#"mutation($config: JSON) {
+            lobby {
+                createGame(config: $config)
+            }
+        }"#)
+    .variables(json!({ "config": config }))
+    .adhoc(&player1.token)
+    .call(app)
+    .await
+    .unwrap();
+
+    assert_eq!(resp.errors, None);
+    let game_id: String = resp.data("lobby.createGame").unwrap();
+
+    for player in [&player1, &player2] {
+        let resp = gql(r#"mutation($id: GameId!) {
+                lobby {
+                    joinGame(gameId: $id)
+                }
+            }"#)
+        .variables(json!({ "id": game_id }))
+        .adhoc(&player.token)
+        .call(app)
+        .await
+        .unwrap();
+
+        assert_eq!(resp.errors, None);
+    }
+
+    let resp = gql(r#"mutation($id: GameId!) {
+            lobby {
+                startGame(gameId: $id)
+            }
+        }"#)
+    .variables(json!({ "id": game_id }))
+    .adhoc(&player1.token)
+    .call(app)
+    .await
+    .unwrap();
+
+    assert_eq!(resp.errors, None);
+
+    (game_id, [player1, player2])
+}
+
+/// Ends the current turn on behalf of the player
+async fn end_turn<S, B>(app: &S, game_id: &str, player: &TestPlayer) -> GraphQLResp
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody,
+{
+    gql(r#"mutation($id: GameId!, $action: JSON!) {
+            game {
+                apply(gameId: $id, action: $action)
+            }
+        }"#)
+    .variables(json!({ "id": game_id, "action": "EndTurn" }))
+    .adhoc(&player.token)
+    .call(app)
+    .await
+    .unwrap()
+}
+
+#[actix_web::test]
+async fn playing_started_game() {
+    let context = Model::test().await.unwrap();
+    let service_config = service::configure(false, context).await.unwrap();
+    let app = App::new().configure(service_config);
+    let app = test::init_service(app).await;
+
+    let (game_id, [player1, player2]) = start_game(&app, json!(null)).await;
+
+    // First player is chosen at random - only one of the players can end the first turn
+    let (first, second) = match end_turn(&app, &game_id, &player1).await.errors {
+        None => (player1, player2),
+        Some(_) => {
+            let resp = end_turn(&app, &game_id, &player2).await;
+            assert_eq!(resp.errors, None);
+            (player2, player1)
+        }
+    };
+
+    let resp = end_turn(&app, &game_id, &first).await;
+    assert!(resp.errors.is_some());
+
+    let resp = end_turn(&app, &game_id, &second).await;
+    assert_eq!(resp.errors, None);
+    let view: PlayerView = resp.data("game.apply").unwrap();
+    let checksum: String = resp.data("game.apply.checksum").unwrap();
+    assert_eq!(view.turn, 3);
+    assert_eq!(checksum, view.checksum());
+    assert_eq!(view.players[view.active.seat()].hand, None);
+    assert_eq!(view.legal_actions.actions, [Action::Concede]);
+
+    // Seed is committed to, but kept secret while the game is played
+    let resp = gql(r#"query($id: GameId!) {
+            game(id: $id) {
+                seedCommitment
+                seed
+                seedNonce
+                reactionDeadline
+            }
+        }"#)
+    .variables(json!({ "id": game_id }))
+    .call(&app)
+    .await
+    .unwrap();
+    assert_eq!(resp.errors, None);
+    let commitment: String = resp.data("game.seedCommitment").unwrap();
+    let seed: Option<String> = resp.data("game.seed").unwrap();
+    let nonce: Option<String> = resp.data("game.seedNonce").unwrap();
+    assert_eq!(commitment.len(), 64);
+    assert_eq!(seed, None);
+    assert_eq!(nonce, None);
+
+    // No attack waits for a reaction
+    let deadline: Option<String> = resp.data("game.reactionDeadline").unwrap();
+    assert_eq!(deadline, None);
+}
+
+/// Queries the game as seen by the player
+async fn view<S, B>(app: &S, game_id: &str, player: &TestPlayer) -> GraphQLResp
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody,
+{
+    gql(r#"query($id: GameId!) {
+            view(id: $id)
+        }"#)
+    .variables(json!({ "id": game_id }))
+    .adhoc(&player.token)
+    .call(app)
+    .await
+    .unwrap()
+}
+
+#[actix_web::test]
+async fn players_see_only_their_hands() {
+    let context = Model::test().await.unwrap();
+    let service_config = service::configure(false, context).await.unwrap();
+    let app = App::new().configure(service_config);
+    let app = test::init_service(app).await;
+
+    let (game_id, players) = start_game(&app, json!(null)).await;
+
+    for (seat, player) in players.iter().enumerate() {
+        let resp = view(&app, &game_id, player).await;
+        assert_eq!(resp.errors, None);
+
+        let view: PlayerView = resp.data("view").unwrap();
+        assert_eq!(view.viewer.seat(), seat);
+        for other in &view.players {
+            assert_eq!(other.hand.is_some(), other.player == view.viewer);
+            assert_eq!(other.hand_size, 5);
+        }
+    }
+
+    let resp = gql(r#"query($id: GameId!) {
+            view(id: $id)
+        }"#)
+    .variables(json!({ "id": game_id }))
+    .call(&app)
+    .await
+    .unwrap();
+    assert!(resp.errors.is_some());
+}
+
+#[actix_web::test]
+async fn playing_rules_variants() {
+    let context = Model::test().await.unwrap();
+    let service_config = service::configure(false, context).await.unwrap();
+    let app = App::new().configure(service_config);
+    let app = test::init_service(app).await;
+
+    let config = json!({ "hand_size": 3, "first_player": { "seat": 1 } });
+    let (game_id, [player1, player2]) = start_game(&app, config).await;
+
+    assert!(end_turn(&app, &game_id, &player1).await.errors.is_some());
+    let resp = end_turn(&app, &game_id, &player2).await;
+    assert_eq!(resp.errors, None);
+
+    let view: PlayerView = resp.data("game.apply").unwrap();
+    assert_eq!(view.active.seat(), 0);
+    for seat in &view.players {
+        assert_eq!(seat.hand_size, 3);
+    }
+}
+
+/// Takes back the player's actions of the current turn
+async fn undo<S, B>(app: &S, game_id: &str, player: &TestPlayer) -> GraphQLResp
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody,
+{
+    gql(r#"mutation($id: GameId!) {
+            game {
+                undo(gameId: $id)
+            }
+        }"#)
+    .variables(json!({ "id": game_id }))
+    .adhoc(&player.token)
+    .call(app)
+    .await
+    .unwrap()
+}
+
+#[actix_web::test]
+async fn undoing_actions() {
+    let context = Model::test().await.unwrap();
+    let service_config = service::configure(false, context).await.unwrap();
+    let app = App::new().configure(service_config);
+    let app = test::init_service(app).await;
+
+    let config = json!({ "first_player": { "seat": 0 } });
+    let (game_id, [player1, player2]) = start_game(&app, config).await;
+    assert!(undo(&app, &game_id, &player1).await.errors.is_some());
+
+    let resp = gql(r#"mutation($id: GameId!, $action: JSON!) {
+            game {
+                apply(gameId: $id, action: $action)
+            }
+        }"#)
+    .variables(json!({ "id": game_id, "action": "EndPhase" }))
+    .adhoc(&player1.token)
+    .call(&app)
+    .await
+    .unwrap();
+    assert_eq!(resp.errors, None);
+    let view: PlayerView = resp.data("game.apply").unwrap();
+    assert!(view.legal_actions.contains(&Action::Undo));
+
+    assert!(undo(&app, &game_id, &player2).await.errors.is_some());
+    let resp = undo(&app, &game_id, &player1).await;
+    assert_eq!(resp.errors, None);
+    let view: PlayerView = resp.data("game.undo").unwrap();
+    assert_eq!(view.phase, game::Phase::Action);
+    assert!(!view.legal_actions.contains(&Action::Undo));
+
+    // Turn change is irreversible
+    assert_eq!(end_turn(&app, &game_id, &player1).await.errors, None);
+    assert!(undo(&app, &game_id, &player1).await.errors.is_some());
+}
+
+#[actix_web::test]
+async fn reading_game_log() {
+    let context = Model::test().await.unwrap();
+    let service_config = service::configure(false, context).await.unwrap();
+    let app = App::new().configure(service_config);
+    let app = test::init_service(app).await;
+
+    let config = json!({ "first_player": { "seat": 0 } });
+    let (game_id, [player1, player2]) = start_game(&app, config).await;
+    assert_eq!(end_turn(&app, &game_id, &player1).await.errors, None);
+
+    let query = r#"query($id: GameId!, $offset: Int, $limit: Int) {
+            game(id: $id) {
+                log(offset: $offset, limit: $limit) {
+                    total
+                    entries
+                }
+            }
+        }"#;
+
+    let resp = gql(query)
+        .variables(json!({ "id": game_id, "offset": 1, "limit": 1 }))
+        .adhoc(&player2.token)
+        .call(&app)
+        .await
+        .unwrap();
+    assert_eq!(resp.errors, None);
+    let total: usize = resp.data("game.log.total").unwrap();
+    let entries: Vec<LogEntry> = resp.data("game.log.entries").unwrap();
+    assert_eq!(total, 3);
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].text, "player1 ended their turn and drew 5 cards");
+
+    // Player sees their own cards
+    let resp = gql(query)
+        .variables(json!({ "id": game_id, "offset": 1, "limit": 10 }))
+        .adhoc(&player1.token)
+        .call(&app)
+        .await
+        .unwrap();
+    assert_eq!(resp.errors, None);
+    let entries: Vec<LogEntry> = resp.data("game.log.entries").unwrap();
+    assert_eq!(entries.len(), 2);
+    assert!(entries[0].text.contains("drew 5 cards ("));
+    assert_eq!(entries[1].text, "player2 started turn 2");
+}
+
+#[actix_web::test]
+async fn paging_long_game_log() {
+    let context = Model::test().await.unwrap();
+    let service_config = service::configure(false, context).await.unwrap();
+    let app = App::new().configure(service_config);
+    let app = test::init_service(app).await;
+
+    let config = json!({ "first_player": { "seat": 0 } });
+    let (game_id, players) = start_game(&app, config).await;
+    // Every turn is told in at least two sentences: ending it and starting the next one
+    for turn in 0..MAX_LOG_PAGE / 2 + 1 {
+        let resp = end_turn(&app, &game_id, &players[turn % 2]).await;
+        assert_eq!(resp.errors, None);
+    }
+
+    let query = r#"query($id: GameId!, $offset: Int, $limit: Int) {
+            game(id: $id) {
+                log(offset: $offset, limit: $limit) {
+                    total
+                    entries
+                }
+            }
+        }"#;
+    let page = async |offset: usize, limit: usize| {
+        let resp = gql(query)
+            .variables(json!({ "id": game_id, "offset": offset, "limit": limit }))
+            .call(&app)
+            .await
+            .unwrap();
+        assert_eq!(resp.errors, None);
+        let total: usize = resp.data("game.log.total").unwrap();
+        let entries: Vec<LogEntry> = resp.data("game.log.entries").unwrap();
+        (total, entries)
+    };
+
+    // Pages are capped, however many entries are asked for
+    let (total, entries) = page(0, 1000).await;
+    assert!(total > MAX_LOG_PAGE);
+    assert_eq!(entries.len(), MAX_LOG_PAGE);
+
+    let (_, rest) = page(MAX_LOG_PAGE, 1000).await;
+    assert_eq!(rest.len(), total - MAX_LOG_PAGE);
+    assert!(rest[0].event > entries[MAX_LOG_PAGE - 1].event);
+
+    let (_, last) = page(total - 1, 10).await;
+    assert_eq!(last, rest[rest.len() - 1..]);
+
+    let (_, past) = page(total, 10).await;
+    assert_eq!(past, []);
+}
+
+#[actix_web::test]
+async fn playing_in_teams() {
+    let context = Model::test().await.unwrap();
+    let service_config = service::configure(false, context).await.unwrap();
+    let app = App::new().configure(service_config);
+    let app = test::init_service(app).await;
+
+    let mut players = vec![];
+    for idx in 0..4 {
+        let resp = gql(r#"mutation($name: String!) {
+                users {
+                    createAdhoc(nickname: $name) {
+                        token
+                    }
+                }
+            }"#)
+        .variables(json!({ "name": format!("player{idx}") }))
+        .call(&app)
+        .await
+        .unwrap();
+
+        assert_eq!(resp.errors, None);
+        players.push(TestPlayer {
+            token: resp.data("users.createAdhoc.token").unwrap(),
+        });
+    }
+
+    let resp = gql(r#"mutation($config: JSON) {
+            lobby {
+                createGame(seats: 4, config: $config)
+            }
+        }"#)
+    .variables(json!({ "config": { "teams": { "shared": ["hand"] } } }))
+    .adhoc(&players[0].token)
+    .call(&app)
+    .await
+    .unwrap();
+
+    assert_eq!(resp.errors, None);
+    let game_id: String = resp.data("lobby.createGame").unwrap();
+
+    let join = |player: &TestPlayer, team: Option<usize>| {
+        gql(r#"mutation($id: GameId!, $team: Int) {
+                lobby {
+                    joinGame(gameId: $id, team: $team)
+                }
+            }"#)
+        .variables(json!({ "id": game_id, "team": team }))
+        .adhoc(&player.token)
+    };
+
+    let resp = join(&players[0], None).call(&app).await.unwrap();
+    assert!(resp.errors.is_some());
+    let resp = join(&players[0], Some(4)).call(&app).await.unwrap();
+    assert!(resp.errors.is_some());
+
+    for (player, team) in players.iter().zip([0, 0, 1, 1]) {
+        let resp = join(player, Some(team)).call(&app).await.unwrap();
+        assert_eq!(resp.errors, None);
+    }
+
+    let resp = gql(r#"mutation($id: GameId!) {
+            lobby {
+                chooseTeam(gameId: $id, team: 0)
+            }
+        }"#)
+    .variables(json!({ "id": game_id }))
+    .adhoc(&players[3].token)
+    .call(&app)
+    .await
+    .unwrap();
+    assert_eq!(resp.errors, None);
+
+    let resp = gql(r#"mutation($id: GameId!) {
+            lobby {
+                chooseTeam(gameId: $id, team: 1)
+            }
+        }"#)
+    .variables(json!({ "id": game_id }))
+    .adhoc(&players[1].token)
+    .call(&app)
+    .await
+    .unwrap();
+    assert_eq!(resp.errors, None);
+
+    let resp = gql(r#"query($id: GameId!) {
+            lobby(id: $id) {
+                teams
+            }
+        }"#)
+    .variables(json!({ "id": game_id }))
+    .call(&app)
+    .await
+    .unwrap();
+    let teams: Vec<usize> = resp.data("lobby.teams").unwrap();
+    assert_eq!(teams, [0, 1, 1, 0]);
+
+    let resp = gql(r#"mutation($id: GameId!) {
+            lobby {
+                startGame(gameId: $id)
+            }
+        }"#)
+    .variables(json!({ "id": game_id }))
+    .adhoc(&players[0].token)
+    .call(&app)
+    .await
+    .unwrap();
+    assert_eq!(resp.errors, None);
+
+    let resp = view(&app, &game_id, &players[0]).await;
+    assert_eq!(resp.errors, None);
+    let view: PlayerView = resp.data("view").unwrap();
+    let visible: Vec<_> = view
+        .players
+        .iter()
+        .map(|seat| seat.hand.is_some())
+        .collect();
+    assert_eq!(visible, [true, false, false, true]);
+    assert_eq!(view.players[3].team, Some(0));
+}